@@ -1,23 +1,44 @@
 use clap::{Parser, Subcommand};
 use console::style;
 use dialoguer::Confirm;
+use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use szdt_cli::block_store::SqliteBlockStore;
+use szdt_cli::chunking::Chunking;
+use szdt_cli::compression::Compression;
 use szdt_cli::config;
-use szdt_cli::file::write_file_deep;
+use szdt_cli::file::{create_file_deep, write_file_deep};
+use szdt_cli::header::parse_header;
 use szdt_cli::key_storage::InsecureKeyStorage;
 use szdt_cli::rand::generate_entropy;
-use szdt_cli::szdt::{Unarchiver, archive};
+use szdt_cli::szdt::{
+    ArchiveBodies, ArchiveOptions, SignOptions, Unarchiver, annotate, archive, countersign, get,
+    import, read_chunk_hashes, read_manifest, read_memo, redact, sign,
+};
+use szdt_cli::time::{format_time, parse_time, parse_time_or_duration};
+use szdt_core::annotate::Annotation;
+use szdt_core::block_store::BlockStore;
+use szdt_core::cbor_seq::CborSeqReader;
+use szdt_core::chunked::{is_chunked, write_chunked_body};
 use szdt_core::contact::Contact;
+use szdt_core::content_encoding::decode_body;
+use szdt_core::did::DidKey;
 use szdt_core::ed25519_key_material::Ed25519KeyMaterial;
+use szdt_core::error::Error as CoreError;
+use szdt_core::hash::HashingReader;
 use szdt_core::link::ToLink;
 use szdt_core::mnemonic::Mnemonic;
 use szdt_core::nickname::Nickname;
 use szdt_core::text::{ELLIPSIS, truncate};
-use szdt_core::time::now;
+use szdt_core::url::SzdtUrl;
+use szdt_core::validation::{
+    ChecksumStatus, SignatureStatus, TimeCheck, TimeStatus, ValidationOptions,
+};
+use szdt_core::witness::{AttestationReport, WitnessPolicy};
 
 /// Shared CLI configuration
 struct Config {
@@ -37,7 +58,7 @@ struct Cli {
 enum Commands {
     #[command(about = "Unpack an .szdt archive")]
     Unarchive {
-        #[arg(help = "Archive file")]
+        #[arg(help = "Archive file, or - to read from stdin")]
         #[arg(value_name = "FILE")]
         file: PathBuf,
         #[arg(
@@ -47,6 +68,19 @@ enum Commands {
             help = "Directory to unpack archive into. Defaults to archive file name."
         )]
         dir: Option<PathBuf>,
+
+        #[arg(help = "Previous versions of an updated archive")]
+        #[arg(
+            long_help = "Previous versions of an updated archive. Bodies that are unchanged since the previous version are read from these archives. Pass the base archive and each update, in order."
+        )]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        base: Vec<PathBuf>,
+
+        #[arg(help = "Validate memos as of this time (RFC 3339 or UNIX timestamp)")]
+        #[arg(long, value_parser = parse_time)]
+        #[arg(value_name = "TIME")]
+        at: Option<u64>,
     },
 
     #[command(about = "Create an .szdt archive from a folder")]
@@ -62,6 +96,263 @@ enum Commands {
         #[arg(short, long)]
         #[arg(value_name = "NICKNAME")]
         sign: String,
+
+        #[arg(help = "Archive file to write. Defaults to folder name.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+
+        #[arg(help = "Previous version of the archive")]
+        #[arg(
+            long_help = "Previous version of the archive. Memos will point to the previous version of each file, and only files that changed since the previous version will be included."
+        )]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        update: Option<PathBuf>,
+
+        #[arg(help = "Embargo archive until this time (duration like 7d, or RFC 3339)")]
+        #[arg(long, value_parser = parse_time_or_duration)]
+        #[arg(value_name = "TIME")]
+        not_before: Option<u64>,
+
+        #[arg(help = "Expire archive at this time (duration like 30d, or RFC 3339)")]
+        #[arg(long, value_parser = parse_time_or_duration)]
+        #[arg(value_name = "TIME")]
+        expires: Option<u64>,
+        #[arg(help = "Additional header to sign on every memo (may be repeated)")]
+        #[arg(
+            long_help = "Additional header to sign on every memo (may be repeated). `key=value` headers have text values. `key:=value` headers have typed values in JSON or CBOR diagnostic notation, e.g. `--header x-tags:='[\"a\", \"b\"]'`."
+        )]
+        #[arg(long = "header", value_parser = parse_header)]
+        #[arg(value_name = "KEY=VALUE")]
+        headers: Vec<(String, cbor4ii::core::Value)>,
+
+        #[arg(help = "Compress file bodies")]
+        #[arg(
+            long_help = "Compress file bodies, marking each memo with a signed content-encoding header. Memo hashes are hashes of the compressed bodies. Bodies are decompressed when unarchiving."
+        )]
+        #[arg(long, value_enum)]
+        #[arg(value_name = "ALGORITHM")]
+        compress: Option<Compression>,
+
+        #[arg(help = "Write each distinct file body once")]
+        #[arg(
+            long_help = "Write each distinct file body once. Memos for files with the same content as an earlier file reference the earlier body by hash."
+        )]
+        #[arg(long)]
+        dedup: bool,
+
+        #[arg(help = "Split large files into content-defined chunks")]
+        #[arg(
+            long_help = "Split files larger than 4 MiB into content-defined chunks. Each distinct chunk is written once, and updates with `--update` only include chunks that changed since the previous version."
+        )]
+        #[arg(long)]
+        chunk: bool,
+    },
+
+    #[command(about = "List the contents of an .szdt archive")]
+    Ls {
+        #[arg(help = "Archive file")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    #[command(about = "Verify the memos in an .szdt archive, reporting every check")]
+    Verify {
+        #[arg(help = "Archive file, or - to read from stdin")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[arg(help = "Validate memos as of this time (RFC 3339 or UNIX timestamp)")]
+        #[arg(long, value_parser = parse_time)]
+        #[arg(value_name = "TIME")]
+        at: Option<u64>,
+
+        #[arg(help = "Trusted witness DID (may be repeated)")]
+        #[arg(
+            long_help = "Trusted witness DID (may be repeated). When given, memos must have valid witness signatures from at least `--threshold` of these witnesses."
+        )]
+        #[arg(long = "witness", value_parser = DidKey::parse)]
+        #[arg(value_name = "DID")]
+        witnesses: Vec<DidKey>,
+
+        #[arg(help = "Number of trusted witnesses required. Defaults to all of them.")]
+        #[arg(long, requires = "witnesses")]
+        #[arg(value_name = "K")]
+        threshold: Option<usize>,
+
+        #[arg(help = "Required co-signer DID (may be repeated)")]
+        #[arg(
+            long_help = "Required co-signer DID (may be repeated). When given, memos must have a valid co-signature from each of these co-signers. Other co-signatures that don't verify are reported, but don't make a memo invalid."
+        )]
+        #[arg(long = "cosigner", value_parser = DidKey::parse)]
+        #[arg(value_name = "DID")]
+        cosigners: Vec<DidKey>,
+    },
+
+    #[command(about = "Sign a file, creating a memo")]
+    Sign {
+        #[arg(help = "File to sign")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[arg(help = "Key to sign file with")]
+        #[arg(
+            long_help = "Nickname of the key to sign the file with. You can generate a signing key with `szdt key create`."
+        )]
+        #[arg(short, long)]
+        #[arg(value_name = "NICKNAME")]
+        sign: String,
+
+        #[arg(help = "File to write. Defaults to FILE.szdt, or FILE.memo when detached.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+
+        #[arg(help = "Additional header to sign (may be repeated)")]
+        #[arg(
+            long_help = "Additional header to sign (may be repeated). `key=value` headers have text values. `key:=value` headers have typed values in JSON or CBOR diagnostic notation, e.g. `--header x-tags:='[\"a\", \"b\"]'`."
+        )]
+        #[arg(long = "header", value_parser = parse_header)]
+        #[arg(value_name = "KEY=VALUE")]
+        headers: Vec<(String, cbor4ii::core::Value)>,
+
+        #[arg(help = "Content type of the file. Defaults to a guess from the file extension.")]
+        #[arg(long)]
+        #[arg(value_name = "MIME")]
+        content_type: Option<String>,
+
+        #[arg(help = "Write the memo without the file body")]
+        #[arg(long)]
+        detached: bool,
+
+        #[arg(help = "Embargo memo until this time (duration like 7d, or RFC 3339)")]
+        #[arg(long, value_parser = parse_time_or_duration)]
+        #[arg(value_name = "TIME")]
+        not_before: Option<u64>,
+
+        #[arg(help = "Expire memo at this time (duration like 30d, or RFC 3339)")]
+        #[arg(long, value_parser = parse_time_or_duration)]
+        #[arg(value_name = "TIME")]
+        expires: Option<u64>,
+    },
+
+    #[command(about = "Add or remove unprotected headers without re-signing")]
+    Annotate {
+        #[arg(help = "Archive file")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[arg(help = "Unprotected header to set (may be repeated)")]
+        #[arg(
+            long_help = "Unprotected header to set (may be repeated). `key=value` headers have text values. `key:=value` headers have typed values in JSON or CBOR diagnostic notation. Unprotected headers aren't signed, so annotating a memo doesn't invalidate its signature."
+        )]
+        #[arg(long, value_parser = parse_header)]
+        #[arg(value_name = "KEY=VALUE")]
+        set: Vec<(String, cbor4ii::core::Value)>,
+
+        #[arg(help = "Unprotected header to remove (may be repeated)")]
+        #[arg(long)]
+        #[arg(value_name = "KEY")]
+        remove: Vec<String>,
+
+        #[arg(
+            help = "Only annotate memos with this path (may be repeated). Defaults to all memos."
+        )]
+        #[arg(short, long)]
+        #[arg(value_name = "PATH")]
+        path: Vec<String>,
+
+        #[arg(help = "File to write. Defaults to rewriting FILE in place.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "Countersign an archive with a witness signature")]
+    Countersign {
+        #[arg(help = "Archive file")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[arg(help = "Key to countersign archive with")]
+        #[arg(
+            long_help = "Nickname of the key to countersign the archive with. Adds a witness signature to every memo, preserving the original issuer signatures."
+        )]
+        #[arg(short, long)]
+        #[arg(value_name = "NICKNAME")]
+        sign: String,
+
+        #[arg(help = "File to write. Defaults to rewriting FILE in place.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "Remove file bodies from an archive, keeping their signed memos")]
+    Redact {
+        #[arg(help = "Archive file")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[arg(help = "Path of file to redact (may be repeated)")]
+        #[arg(
+            long_help = "Path of file to redact (may be repeated). The file's memo stays in the archive, so the archive still proves what was published, but the file body is removed."
+        )]
+        #[arg(short, long, required = true)]
+        #[arg(value_name = "PATH")]
+        path: Vec<String>,
+
+        #[arg(help = "File to write. Defaults to rewriting FILE in place.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "Import archives into the local block store")]
+    #[command(
+        long_about = "Import every block in one or more archives into the local block store, at ~/.szdt/blocks.sqlite. Blocks are stored by content address, so blocks shared between archives are stored once."
+    )]
+    Import {
+        #[arg(help = "Archive files")]
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+    },
+
+    #[command(about = "Check that a file is strictly conformant CBOR/c")]
+    #[command(
+        long_about = "Check that every block in a file is strictly conformant CBOR/c: definite lengths, shortest integer and float encodings, sorted and unique map keys, and no null memo headers. Reports the byte offset and rule of the first violation."
+    )]
+    Check {
+        #[arg(help = "File to check")]
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    #[command(about = "Get a file from an archive by szdt: URL")]
+    #[command(
+        long_about = "Get a file from an archive by szdt: URL. URLs may name a hash (szdt:<hash>), a hash and path (szdt:<hash>/<path>), or an issuer and path (szdt:did:key:<key>/<path>). If several versions match, the latest with a valid memo is used. Writes the file to stdout unless --out is given."
+    )]
+    Get {
+        #[arg(help = "szdt: URL of the file")]
+        #[arg(value_name = "URL")]
+        url: String,
+
+        #[arg(help = "Archive to resolve the URL against")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        from: PathBuf,
+
+        #[arg(help = "File to write. Defaults to stdout.")]
+        #[arg(short, long)]
+        #[arg(value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "Work with individual memos")]
+    Memo {
+        #[command(subcommand)]
+        command: MemoCommands,
     },
 
     #[command(about = "Create and manage signing keys")]
@@ -71,6 +362,25 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum MemoCommands {
+    #[command(about = "Verify a memo against its body")]
+    Verify {
+        #[arg(help = "Memo file, either detached or followed by its body")]
+        #[arg(value_name = "MEMO")]
+        memo: PathBuf,
+
+        #[arg(help = "Body file. Required when the memo is detached.")]
+        #[arg(value_name = "BODY")]
+        body: Option<PathBuf>,
+
+        #[arg(help = "Validate memo as of this time (RFC 3339 or UNIX timestamp)")]
+        #[arg(long, value_parser = parse_time)]
+        #[arg(value_name = "TIME")]
+        at: Option<u64>,
+    },
+}
+
 #[derive(Subcommand)]
 enum KeyCommands {
     #[command(about = "Create a new keypair")]
@@ -91,21 +401,39 @@ enum KeyCommands {
     },
 }
 
-fn archive_cmd(config: &Config, dir: &Path, nickname: &str) {
+fn archive_cmd(
+    config: &Config,
+    dir: &Path,
+    nickname: &str,
+    out: Option<PathBuf>,
+    update: Option<PathBuf>,
+    options: ArchiveOptions,
+) {
     let default_file_name = OsStr::new("archive");
 
-    let file_name =
-        PathBuf::from(dir.file_stem().unwrap_or(default_file_name)).with_extension("szdt");
+    let file_name = out.unwrap_or_else(|| {
+        PathBuf::from(dir.file_stem().unwrap_or(default_file_name)).with_extension("szdt")
+    });
 
-    let nickname = Nickname::parse(nickname).expect("Invalid nickname");
+    if update.as_ref() == Some(&file_name) {
+        panic!(
+            "Can't overwrite the previous version of the archive. Tip: choose a different file name with `--out`."
+        );
+    }
 
-    let contact = config
-        .key_storage
-        .contact(&nickname)
-        .expect("Unable to access contacts")
-        .expect("No contact with that nickname. Tip: create a key using `szdt key create`.");
+    let contact = signing_contact(config, nickname);
 
-    let archive_receipt = archive(dir, &file_name, &contact).expect("Unable to create archive");
+    let options = match update {
+        Some(update) => ArchiveOptions {
+            prev: Some(read_manifest(&update).expect("Unable to read previous archive")),
+            prev_chunks: read_chunk_hashes(&update).expect("Unable to read previous archive"),
+            ..options
+        },
+        None => options,
+    };
+
+    let archive_receipt =
+        archive(dir, &file_name, &contact, &options).expect("Unable to create archive");
 
     println!("{:<12} {}", "Archive:", file_name.display());
     println!(
@@ -114,6 +442,13 @@ fn archive_cmd(config: &Config, dir: &Path, nickname: &str) {
         style(contact.nickname).bold().cyan(),
         style(format!("<{}>", contact.did)).cyan()
     );
+    if let Some(memo) = archive_receipt.manifest.first() {
+        println!(
+            "{:<12} {}",
+            "Valid:",
+            format_validity_window(memo.protected.nbf, memo.protected.exp)
+        );
+    }
     println!();
     println!("{:<32} | {:<52}", "File", "Hash");
     for memo in &archive_receipt.manifest {
@@ -128,24 +463,359 @@ fn archive_cmd(config: &Config, dir: &Path, nickname: &str) {
     println!("Archived {} files", &archive_receipt.manifest.len());
 }
 
-fn unarchive_cmd(config: &mut Config, dir: Option<PathBuf>, file_path: PathBuf) {
+/// Look up the contact to sign with by nickname
+fn signing_contact(config: &Config, nickname: &str) -> Contact {
+    let nickname = Nickname::parse(nickname).expect("Invalid nickname");
+    config
+        .key_storage
+        .contact(&nickname)
+        .expect("Unable to access contacts")
+        .expect("No contact with that nickname. Tip: create a key using `szdt key create`.")
+}
+
+fn sign_cmd(
+    config: &Config,
+    file: &Path,
+    nickname: &str,
+    out: Option<PathBuf>,
+    options: &SignOptions,
+) {
+    let extension = if options.detached { "memo" } else { "szdt" };
+    let out_file = out.unwrap_or_else(|| {
+        let mut file_name = file.as_os_str().to_owned();
+        file_name.push(".");
+        file_name.push(extension);
+        PathBuf::from(file_name)
+    });
+
+    let contact = signing_contact(config, nickname);
+    let memo = sign(file, &out_file, &contact, options).expect("Unable to sign file");
+
+    println!("{:<12} {}", "Memo:", out_file.display());
+    println!(
+        "{:<12} {} {}",
+        "Issuer:",
+        style(contact.nickname).bold().cyan(),
+        style(format!("<{}>", contact.did)).cyan()
+    );
+    println!("{:<12} {}", "Hash:", style(memo.protected.src).green());
+    println!(
+        "{:<12} {}",
+        "Valid:",
+        format_validity_window(memo.protected.nbf, memo.protected.exp)
+    );
+    if options.detached {
+        println!("{:<12} {}", "Body:", style("detached").yellow());
+    }
+}
+
+fn verify_memo_cmd(memo_file: &Path, body_file: Option<PathBuf>, at: Option<u64>) {
+    let (memo, bytes) = read_memo(memo_file).expect("Unable to read memo");
+    let hash = match body_file {
+        // Stream detached bodies through the hasher, rather than reading them
+        // into memory.
+        Some(body_file) => {
+            let file = File::open(&body_file).expect("Unable to open body file");
+            let len = file.metadata().expect("Unable to read body file").len();
+            let mut reader = HashingReader::for_body(BufReader::new(file), len);
+            std::io::copy(&mut reader, &mut std::io::sink()).expect("Unable to read body file");
+            reader.hash().expect("Unable to hash body file")
+        }
+        None => bytes
+            .expect("Memo is detached. Tip: pass the body file to verify against.")
+            .to_link()
+            .expect("Unable to hash body bytes"),
+    };
+
+    let validation_options = ValidationOptions {
+        time: Some(at.unwrap_or_else(szdt_core::time::now)),
+        ..Default::default()
+    };
+    let iss = memo
+        .protected
+        .iss
+        .as_ref()
+        .map(|did| did.to_string())
+        .unwrap_or("None".to_string());
+    println!("{:<12} {}", "Issuer:", style(format!("<{iss}>")).cyan());
+    println!("{:<12} {}", "Hash:", style(memo.protected.src).green());
+
+    let mut is_valid = true;
+    match memo.validate(&validation_options) {
+        Ok(()) => println!("{:<12} {}", "Memo:", style("valid").green()),
+        Err(err) => {
+            println!(
+                "{:<12} {}",
+                "Memo:",
+                style(format!("invalid ({err})")).red()
+            );
+            is_valid = false;
+        }
+    }
+    match memo.checksum(&hash) {
+        Ok(()) => println!("{:<12} {}", "Checksum:", style("valid").green()),
+        Err(err) => {
+            println!(
+                "{:<12} {}",
+                "Checksum:",
+                style(format!("invalid ({err})")).red()
+            );
+            is_valid = false;
+        }
+    }
+
+    if !is_valid {
+        std::process::exit(1);
+    }
+}
+
+fn annotate_cmd(file: &Path, out: Option<PathBuf>, annotation: &Annotation, paths: &[String]) {
+    if annotation.is_empty() {
+        panic!("Nothing to annotate. Tip: pass headers with `--set` or `--remove`.");
+    }
+    let out_file = out.unwrap_or_else(|| file.to_path_buf());
+    let count = annotate(file, &out_file, annotation, paths).expect("Unable to annotate archive");
+    println!("Annotated {count} memos in {}", out_file.display());
+}
+
+fn countersign_cmd(config: &Config, file: &Path, nickname: &str, out: Option<PathBuf>) {
+    let contact = signing_contact(config, nickname);
+    let out_file = out.unwrap_or_else(|| file.to_path_buf());
+    let count = countersign(file, &out_file, &contact).expect("Unable to countersign archive");
+    println!(
+        "Countersigned {count} memos in {} as {} {}",
+        out_file.display(),
+        style(contact.nickname).bold().cyan(),
+        style(format!("<{}>", contact.did)).cyan()
+    );
+}
+
+fn redact_cmd(file: &Path, paths: &[String], out: Option<PathBuf>) {
+    let out_file = out.unwrap_or_else(|| file.to_path_buf());
+    let count = redact(file, &out_file, paths).expect("Unable to redact archive");
+    println!("Redacted {count} bodies in {}", out_file.display());
+}
+
+fn import_cmd(files: &[PathBuf]) {
+    let blocks_file = config::blocks_file().expect("Unable to locate block store");
+    let mut store = SqliteBlockStore::new(&blocks_file).expect("Unable to open block store");
+    for file in files {
+        let before = store.iter().expect("Unable to read block store").count();
+        let hashes = import(file, &mut store).expect("Unable to import archive");
+        let after = store.iter().expect("Unable to read block store").count();
+        println!(
+            "Imported {} blocks from {} ({} new)",
+            hashes.len(),
+            style(file.display()).bold(),
+            after - before
+        );
+    }
+    println!("Block store: {}", blocks_file.display());
+}
+
+fn check_cmd(file: &Path) {
+    let file_reader = BufReader::new(File::open(file).expect("Unable to open file"));
+    // Read block by block, so the limits apply to every block, and only one
+    // block is in memory at a time.
+    let mut reader = CborSeqReader::new(file_reader).with_strict(true);
+    let mut count = 0;
+    loop {
+        let offset = reader.position();
+        match reader.read_block_bytes() {
+            Ok(_) => count += 1,
+            Err(CoreError::Eof) => break,
+            Err(err) => {
+                let (offset, label, detail) = match err {
+                    CoreError::Conformance(violation) => {
+                        (violation.offset, "Rule:", violation.rule.to_string())
+                    }
+                    err => (offset, "Error:", err.to_string()),
+                };
+                println!("{:<12} {}", "Block:", count);
+                println!("{:<12} {}", "Offset:", offset);
+                println!("{:<12} {}", label, style(detail).red());
+                println!();
+                println!(
+                    "{}",
+                    style(format!("{} is not conformant CBOR/c", file.display())).red()
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+    println!(
+        "{}",
+        style(format!("Checked {count} blocks. All conform to CBOR/c.")).green()
+    );
+}
+
+fn get_cmd(url: &str, archive_file: &Path, out: Option<PathBuf>) {
+    let url = SzdtUrl::parse(url).expect("Unable to parse URL");
+    let validation_options = ValidationOptions {
+        time: Some(szdt_core::time::now()),
+        ..Default::default()
+    };
+    let (memo, bytes) = match get(&url, archive_file, &validation_options) {
+        Ok(found) => found,
+        Err(err) => {
+            eprintln!("{}", style(err).red());
+            std::process::exit(1);
+        }
+    };
+    let Some(bytes) = bytes else {
+        eprintln!("{}", style("File body is absent from archive").red());
+        std::process::exit(1);
+    };
+    match out {
+        Some(out) => {
+            write_file_deep(&out, &bytes.0).expect("Unable to write file");
+            let path = memo.protected.path.as_deref().unwrap_or("None");
+            println!("{:<12} {}", "Path:", path);
+            println!("{:<12} {}", "Hash:", style(memo.protected.src).green());
+            println!("{:<12} {}", "Written:", out.display());
+        }
+        None => std::io::stdout()
+            .write_all(&bytes.0)
+            .expect("Unable to write to stdout"),
+    }
+}
+
+/// Format the window of time in which a memo is valid
+fn format_validity_window(nbf: Option<u64>, exp: Option<u64>) -> String {
+    let from = nbf.map(format_time).unwrap_or("any time".to_string());
+    let until = exp.map(format_time).unwrap_or("forever".to_string());
+    format!("{from} until {until}")
+}
+
+fn ls_cmd(file_path: &Path) {
+    let file_bufreader = BufReader::new(File::open(file_path).expect("Unable to open file"));
+
+    println!(
+        "{:<32} | {:<52} | {:<7} | {:<20} | {:<20}",
+        "File", "Hash", "Body", "Not before", "Expires"
+    );
+    let mut count = 0;
+    let mut absent_count = 0;
+    let mut partial_count = 0;
+    let mut unarchiver = Unarchiver::new(file_bufreader);
+    while let Some(result) = unarchiver.next() {
+        let (memo, bytes) = result.expect("Unable to read archive blocks");
+        let body = match bytes {
+            Some(bytes) => {
+                let missing_chunks = unarchiver
+                    .missing_chunks(&memo, &bytes)
+                    .expect("Invalid chunked body");
+                if missing_chunks.is_empty() {
+                    style(format!("{:<7}", "present")).green()
+                } else {
+                    partial_count += 1;
+                    style(format!("{:<7}", "partial")).yellow()
+                }
+            }
+            None => {
+                absent_count += 1;
+                style(format!("{:<7}", "absent")).yellow()
+            }
+        };
+        let path = memo.protected.path.as_deref().unwrap_or("None");
+        let nbf = memo.protected.nbf.map(format_time).unwrap_or_default();
+        let exp = memo.protected.exp.map(format_time).unwrap_or_default();
+        println!(
+            "{:<32} | {:<52} | {} | {:<20} | {:<20}",
+            truncate(path, 32, ELLIPSIS),
+            style(memo.protected.src).green(),
+            body,
+            nbf,
+            exp
+        );
+        count += 1;
+    }
+    println!();
+    println!("{count} files");
+    if absent_count > 0 {
+        println!(
+            "{absent_count} bodies absent (redacted, or omitted from an update to a previous version)"
+        );
+    }
+    if partial_count > 0 {
+        println!(
+            "{partial_count} chunked bodies missing chunks (omitted from an update to a previous version)"
+        );
+    }
+}
+
+fn unarchive_cmd(
+    config: &mut Config,
+    dir: Option<PathBuf>,
+    file_path: PathBuf,
+    base: Vec<PathBuf>,
+    at: Option<u64>,
+) {
     // Create a folder named after the file path
     let archive_dir = match dir {
         Some(dir) => dir,
+        None if is_stdin(&file_path) => "archive".into(),
         None => file_path
             .file_stem()
             .map(|p| p.into())
             .unwrap_or("archive".into()),
     };
 
-    let file_bufreader = BufReader::new(File::open(&file_path).expect("Unable to open file"));
+    if is_stdin(&file_path) {
+        let unarchiver = Unarchiver::from_stream(io::stdin().lock());
+        unarchive_all(config, unarchiver, &archive_dir, &base, at);
+    } else {
+        let file_bufreader = BufReader::new(File::open(&file_path).expect("Unable to open file"));
+        unarchive_all(
+            config,
+            Unarchiver::new(file_bufreader),
+            &archive_dir,
+            &base,
+            at,
+        );
+    }
+}
+
+/// Unpack every file in an archive into `archive_dir`
+fn unarchive_all<R: BufRead>(
+    config: &mut Config,
+    mut unarchiver: Unarchiver<R>,
+    archive_dir: &Path,
+    base: &[PathBuf],
+    at: Option<u64>,
+) {
+    // Bodies and chunks omitted from this archive are read out of previous
+    // versions
+    let mut base_bodies = ArchiveBodies::open(base).expect("Unable to read previous archives");
 
-    let now_time = now();
+    let validation_options = ValidationOptions {
+        time: Some(at.unwrap_or_else(szdt_core::time::now)),
+        ..Default::default()
+    };
 
     let mut count = 0;
-    for result in Unarchiver::new(file_bufreader) {
+    let mut absent_count = 0;
+    while let Some(result) = unarchiver.next() {
         let (memo, bytes) = result.expect("Unable to read archive blocks");
 
+        // Bodies may be in this archive, or in a previous version
+        let src = memo.protected.src;
+        let bytes = match bytes {
+            Some(bytes) => Some(bytes),
+            None => base_bodies
+                .read_body(&src)
+                .expect("Unable to read previous archives"),
+        };
+        let Some(bytes) = bytes else {
+            println!(
+                "Body absent for {}. Skipping",
+                style(memo.protected.path.as_deref().unwrap_or("memo")).bold()
+            );
+            println!();
+            absent_count += 1;
+            continue;
+        };
+
         let Some(iss) = memo.protected.iss.as_ref() else {
             println!("Unsigned memo. Skipping");
             continue;
@@ -205,7 +875,7 @@ fn unarchive_cmd(config: &mut Config, dir: Option<PathBuf>, file_path: PathBuf)
         };
 
         // Check sig and expiries
-        memo.validate(Some(now_time))
+        memo.validate(&validation_options)
             .expect("Invalid memo signature");
 
         // Check checksum
@@ -214,10 +884,26 @@ fn unarchive_cmd(config: &mut Config, dir: Option<PathBuf>, file_path: PathBuf)
             .expect("Body bytes don't match checksum");
 
         // Use the path in the headers, or else the hash if no path given
-        let file_path = memo.protected.path.clone().unwrap_or(hash.to_string());
+        let file_path = memo.protected.path.clone().unwrap_or(src.to_string());
         let path = archive_dir.join(&file_path);
-        let bytes = bytes.into_inner();
-        write_file_deep(&path, &bytes).expect("Unable to write file");
+        let limits = *unarchiver.limits();
+        if is_chunked(&memo).expect("Invalid chunked header") {
+            // Chunks are read by hash, from this archive or a previous
+            // version, and streamed to the file one at a time
+            let mut writer =
+                BufWriter::new(create_file_deep(&path).expect("Unable to create file"));
+            write_chunked_body(&memo, &bytes, &limits, &mut writer, |hash| match unarchiver
+                .read_body(hash)?
+            {
+                Some(chunk) => Ok(Some(chunk)),
+                None => base_bodies.read_body(hash),
+            })
+            .expect("Unable to read chunked body");
+            writer.flush().expect("Unable to write file");
+        } else {
+            let bytes = decode_body(&memo, bytes, &limits).expect("Unable to decode body");
+            write_file_deep(&path, &bytes.0).expect("Unable to write file");
+        }
 
         println!("Path: {}", style(&file_path).bold());
         println!("Hash: {}", style(memo.protected.src.to_string()).green());
@@ -231,6 +917,166 @@ fn unarchive_cmd(config: &mut Config, dir: Option<PathBuf>, file_path: PathBuf)
     }
 
     println!("Unarchived {} files to {}", count, archive_dir.display());
+    if absent_count > 0 {
+        println!(
+            "{}",
+            style(format!("{absent_count} bodies absent. Bodies may have been redacted. Tip: if this archive is an update, pass previous versions with `--base`.")).red()
+        );
+        std::process::exit(1);
+    }
+}
+
+fn style_time_check(check: &TimeCheck) -> String {
+    match (check.status, check.timestamp) {
+        (TimeStatus::Valid, Some(timestamp)) => style(format!("valid ({timestamp})")).green(),
+        (TimeStatus::Invalid, Some(timestamp)) => style(format!("invalid ({timestamp})")).red(),
+        _ => style("none".to_string()).dim(),
+    }
+    .to_string()
+}
+
+/// Format the DIDs that validly signed, and those that didn't
+fn format_attestations(report: &AttestationReport) -> String {
+    let signed = report
+        .signed
+        .iter()
+        .map(|did| style(format!("<{did}>")).green().to_string());
+    let invalid = report.invalid.iter().map(|invalid| {
+        style(format!("<{}> (invalid)", invalid.iss))
+            .red()
+            .to_string()
+    });
+    signed.chain(invalid).collect::<Vec<String>>().join(", ")
+}
+
+fn verify_cmd(file_path: &Path, validation_options: &ValidationOptions) {
+    if is_stdin(file_path) {
+        let unarchiver = Unarchiver::from_stream(io::stdin().lock());
+        verify_all(unarchiver, validation_options);
+    } else {
+        let file_bufreader = BufReader::new(File::open(file_path).expect("Unable to open file"));
+        verify_all(Unarchiver::new(file_bufreader), validation_options);
+    }
+}
+
+/// Verify every memo in an archive, printing a report for each
+fn verify_all<R: BufRead>(mut unarchiver: Unarchiver<R>, validation_options: &ValidationOptions) {
+    let mut count = 0;
+    let mut invalid_count = 0;
+    while let Some(result) = unarchiver.next() {
+        let (memo, bytes) = result.expect("Unable to read archive blocks");
+        let body_hash = bytes
+            .as_ref()
+            .map(|bytes| bytes.to_link().expect("Unable to hash body bytes"));
+        let missing_chunks = match &bytes {
+            Some(bytes) if is_chunked(&memo).expect("Invalid chunked header") => Some(
+                unarchiver
+                    .missing_chunks(&memo, bytes)
+                    .expect("Invalid chunked body"),
+            ),
+            _ => None,
+        };
+        let report = memo.validation_report(validation_options, body_hash.as_ref());
+
+        let signature = match report.signature {
+            SignatureStatus::Valid => style("valid".to_string()).green(),
+            SignatureStatus::Invalid => style(format!(
+                "invalid ({})",
+                report.signature_error.as_deref().unwrap_or("unknown error")
+            ))
+            .red(),
+            SignatureStatus::Unsigned => style("unsigned".to_string()).red(),
+            SignatureStatus::IssuerMissing => style("issuer missing".to_string()).red(),
+        };
+        let checksum = match report.checksum {
+            ChecksumStatus::Valid => style("valid").green(),
+            ChecksumStatus::Invalid => style("invalid").red(),
+            ChecksumStatus::Unchecked => style("unchecked (body absent)").yellow(),
+        };
+        let iss = report
+            .iss
+            .as_ref()
+            .map(|did| did.to_string())
+            .unwrap_or("None".to_string());
+
+        println!(
+            "{:<12} {}",
+            "Path:",
+            style(memo.protected.path.as_deref().unwrap_or("None")).bold()
+        );
+        println!("{:<12} {}", "Hash:", style(memo.protected.src).green());
+        println!("{:<12} {}", "Issuer:", style(format!("<{iss}>")).cyan());
+        println!("{:<12} {}", "Signature:", signature);
+        println!("{:<12} {}", "Not before:", style_time_check(&report.nbf));
+        println!("{:<12} {}", "Expires:", style_time_check(&report.exp));
+        println!("{:<12} {}", "Checksum:", checksum);
+        if let Some(missing_chunks) = missing_chunks {
+            let chunks = match missing_chunks.len() {
+                0 => style("all present".to_string()).green(),
+                n => style(format!("{n} absent")).yellow(),
+            };
+            println!("{:<12} {}", "Chunks:", chunks);
+        }
+        if !memo.unprotected.cosigs.is_empty() {
+            println!(
+                "{:<12} {}",
+                "Cosigners:",
+                format_attestations(&report.cosigs)
+            );
+        }
+        if !report.missing_cosigners.is_empty() {
+            let missing = report
+                .missing_cosigners
+                .iter()
+                .map(|did| format!("<{did}>"))
+                .collect::<Vec<String>>()
+                .join(", ");
+            println!("{:<12} {}", "Missing:", style(missing).red());
+        }
+        if !memo.unprotected.witnesses.is_empty() {
+            println!(
+                "{:<12} {}",
+                "Witnesses:",
+                format_attestations(&report.witnesses)
+            );
+        }
+        if let Some(result) = &report.witness_policy {
+            let policy = format!(
+                "{} of {} required witnesses signed",
+                result.signed.len(),
+                result.threshold
+            );
+            let policy = if result.satisfied {
+                style(policy).green()
+            } else {
+                style(policy).red()
+            };
+            println!("{:<12} {}", "Policy:", policy);
+        }
+        for warning in &report.warnings {
+            println!("{:<12} {}", "Warning:", style(warning).yellow());
+        }
+        println!();
+
+        count += 1;
+        if !report.is_valid() {
+            invalid_count += 1;
+        }
+    }
+
+    println!(
+        "Checked {count} memos at time {}",
+        validation_options.time()
+    );
+    if invalid_count > 0 {
+        println!("{}", style(format!("{invalid_count} invalid memos")).red());
+        std::process::exit(1);
+    }
+}
+
+/// Is the path `-`, meaning stdin?
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
 }
 
 fn create_key_cmd(config: &mut Config, nickname: &str) {
@@ -310,8 +1156,92 @@ fn main() {
 
     let cli = Cli::parse();
     match cli.command {
-        Commands::Archive { dir, sign } => archive_cmd(&config, &dir, &sign),
-        Commands::Unarchive { file, dir } => unarchive_cmd(&mut config, dir, file),
+        Commands::Archive {
+            dir,
+            sign,
+            out,
+            update,
+            not_before,
+            expires,
+            headers,
+            compress,
+            dedup,
+            chunk,
+        } => {
+            let options = ArchiveOptions {
+                prev: None,
+                nbf: not_before,
+                exp: expires,
+                headers,
+                compression: compress,
+                dedup,
+                chunking: chunk.then(Chunking::default),
+                prev_chunks: HashSet::new(),
+            };
+            archive_cmd(&config, &dir, &sign, out, update, options)
+        }
+        Commands::Ls { file } => ls_cmd(&file),
+        Commands::Unarchive {
+            file,
+            dir,
+            base,
+            at,
+        } => unarchive_cmd(&mut config, dir, file, base, at),
+        Commands::Verify {
+            file,
+            at,
+            witnesses,
+            threshold,
+            cosigners,
+        } => {
+            let witness_policy = (!witnesses.is_empty()).then(|| {
+                // Default to requiring every distinct witness
+                let distinct = witnesses.iter().collect::<HashSet<_>>().len();
+                WitnessPolicy::new(threshold.unwrap_or(distinct), witnesses)
+                    .expect("Invalid witness policy")
+            });
+            let options = ValidationOptions {
+                time: Some(at.unwrap_or_else(szdt_core::time::now)),
+                required_cosigners: cosigners,
+                witness_policy,
+                ..Default::default()
+            };
+            verify_cmd(&file, &options)
+        }
+        Commands::Sign {
+            file,
+            sign,
+            out,
+            headers,
+            content_type,
+            detached,
+            not_before,
+            expires,
+        } => {
+            let options = SignOptions {
+                content_type,
+                headers,
+                nbf: not_before,
+                exp: expires,
+                detached,
+            };
+            sign_cmd(&config, &file, &sign, out, &options)
+        }
+        Commands::Annotate {
+            file,
+            set,
+            remove,
+            path,
+            out,
+        } => annotate_cmd(&file, out, &Annotation { set, remove }, &path),
+        Commands::Countersign { file, sign, out } => countersign_cmd(&config, &file, &sign, out),
+        Commands::Redact { file, path, out } => redact_cmd(&file, &path, out),
+        Commands::Import { files } => import_cmd(&files),
+        Commands::Check { file } => check_cmd(&file),
+        Commands::Get { url, from, out } => get_cmd(&url, &from, out),
+        Commands::Memo { command } => match command {
+            MemoCommands::Verify { memo, body, at } => verify_memo_cmd(&memo, body, at),
+        },
         Commands::Key { command } => match command {
             KeyCommands::Create { nickname } => create_key_cmd(&mut config, &nickname),
             KeyCommands::List {} => list_keys_cmd(&config),
@@ -0,0 +1,143 @@
+use crate::db::migrations::migrate;
+use crate::error::Error;
+use rusqlite::{OptionalExtension, params};
+use std::path::Path;
+use szdt_core::block_store::{BlockIter, BlockStore, check_block};
+use szdt_core::error::Error as CoreError;
+use szdt_core::hash::Hash;
+
+fn migration1(tx: &rusqlite::Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS block (
+            hash BLOB PRIMARY KEY,
+            data BLOB NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn store_error(err: rusqlite::Error) -> CoreError {
+    CoreError::BlockStoreError(err.to_string())
+}
+
+/// Block store backed by a SQLite database
+pub struct SqliteBlockStore {
+    db: rusqlite::Connection,
+}
+
+impl SqliteBlockStore {
+    pub fn new(file_path: &Path) -> Result<Self, Error> {
+        let db = rusqlite::Connection::open(file_path)?;
+        Self::from_connection(db)
+    }
+
+    /// Open a block store in an in-memory database
+    pub fn open_in_memory() -> Result<Self, Error> {
+        let db = rusqlite::Connection::open_in_memory()?;
+        Self::from_connection(db)
+    }
+
+    fn from_connection(mut db: rusqlite::Connection) -> Result<Self, Error> {
+        migrate(&mut db, &[migration1])?;
+        Ok(SqliteBlockStore { db })
+    }
+}
+
+impl BlockStore for SqliteBlockStore {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, CoreError> {
+        let block: Option<Vec<u8>> = self
+            .db
+            .query_row(
+                "SELECT data FROM block WHERE hash = ?",
+                params![hash.as_bytes()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(store_error)?;
+        if let Some(block) = &block {
+            check_block(hash, block)?;
+        }
+        Ok(block)
+    }
+
+    fn put(&mut self, block: &[u8]) -> Result<Hash, CoreError> {
+        let hash = Hash::new(block);
+        self.db
+            .execute(
+                "INSERT OR IGNORE INTO block (hash, data) VALUES (?, ?)",
+                params![hash.as_bytes(), block],
+            )
+            .map_err(store_error)?;
+        Ok(hash)
+    }
+
+    fn has(&self, hash: &Hash) -> Result<bool, CoreError> {
+        self.db
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM block WHERE hash = ?)",
+                params![hash.as_bytes()],
+                |row| row.get(0),
+            )
+            .map_err(store_error)
+    }
+
+    fn iter(&self) -> Result<BlockIter<'_>, CoreError> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT hash FROM block ORDER BY hash")
+            .map_err(store_error)?;
+        let hashes: Vec<Vec<u8>> = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(store_error)?
+            .collect::<Result<_, _>>()
+            .map_err(store_error)?;
+        Ok(Box::new(hashes.into_iter().map(|bytes| {
+            Hash::from_slice(&bytes).map_err(|_| {
+                CoreError::BlockStoreError(format!("Invalid hash length: {}", bytes.len()))
+            })
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use szdt_core::block_store::check_store;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sqlite_block_store() {
+        check_store(&mut SqliteBlockStore::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_block_store_detects_corruption() {
+        let mut store = SqliteBlockStore::open_in_memory().unwrap();
+        let hash = store.put_value(&"Hello").unwrap();
+        store
+            .db
+            .execute(
+                "UPDATE block SET data = ? WHERE hash = ?",
+                params![b"corrupt".as_slice(), hash.as_bytes()],
+            )
+            .unwrap();
+        assert!(matches!(
+            store.get(&hash),
+            Err(CoreError::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    fn test_sqlite_block_store_persists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("blocks.sqlite");
+        let hash = {
+            let mut store = SqliteBlockStore::new(&path).unwrap();
+            store.put_value(&"Hello").unwrap()
+        };
+        let store = SqliteBlockStore::new(&path).unwrap();
+        let value: String = store.get_value(&hash).unwrap().unwrap();
+        assert_eq!(value, "Hello");
+    }
+}
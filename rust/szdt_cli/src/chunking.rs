@@ -0,0 +1,124 @@
+//! Content-defined chunking of large file bodies.
+//!
+//! Large files are split into chunks at boundaries chosen by the content
+//! itself (FastCDC), so a small edit only changes the chunks around it. See
+//! `szdt_core::chunked` for how chunked bodies are stored and reassembled.
+use crate::error::Error;
+use fastcdc::v2020::{self, FastCDC, StreamCDC};
+use std::io::{self, Read};
+
+/// Chunk size settings for content-defined chunking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunking {
+    min_size: u32,
+    avg_size: u32,
+    max_size: u32,
+}
+
+impl Chunking {
+    /// Create chunk size settings. Sizes are in bytes, and must be within the
+    /// bounds supported by FastCDC.
+    pub fn new(min_size: u32, avg_size: u32, max_size: u32) -> Result<Self, Error> {
+        let is_valid = (v2020::MINIMUM_MIN..=v2020::MINIMUM_MAX).contains(&min_size)
+            && (v2020::AVERAGE_MIN..=v2020::AVERAGE_MAX).contains(&avg_size)
+            && (v2020::MAXIMUM_MIN..=v2020::MAXIMUM_MAX).contains(&max_size)
+            && min_size <= avg_size
+            && avg_size <= max_size;
+        if !is_valid {
+            return Err(Error::Chunking(format!(
+                "Unsupported chunk sizes: min {min_size}, avg {avg_size}, max {max_size}"
+            )));
+        }
+        Ok(Self {
+            min_size,
+            avg_size,
+            max_size,
+        })
+    }
+
+    /// Max chunk size. Files no larger than this are not chunked.
+    pub fn max_size(&self) -> u32 {
+        self.max_size
+    }
+
+    /// Split bytes into content-defined chunks
+    pub fn chunk<'a>(&self, bytes: &'a [u8]) -> Vec<&'a [u8]> {
+        FastCDC::new(bytes, self.min_size, self.avg_size, self.max_size)
+            .map(|chunk| &bytes[chunk.offset..chunk.offset + chunk.length])
+            .collect()
+    }
+
+    /// Split a stream into content-defined chunks, reading one chunk at a
+    /// time. Gives the same chunks as `chunk`.
+    pub fn stream<R: Read>(&self, reader: R) -> impl Iterator<Item = Result<Vec<u8>, Error>> {
+        StreamCDC::new(reader, self.min_size, self.avg_size, self.max_size)
+            .map(|chunk| Ok(chunk.map_err(io::Error::from)?.data))
+    }
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self {
+            // 256 KiB
+            min_size: 256 * 1024,
+            // 1 MiB
+            avg_size: 1024 * 1024,
+            // 4 MiB
+            max_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::noise;
+
+    fn small_chunking() -> Chunking {
+        Chunking::new(256, 1024, 4096).unwrap()
+    }
+
+    #[test]
+    fn test_chunking_is_content_defined() {
+        let chunking = small_chunking();
+        let data = noise(64 * 1024, 1);
+        let chunks = chunking.chunk(&data);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 4096));
+
+        // Inserting bytes near the start only changes the chunks around the
+        // edit.
+        let mut edited = data.clone();
+        edited.splice(100..100, b"edit".iter().copied());
+        let edited_chunks = chunking.chunk(&edited);
+        let shared = edited_chunks
+            .iter()
+            .filter(|chunk| chunks.contains(chunk))
+            .count();
+        assert!(shared >= chunks.len() - 2);
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        let chunking = small_chunking();
+        let data = noise(64 * 1024, 3);
+        let chunks: Vec<Vec<u8>> = chunking
+            .stream(data.as_slice())
+            .map(|chunk| chunk.unwrap())
+            .collect();
+        assert_eq!(chunks, chunking.chunk(&data));
+    }
+
+    #[test]
+    fn test_invalid_chunk_sizes() {
+        assert!(matches!(
+            Chunking::new(1, 1024, 4096),
+            Err(Error::Chunking(_))
+        ));
+        assert!(matches!(
+            Chunking::new(4096, 1024, 2048),
+            Err(Error::Chunking(_))
+        ));
+    }
+}
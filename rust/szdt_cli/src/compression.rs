@@ -0,0 +1,72 @@
+//! Body compression.
+//!
+//! Compressed bodies are marked with a protected `content-encoding` header.
+//! Decoding lives in core (see `szdt_core::content_encoding`), so that any
+//! reader of an archive can decode its bodies. Compression is only needed
+//! when writing archives, so it lives here.
+use crate::error::Error;
+use clap::ValueEnum;
+use std::io::Write;
+use szdt_core::content_encoding::ContentEncoding;
+
+/// zstd compression level. zstd's default level, which favors speed.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Supported body compression algorithms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    /// The `content-encoding` of bodies compressed with this algorithm
+    pub fn encoding(&self) -> ContentEncoding {
+        match self {
+            Compression::Zstd => ContentEncoding::Zstd,
+            Compression::Gzip => ContentEncoding::Gzip,
+        }
+    }
+
+    /// Name of the algorithm, as used in the `content-encoding` header
+    pub fn name(&self) -> &'static str {
+        self.encoding().name()
+    }
+
+    /// Compress bytes. Output is deterministic, so the same input always
+    /// compresses to the same hash.
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Compression::Zstd => Ok(zstd::encode_all(bytes, ZSTD_LEVEL)?),
+            Compression::Gzip => {
+                // flate2 writes a zero modification time, keeping output
+                // deterministic.
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "All work and no play makes Jack a dull boy. ".repeat(100);
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let compressed = compression.compress(text.as_bytes()).unwrap();
+            assert!(compressed.len() < text.len());
+            // Compression is deterministic
+            assert_eq!(compression.compress(text.as_bytes()).unwrap(), compressed);
+            let decompressed = compression
+                .encoding()
+                .decode(&compressed, u64::MAX)
+                .unwrap();
+            assert_eq!(decompressed, text.as_bytes());
+        }
+    }
+}
@@ -12,3 +12,8 @@ pub fn config_dir() -> Result<PathBuf, Error> {
 pub fn contacts_file() -> Result<PathBuf, Error> {
     Ok(config_dir()?.join("contacts.sqlite"))
 }
+
+/// Returns the path to the SQLite block store.
+pub fn blocks_file() -> Result<PathBuf, Error> {
+    Ok(config_dir()?.join("blocks.sqlite"))
+}
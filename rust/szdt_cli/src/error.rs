@@ -20,6 +20,14 @@ pub enum Error {
     Fs(String),
     #[error("Nickname already taken: {0}")]
     NicknameAlreadyTaken(String),
+    #[error("Archive error: {0}")]
+    Archive(String),
+    #[error("Invalid time: {0}")]
+    Time(String),
+    #[error("Invalid header: {0}")]
+    Header(String),
+    #[error("Chunking error: {0}")]
+    Chunking(String),
 }
 
 impl From<szdt_core::nickname::NicknameError> for Error {
@@ -46,6 +46,14 @@ pub fn write_file_deep<P: AsRef<Path>, C: AsRef<[u8]>>(
     fs::write(path, content)
 }
 
+/// Create a file at a path, creating parent directories if necessary.
+pub fn create_file_deep<P: AsRef<Path>>(path: P) -> Result<fs::File, io::Error> {
+    let path = path.as_ref();
+    let parent = path.parent().ok_or_else(|| io::Error::other("no parent"))?;
+    fs::create_dir_all(parent)?;
+    fs::File::create(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
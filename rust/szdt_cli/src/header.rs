@@ -0,0 +1,324 @@
+use crate::error::Error;
+use cbor4ii::core::Value;
+use szdt_core::header;
+
+/// Parse a header given as `key=value` or `key:=value` on the command line.
+///
+/// `key=value` headers always have text values, so `x-release=1.0` is the
+/// text `"1.0"`. `key:=value` headers have typed values, written in CBOR
+/// diagnostic notation, which is a superset of JSON. For example `1`, `true`,
+/// `"text"`, `["a", "b"]`, `{"a": 1}` and `h'cafe'` (bytes). Typed values
+/// that don't parse are an error. Values are checked against the header
+/// rules (see `szdt_core::header::check_value`), so floats and nulls are
+/// rejected up front.
+pub fn parse_header(text: &str) -> Result<(String, Value), Error> {
+    let Some((key, value)) = text.split_once('=') else {
+        return Err(Error::Header(format!(
+            "Expected header in the form key=value or key:=value. Got: {text}"
+        )));
+    };
+    let (key, value) = match key.strip_suffix(':') {
+        Some(key) => (key.trim(), parse_diagnostic(value)?),
+        None => (key.trim(), Value::Text(value.to_string())),
+    };
+    if key.is_empty() {
+        return Err(Error::Header("Header key must not be empty".to_string()));
+    }
+    header::check_value(key, &value)?;
+    Ok((key.to_string(), value))
+}
+
+/// Parse a value written in CBOR diagnostic notation.
+/// Supports the JSON subset of diagnostic notation, plus hex byte strings
+/// (`h'cafe'`), non-text map keys, and tags (`1(1700000000)`).
+pub fn parse_diagnostic(text: &str) -> Result<Value, Error> {
+    let mut parser = DiagnosticParser { text, pos: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+struct DiagnosticParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl DiagnosticParser<'_> {
+    fn error(&self, msg: &str) -> Error {
+        Error::Header(format!("{msg} at position {}", self.pos))
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() != Some(c) {
+            return Err(self.error(&format!("Expected {c:?}")));
+        }
+        self.pos += c.len_utf8();
+        Ok(())
+    }
+
+    /// Consume the given char if it is next, returning whether it was consumed
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.parse_text().map(Value::Text),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_map(),
+            Some('h') if self.rest().starts_with("h'") => self.parse_hex_bytes(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(_) => self.parse_keyword(),
+            None => Err(self.error("Expected value")),
+        }
+    }
+
+    fn parse_keyword(&mut self) -> Result<Value, Error> {
+        for (keyword, value) in [
+            ("true", Value::Bool(true)),
+            ("false", Value::Bool(false)),
+            ("null", Value::Null),
+        ] {
+            if self.rest().starts_with(keyword) {
+                self.pos += keyword.len();
+                return Ok(value);
+            }
+        }
+        Err(self.error("Unexpected character"))
+    }
+
+    fn parse_number(&mut self) -> Result<Value, Error> {
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(self.rest().len());
+        let number = &self.rest()[..len];
+        let value = if number.contains(['.', 'e', 'E']) {
+            number
+                .parse::<f64>()
+                .map(Value::Float)
+                .map_err(|_| self.error("Invalid float"))?
+        } else {
+            number
+                .parse::<i128>()
+                .map(Value::Integer)
+                .map_err(|_| self.error("Invalid integer"))?
+        };
+        self.pos += len;
+
+        // Tagged value, e.g. `1(1700000000)`
+        match value {
+            Value::Integer(tag) if self.rest().starts_with('(') => {
+                let tag = u64::try_from(tag).map_err(|_| self.error("Invalid tag"))?;
+                self.expect('(')?;
+                let inner = self.parse_value()?;
+                self.expect(')')?;
+                Ok(Value::Tag(tag, Box::new(inner)))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn parse_text(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(text);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("Invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+                    text.push(escaped);
+                }
+                c => text.push(c),
+            }
+        }
+        Err(self.error("Unterminated string"))
+    }
+
+    fn parse_hex_bytes(&mut self) -> Result<Value, Error> {
+        self.pos += "h'".len();
+        let Some(len) = self.rest().find('\'') else {
+            return Err(self.error("Unterminated byte string"));
+        };
+        let hex: String = self.rest()[..len]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(self.error("Invalid hex in byte string"));
+        }
+        if !hex.len().is_multiple_of(2) {
+            return Err(self.error("Byte string must have an even number of hex digits"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| self.error("Invalid hex in byte string"))?;
+        self.pos += len + 1;
+        Ok(Value::Bytes(bytes))
+    }
+
+    fn parse_array(&mut self) -> Result<Value, Error> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        if self.eat(']') {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_map(&mut self) -> Result<Value, Error> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        if self.eat('}') {
+            return Ok(Value::Map(entries));
+        }
+        loop {
+            let key = self.parse_value()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            entries.push((key, value));
+            if self.eat('}') {
+                return Ok(Value::Map(entries));
+            }
+            self.expect(',')?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header() {
+        let (key, value) = parse_header("x-release=1.0").unwrap();
+        assert_eq!(key, "x-release");
+        assert_eq!(value, Value::Text("1.0".to_string()));
+    }
+
+    #[test]
+    fn test_parse_header_value_with_equals() {
+        let (key, value) = parse_header("x-query=a=b").unwrap();
+        assert_eq!(key, "x-query");
+        assert_eq!(value, Value::Text("a=b".to_string()));
+    }
+
+    #[test]
+    fn test_parse_header_typed_values() {
+        let (key, value) = parse_header("x-count:=42").unwrap();
+        assert_eq!(key, "x-count");
+        assert_eq!(value, Value::Integer(42));
+        let (_, value) = parse_header("x-tags:=[\"a\", \"b\"]").unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Text("a".to_string()),
+                Value::Text("b".to_string())
+            ])
+        );
+        let (_, value) = parse_header("x-digest:=h'cafe'").unwrap();
+        assert_eq!(value, Value::Bytes(vec![0xca, 0xfe]));
+        let (_, value) = parse_header("x-id:=\"007\"").unwrap();
+        assert_eq!(value, Value::Text("007".to_string()));
+    }
+
+    #[test]
+    fn test_parse_header_invalid() {
+        assert!(parse_header("x-release").is_err());
+        assert!(parse_header("=1.0").is_err());
+        assert!(parse_header(":=1").is_err());
+        // Typed values must parse
+        assert!(parse_header("x-tags:=[\"a\"").is_err());
+        assert!(parse_header("x-name:=hello").is_err());
+        // Floats can't be encoded in shortest form, so they're not allowed
+        assert!(parse_header("x-release:=1.0").is_err());
+    }
+
+    #[test]
+    fn test_parse_diagnostic() {
+        let value =
+            parse_diagnostic(r#"{"a": [1, -2.5, true, null], 1: h'00ff', "b": "\"q\""}"#).unwrap();
+        assert_eq!(
+            value,
+            Value::Map(vec![
+                (
+                    Value::Text("a".to_string()),
+                    Value::Array(vec![
+                        Value::Integer(1),
+                        Value::Float(-2.5),
+                        Value::Bool(true),
+                        Value::Null
+                    ])
+                ),
+                (Value::Integer(1), Value::Bytes(vec![0x00, 0xff])),
+                (
+                    Value::Text("b".to_string()),
+                    Value::Text("\"q\"".to_string())
+                ),
+            ])
+        );
+        assert_eq!(
+            parse_diagnostic("1(1700000000)").unwrap(),
+            Value::Tag(1, Box::new(Value::Integer(1700000000)))
+        );
+    }
+
+    #[test]
+    fn test_parse_diagnostic_invalid() {
+        assert!(parse_diagnostic("[1, 2").is_err());
+        assert!(parse_diagnostic("\"open").is_err());
+        assert!(parse_diagnostic("h'abc'").is_err());
+        assert!(parse_diagnostic("1 2").is_err());
+    }
+}
@@ -1,7 +1,14 @@
+pub mod block_store;
+pub mod chunking;
+pub mod compression;
 pub mod config;
 pub mod db;
 pub mod error;
 pub mod file;
+pub mod header;
 pub mod key_storage;
 pub mod rand;
 pub mod szdt;
+#[cfg(test)]
+mod test_utils;
+pub mod time;
@@ -1,51 +1,183 @@
+use crate::chunking::Chunking;
+use crate::compression::Compression;
 use crate::error::Error;
 use crate::file::walk_files;
-use std::fs::{self, File};
-use std::io::BufRead;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Permissions};
+use std::io::{BufRead, BufReader, Seek};
+use std::path::{Path, PathBuf};
+use szdt_core::annotate::{Annotation, annotate_seq, rewrite_memos};
+use szdt_core::block_store::{BlockStore, import_seq};
 use szdt_core::bytes::Bytes;
 use szdt_core::cbor_seq::{CborSeqReader, CborSeqWriter};
+use szdt_core::chunked::{CHUNKED, chunk_hashes, is_chunked, read_file};
 use szdt_core::contact::Contact;
+use szdt_core::content_encoding::CONTENT_ENCODING;
 use szdt_core::content_type;
 use szdt_core::ed25519_key_material::Ed25519KeyMaterial;
+use szdt_core::error::Error as CoreError;
+use szdt_core::hash::Hash;
+use szdt_core::hashseq::HashSeq;
+use szdt_core::limits::Limits;
+use szdt_core::link::ToLink;
 use szdt_core::memo::Memo;
+use szdt_core::url::SzdtUrl;
+use szdt_core::validation::ValidationOptions;
+use szdt_core::value::{Block, BlockDecoder};
+use tempfile::NamedTempFile;
 
 #[derive(Debug, Clone)]
 pub struct ArchiveReceipt {
     pub manifest: Vec<Memo>,
 }
 
+/// Options for writing an archive
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Manifest of the previous version of this archive.
+    /// When given, memos will point to the memo for the same path in the
+    /// previous version via `prev`, and only bodies that changed are written.
+    pub prev: Option<Vec<Memo>>,
+    /// Not valid before time (UNIX timestamp, seconds).
+    /// Defaults to the time the archive is created.
+    pub nbf: Option<u64>,
+    /// Expiration time (UNIX timestamp, seconds)
+    pub exp: Option<u64>,
+    /// Additional protected headers, set on every memo
+    pub headers: Vec<(String, cbor4ii::core::Value)>,
+    /// Compress every body with the given algorithm, marking memos with a
+    /// `content-encoding` header. Memo `src` hashes are hashes of the
+    /// compressed bodies.
+    pub compression: Option<Compression>,
+    /// Write each distinct body once. Memos for files with the same content
+    /// as an earlier file reference the earlier body by `src`, and are written
+    /// without a body.
+    pub dedup: bool,
+    /// Split files larger than the max chunk size into content-defined
+    /// chunks. The memo body becomes a `HashSeq` of chunk hashes, followed by
+    /// the chunks. Each distinct chunk is written once.
+    pub chunking: Option<Chunking>,
+    /// Hashes of chunks in the previous version of this archive.
+    /// These chunks are not written again.
+    pub prev_chunks: HashSet<Hash>,
+}
+
 /// Write an archive file by reading files from a directory
 pub fn archive(
     dir: &Path,
     archive_file: &Path,
     contact: &Contact,
+    options: &ArchiveOptions,
 ) -> Result<ArchiveReceipt, Error> {
     let key_material = Ed25519KeyMaterial::try_from(contact)?;
     let paths = walk_files(dir)?;
 
+    check_validity_window(options.nbf, options.exp)?;
+
+    if options.compression.is_some()
+        && options
+            .headers
+            .iter()
+            .any(|(key, _)| key == CONTENT_ENCODING)
+    {
+        return Err(Error::Header(format!(
+            "{CONTENT_ENCODING} is set by compression, and can't also be given as a header"
+        )));
+    }
+
+    // Index previous memos by path
+    let mut prev_memos: HashMap<&str, &Memo> = HashMap::new();
+    for memo in options.prev.iter().flatten() {
+        if let Some(path) = memo.protected.path.as_deref() {
+            prev_memos.insert(path, memo);
+        }
+    }
+
     let archive_file = File::create(archive_file)?;
     let mut archive_writer = CborSeqWriter::new(archive_file);
     let mut manifest: Vec<Memo> = Vec::new();
+    // Hashes of the bodies and chunks written so far
+    let mut written: HashSet<Hash> = HashSet::new();
+    let mut written_chunks: HashSet<Hash> = HashSet::new();
 
     for path in &paths {
-        // Read file bytes
-        let bytes = fs::read(path)?;
-        let cbor_bytes = Bytes(bytes);
-        let relative_path = path.strip_prefix(dir)?;
+        // Read file bytes, compressing them if requested. Large files are
+        // split into chunks, which are compressed separately. Chunks are
+        // streamed from the file, so large files are never read into memory
+        // whole.
+        let size = fs::metadata(path)?.len();
+        let chunking = options
+            .chunking
+            .filter(|chunking| size > chunking.max_size() as u64);
+        let cbor_bytes = match chunking {
+            Some(chunking) => {
+                let hashseq = chunk_file(path, chunking, options.compression, |_, _| Ok(()))?;
+                Bytes(hashseq.into_vec())
+            }
+            None => encode_body(fs::read(path)?, options.compression)?,
+        };
+        let relative_path = path.strip_prefix(dir)?.to_string_lossy().to_string();
         // Create a memo for this file
         let mut memo = Memo::for_body(&cbor_bytes)?;
+        // Point to the previous version of this file (if any), by the hash of
+        // its protected headers, so the link survives annotations
+        let prev_memo = prev_memos.get(relative_path.as_str());
+        if let Some(prev_memo) = prev_memo {
+            memo.protected.prev = Some(prev_memo.protected.signing_hash()?);
+        }
         // Set file path
-        memo.protected.path = Some(relative_path.to_string_lossy().to_string());
+        memo.protected.path = Some(relative_path);
         // Set content type (if we can guess it)
         memo.protected.content_type = content_type::guess_from_path(path);
         memo.protected.iss_nickname = Some(contact.nickname.to_string());
+        for (key, value) in &options.headers {
+            memo.protected.set_header(key, value)?;
+        }
+        if let Some(compression) = options.compression {
+            memo.protected
+                .set_header(CONTENT_ENCODING, &compression.name())?;
+        }
+        if chunking.is_some() {
+            memo.protected.set_header(CHUNKED, &true)?;
+        }
+        set_validity_window(&mut memo, options.nbf, options.exp);
         // Sign memo
         memo.sign(&key_material)?;
         // Write memo
         archive_writer.write_block(&memo)?;
-        // Write bytes
-        archive_writer.write_block(&cbor_bytes)?;
+        // Write bytes, unless they are unchanged since the previous version,
+        // or are a duplicate of a body already written
+        let is_unchanged = prev_memo.is_some_and(|prev| prev.protected.src == memo.protected.src);
+        let is_duplicate = options.dedup && written.contains(&memo.protected.src);
+        if !is_unchanged && !is_duplicate {
+            archive_writer.write_block(&cbor_bytes)?;
+            written.insert(memo.protected.src);
+            // Read the file again, writing chunks that aren't already in this
+            // archive, or in the previous version
+            if let Some(chunking) = chunking {
+                let hashseq = chunk_hashes(&cbor_bytes)?;
+                let mut expected = hashseq.iter();
+                let changed = || {
+                    Error::Chunking(format!(
+                        "{} changed while it was being archived",
+                        path.display()
+                    ))
+                };
+                chunk_file(path, chunking, options.compression, |hash, chunk| {
+                    if expected.next() != Some(hash) {
+                        return Err(changed());
+                    }
+                    if !written_chunks.contains(&hash) && !options.prev_chunks.contains(&hash) {
+                        archive_writer.write_block(chunk)?;
+                        written_chunks.insert(hash);
+                    }
+                    Ok(())
+                })?;
+                if expected.next().is_some() {
+                    return Err(changed());
+                }
+            }
+        }
         // Push memo into manifest
         manifest.push(memo);
     }
@@ -55,38 +187,1328 @@ pub fn archive(
     Ok(ArchiveReceipt { manifest })
 }
 
-/// Read a pair of memo and bytes from an archive.
-/// This function assumes the streaming-friendly sequence layout of:
-/// `memo | bytes | memo | bytes | ...`
-fn read_archive_memo_pair<R: BufRead>(
-    reader: &mut CborSeqReader<R>,
-) -> Result<(Memo, Bytes), Error> {
-    let memo: Memo = reader.read_block()?;
-    let bytes: Bytes = reader.read_block()?;
-    Ok((memo, bytes))
+/// Split a file into content-defined chunks, encoding each chunk with the
+/// given compression, if any. Calls `f` with each chunk and its hash, as the
+/// chunks are read. Returns the `HashSeq` of chunk hashes.
+fn chunk_file<F>(
+    path: &Path,
+    chunking: Chunking,
+    compression: Option<Compression>,
+    mut f: F,
+) -> Result<HashSeq, Error>
+where
+    F: FnMut(Hash, &Bytes) -> Result<(), Error>,
+{
+    let mut hashseq = HashSeq::empty();
+    for chunk in chunking.stream(BufReader::new(File::open(path)?)) {
+        let chunk = encode_body(chunk?, compression)?;
+        let hash = chunk.to_link()?;
+        f(hash, &chunk)?;
+        hashseq.append(hash);
+    }
+    Ok(hashseq)
+}
+
+/// Encode a body with the given compression, if any
+fn encode_body(bytes: Vec<u8>, compression: Option<Compression>) -> Result<Bytes, Error> {
+    match compression {
+        Some(compression) => Ok(Bytes(compression.compress(&bytes)?)),
+        None => Ok(Bytes(bytes)),
+    }
+}
+
+/// Options for signing a single file
+#[derive(Debug, Clone, Default)]
+pub struct SignOptions {
+    /// Content type (MIME type). Guessed from the file path when not given.
+    pub content_type: Option<String>,
+    /// Additional protected headers.
+    /// Keys must follow the rules for extension headers (see `ProtectedHeaders::set_header`).
+    pub headers: Vec<(String, cbor4ii::core::Value)>,
+    /// Not valid before time (UNIX timestamp, seconds).
+    /// Defaults to the time the memo is created.
+    pub nbf: Option<u64>,
+    /// Expiration time (UNIX timestamp, seconds)
+    pub exp: Option<u64>,
+    /// Write a detached memo, without the body
+    pub detached: bool,
+}
+
+/// Sign a file, writing a sequence of `memo | bytes` to the output file.
+/// When signing with the `detached` option, only the memo is written.
+pub fn sign(
+    file: &Path,
+    out_file: &Path,
+    contact: &Contact,
+    options: &SignOptions,
+) -> Result<Memo, Error> {
+    let key_material = Ed25519KeyMaterial::try_from(contact)?;
+    check_validity_window(options.nbf, options.exp)?;
+
+    let cbor_bytes = Bytes(fs::read(file)?);
+    let mut memo = Memo::for_body(&cbor_bytes)?;
+    memo.protected.path = file
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string());
+    memo.protected.content_type = options
+        .content_type
+        .clone()
+        .or_else(|| content_type::guess_from_path(file));
+    memo.protected.iss_nickname = Some(contact.nickname.to_string());
+    for (key, value) in &options.headers {
+        memo.protected.set_header(key, value)?;
+    }
+    set_validity_window(&mut memo, options.nbf, options.exp);
+    memo.sign(&key_material)?;
+
+    let mut writer = CborSeqWriter::new(File::create(out_file)?);
+    writer.write_block(&memo)?;
+    if !options.detached {
+        writer.write_block(&cbor_bytes)?;
+    }
+    writer.flush()?;
+
+    Ok(memo)
+}
+
+/// Annotate the unprotected headers of memos in a sequence file, writing the
+/// result to `out_file`. When `paths` is non-empty, only memos with those
+/// paths are annotated. Returns the number of memos annotated.
+///
+/// `out_file` may be the same as `file`, in which case the file is rewritten.
+pub fn annotate(
+    file: &Path,
+    out_file: &Path,
+    annotation: &Annotation,
+    paths: &[String],
+) -> Result<usize, Error> {
+    rewrite_file(file, out_file, |reader, writer| {
+        annotate_seq(reader, writer, annotation, |memo| {
+            paths.is_empty()
+                || memo
+                    .protected
+                    .path
+                    .as_ref()
+                    .is_some_and(|path| paths.contains(path))
+        })
+    })
+}
+
+/// Countersign every memo in a sequence file with a witness signature,
+/// writing the result to `out_file`. The issuer signature of each memo is
+/// verified before it is witnessed, and preserved. Returns the number of memos
+/// countersigned.
+///
+/// `out_file` may be the same as `file`, in which case the file is rewritten.
+pub fn countersign(file: &Path, out_file: &Path, contact: &Contact) -> Result<usize, Error> {
+    let key_material = Ed25519KeyMaterial::try_from(contact)?;
+    rewrite_file(file, out_file, |reader, writer| {
+        rewrite_memos(reader, writer, |memo| {
+            memo.verify()?;
+            memo.witness(&key_material)?;
+            Ok(true)
+        })
+    })
+}
+
+/// Redact the bodies of memos with the given paths, writing the result to
+/// `out_file`. Memos are kept, so the archive still proves what was published,
+/// but the redacted bodies are omitted. Returns the number of bodies redacted.
+/// Fails if any of the paths matches no memo in the archive.
+///
+/// Bodies and chunks shared with files that aren't redacted, in deduplicated
+/// or chunked archives, are kept. They are written after the first memo that
+/// still needs them.
+///
+/// `out_file` may be the same as `file`, in which case the file is rewritten.
+pub fn redact(file: &Path, out_file: &Path, paths: &[String]) -> Result<usize, Error> {
+    let mut entries = Unarchiver::new(BufReader::new(File::open(file)?));
+    let permissions = fs::metadata(file)?.permissions();
+    write_file_atomic(out_file, permissions, |writer| {
+        let mut count = 0;
+        let mut matched: HashSet<&str> = HashSet::new();
+        // Hashes of the bodies and chunks written so far
+        let mut written: HashSet<Hash> = HashSet::new();
+        while let Some(entry) = entries.next() {
+            let (memo, bytes) = entry?;
+            writer.write_block(&memo)?;
+            let redacted_path = paths
+                .iter()
+                .find(|path| memo.protected.path.as_ref() == Some(path));
+            if let Some(path) = redacted_path {
+                matched.insert(path);
+            }
+            let Some(bytes) = bytes else {
+                continue;
+            };
+            if redacted_path.is_some() {
+                count += 1;
+                continue;
+            }
+            if !written.insert(bytes.to_link()?) {
+                continue;
+            }
+            writer.write_block(&bytes)?;
+            if is_chunked(&memo)? {
+                for hash in chunk_hashes(&bytes)?.iter() {
+                    // Chunks omitted from an update to a previous version of
+                    // the archive stay omitted
+                    if !written.contains(&hash)
+                        && let Some(chunk) = entries.read_body(&hash)?
+                    {
+                        writer.write_block(&chunk)?;
+                        written.insert(hash);
+                    }
+                }
+            }
+        }
+        if let Some(path) = paths.iter().find(|path| !matched.contains(path.as_str())) {
+            return Err(Error::Archive(format!("No memo found for path {path}")));
+        }
+        Ok(count)
+    })
+}
+
+/// Rewrite a sequence file with the given function.
+fn rewrite_file<F>(file: &Path, out_file: &Path, rewrite: F) -> Result<usize, Error>
+where
+    F: FnOnce(
+        &mut CborSeqReader<BufReader<File>>,
+        &mut CborSeqWriter<File>,
+    ) -> Result<usize, CoreError>,
+{
+    let mut reader = CborSeqReader::new(BufReader::new(File::open(file)?));
+    let permissions = fs::metadata(file)?.permissions();
+    write_file_atomic(out_file, permissions, |writer| {
+        Ok(rewrite(&mut reader, writer)?)
+    })
+}
+
+/// Write a sequence file with the given function.
+/// Writes to a uniquely named temporary file in the same directory first, then
+/// moves it into place, so that a file can be rewritten in place, concurrent
+/// writes don't clobber each other, and failures don't leave partial files
+/// behind. The file is given `permissions`, since temporary files are only
+/// readable by their owner.
+fn write_file_atomic<F>(out_file: &Path, permissions: Permissions, write: F) -> Result<usize, Error>
+where
+    F: FnOnce(&mut CborSeqWriter<File>) -> Result<usize, Error>,
+{
+    let dir = match out_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // The temporary file is removed when `tmp_path` is dropped, unless it has
+    // been persisted
+    let (file, tmp_path) = NamedTempFile::new_in(dir)?.into_parts();
+    let mut writer = CborSeqWriter::new(file);
+    let count = write(&mut writer)?;
+    writer.flush()?;
+    fs::set_permissions(&tmp_path, permissions)?;
+    tmp_path
+        .persist(out_file)
+        .map_err(|err| Error::Io(err.error))?;
+    Ok(count)
+}
+
+/// Import every block in an archive into a block store, so that archives can be
+/// cached locally and blocks shared between archives are stored once.
+/// Returns the content addresses of the blocks, in sequence order.
+pub fn import<S: BlockStore>(file: &Path, store: &mut S) -> Result<Vec<Hash>, Error> {
+    let mut reader = CborSeqReader::new(BufReader::new(File::open(file)?));
+    Ok(import_seq(&mut reader, store)?)
+}
+
+/// Read a memo file, returning the memo and its body (if the body follows the
+/// memo in the file).
+pub fn read_memo(memo_file: &Path) -> Result<(Memo, Option<Bytes>), Error> {
+    let reader = BufReader::new(File::open(memo_file)?);
+    Unarchiver::new(reader)
+        .next()
+        .unwrap_or(Err(Error::Archive("No memo found".to_string())))
+}
+
+/// Resolve a `szdt:` URL against an archive file, returning the latest
+/// matching memo that is valid according to `options`, and its decoded body
+/// (if the body is present in the archive).
+///
+/// Memos are picked the same way as `SzdtUrl::resolve`, but the archive is
+/// streamed, so only the body of the memo returned is kept in memory.
+pub fn get(
+    url: &SzdtUrl,
+    archive_file: &Path,
+    options: &ValidationOptions,
+) -> Result<(Memo, Option<Bytes>), Error> {
+    let mut unarchiver = Unarchiver::new(BufReader::new(File::open(archive_file)?));
+    let mut found: Option<(Memo, Option<Bytes>)> = None;
+    for result in unarchiver.by_ref() {
+        let (memo, bytes) = result?;
+        if !url.matches(&memo.to_link()?, &memo) || memo.validate(options).is_err() {
+            continue;
+        }
+        // Bodies that don't match the memo checksum don't belong to it
+        let bytes = match bytes {
+            Some(bytes) if bytes.to_link()? == memo.protected.src => Some(bytes),
+            _ => None,
+        };
+        // Prefer memos with bodies, then the most recently issued. Later
+        // memos win ties.
+        let rank = |memo: &Memo, bytes: &Option<Bytes>| (bytes.is_some(), memo.protected.iat);
+        if found.as_ref().is_none_or(|(found_memo, found_bytes)| {
+            rank(&memo, &bytes) >= rank(found_memo, found_bytes)
+        }) {
+            found = Some((memo, bytes));
+        }
+    }
+    let Some((memo, bytes)) = found else {
+        return Err(Error::Archive(format!("No valid file found for {url}")));
+    };
+    let limits = *unarchiver.limits();
+    let body = match bytes {
+        Some(bytes) => Some(read_file(&memo, bytes, &limits, |hash| {
+            unarchiver.read_body(hash)
+        })?),
+        None => None,
+    };
+    Ok((memo, body))
+}
+
+/// Check that the expiration time comes after the not-before time
+fn check_validity_window(nbf: Option<u64>, exp: Option<u64>) -> Result<(), Error> {
+    if nbf.zip(exp).is_some_and(|(nbf, exp)| exp <= nbf) {
+        return Err(Error::Time(
+            "Expiration time must be after not-before time".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Set the window of time a memo is valid.
+/// Memos default to being valid from the time they are created.
+fn set_validity_window(memo: &mut Memo, nbf: Option<u64>, exp: Option<u64>) {
+    if nbf.is_some() {
+        memo.protected.nbf = nbf;
+    }
+    memo.protected.exp = exp;
+}
+
+/// Read the manifest of memos from an archive file.
+pub fn read_manifest(archive_file: &Path) -> Result<Vec<Memo>, Error> {
+    let reader = BufReader::new(File::open(archive_file)?);
+    let mut manifest = Vec::new();
+    for result in Unarchiver::new(reader) {
+        let (memo, _) = result?;
+        manifest.push(memo);
+    }
+    Ok(manifest)
+}
+
+/// Read the hashes of the chunks listed by the chunked bodies in an archive
+/// file. Used to avoid writing chunks again in an update to the archive.
+pub fn read_chunk_hashes(archive_file: &Path) -> Result<HashSet<Hash>, Error> {
+    let reader = BufReader::new(File::open(archive_file)?);
+    let mut hashes = HashSet::new();
+    for result in Unarchiver::new(reader) {
+        let (memo, bytes) = result?;
+        if let Some(bytes) = bytes
+            && is_chunked(&memo)?
+        {
+            hashes.extend(chunk_hashes(&bytes)?.iter());
+        }
+    }
+    Ok(hashes)
+}
+
+/// Bodies and chunks in a series of archive files, read by hash. Used to
+/// reconstruct the bodies omitted from an updated archive, by reading them out
+/// of the previous versions of the archive.
+///
+/// Only the offsets of bodies are kept in memory. Bodies are read back from
+/// the archive files as needed.
+pub struct ArchiveBodies {
+    unarchivers: Vec<Unarchiver<BufReader<File>>>,
 }
 
+impl ArchiveBodies {
+    /// Open a series of archive files, indexing the bodies in each
+    pub fn open(archive_files: &[PathBuf]) -> Result<Self, Error> {
+        let mut unarchivers = Vec::new();
+        for archive_file in archive_files {
+            let mut unarchiver = Unarchiver::new(BufReader::new(File::open(archive_file)?));
+            for result in unarchiver.by_ref() {
+                result?;
+            }
+            unarchivers.push(unarchiver);
+        }
+        Ok(Self { unarchivers })
+    }
+
+    /// Is a body or chunk with the given hash in any of the archives?
+    pub fn has_body(&self, hash: &Hash) -> bool {
+        self.unarchivers
+            .iter()
+            .any(|unarchiver| unarchiver.has_body(hash))
+    }
+
+    /// Read a body or chunk by hash, from the first archive that has it
+    pub fn read_body(&mut self, hash: &Hash) -> Result<Option<Bytes>, Error> {
+        for unarchiver in &mut self.unarchivers {
+            if let Some(bytes) = unarchiver.read_body(hash)? {
+                return Ok(Some(bytes));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Iterates over the memos in an archive, along with their bodies.
+///
+/// This iterator assumes the streaming-friendly sequence layout of:
+/// `memo | bytes | memo | bytes | ...`.
+/// Memos in updated archives may omit bodies that are unchanged since the
+/// previous version, in which case the body is returned as `None`.
+///
+/// Memos in deduplicated archives may omit bodies that appeared earlier in the
+/// archive. Bodies are resolved by hash, not by position, in a single pass.
+/// Archives opened with `new` are seekable: the iterator keeps the offset of
+/// every body it reads, keyed by the body's hash, and reads shared bodies back
+/// from the archive when a later memo refers to them. Bodies read back are
+/// checked against their hash again, so they always match the memo `src` they
+/// were resolved for. Archives opened with `from_stream`, such as pipes or
+/// stdin, can't be read back, so the iterator keeps every body it reads in
+/// memory instead.
+///
+/// Chunked bodies are followed by the chunks that first appear in the archive:
+/// `memo | hashseq | chunk | chunk | ...`. The iterator reads past these
+/// chunks along with the body, keeping only their offsets, so that files of
+/// any size can be read. Use `read_body` to read chunks by hash.
 pub struct Unarchiver<R> {
     reader: CborSeqReader<R>,
+    decoder: BlockDecoder,
+    /// Memo that has been read, but whose body has not been read yet
+    pending: Option<Memo>,
+    /// Body of the pending memo, if it is chunked and its chunks have not
+    /// all been read yet
+    pending_body: Option<Bytes>,
+    /// Bodies and chunks read so far, by hash
+    bodies: HashMap<Hash, BodyLocation>,
+    /// Reads a body back at an offset. Only set for seekable readers.
+    read_at: Option<ReadAt<R>>,
 }
 
-impl<R: BufRead> Unarchiver<R> {
+/// Reads the body at an offset in the sequence
+type ReadAt<R> = fn(&mut CborSeqReader<R>, usize) -> Result<Bytes, CoreError>;
+
+/// Where to find a body that has been read by the `Unarchiver`
+#[derive(Debug, Clone)]
+enum BodyLocation {
+    /// Offset of the body in a seekable archive
+    Offset(usize),
+    /// Body kept in memory, for archives that can't be read back
+    Buffered(Bytes),
+}
+
+impl<R: BufRead + Seek> Unarchiver<R> {
+    /// Read a seekable archive, such as a file. Only the offsets of bodies are
+    /// kept in memory.
     pub fn new(reader: R) -> Self {
+        Self {
+            read_at: Some(|reader, offset| reader.read_block_at(offset)),
+            ..Self::from_stream(reader)
+        }
+    }
+}
+
+impl<R: BufRead> Unarchiver<R> {
+    /// Read an archive from a stream that can't be read back, such as a pipe
+    /// or stdin. Every body and chunk is kept in memory, so that shared bodies
+    /// and chunks can be resolved in a single pass. Prefer `new` for files.
+    pub fn from_stream(reader: R) -> Self {
         Self {
             reader: CborSeqReader::new(reader),
+            decoder: BlockDecoder::new(),
+            pending: None,
+            pending_body: None,
+            bodies: HashMap::new(),
+            read_at: None,
+        }
+    }
+
+    /// Has a body or chunk with the given hash been read from the archive so
+    /// far?
+    pub fn has_body(&self, hash: &Hash) -> bool {
+        self.bodies.contains_key(hash)
+    }
+
+    /// Get the hashes of the chunks of a memo body that haven't been read from
+    /// the archive so far. Empty for bodies that aren't chunked. Chunks follow
+    /// the first body that lists them, so once a memo has been returned, its
+    /// chunks are either in the archive, or missing from it.
+    pub fn missing_chunks(&self, memo: &Memo, body: &Bytes) -> Result<Vec<Hash>, Error> {
+        if !is_chunked(memo)? {
+            return Ok(Vec::new());
+        }
+        Ok(chunk_hashes(body)?
+            .iter()
+            .filter(|hash| !self.has_body(hash))
+            .collect())
+    }
+
+    /// Read a body or chunk with the given hash back from earlier in the
+    /// archive. Returns `None` if it hasn't been read so far.
+    pub fn read_body(&mut self, hash: &Hash) -> Result<Option<Bytes>, Error> {
+        let offset = match self.bodies.get(hash) {
+            Some(BodyLocation::Offset(offset)) => *offset,
+            Some(BodyLocation::Buffered(bytes)) => return Ok(Some(bytes.clone())),
+            None => return Ok(None),
+        };
+        let Some(read_at) = self.read_at else {
+            return Ok(None);
+        };
+        let bytes = read_at(&mut self.reader, offset)?;
+        // The archive may have changed since the body was first read
+        let actual = bytes.to_link()?;
+        if &actual != hash {
+            return Err(CoreError::IntegrityError(format!(
+                "Body changed since it was read. Expected {hash}. Got: {actual}"
+            ))
+            .into());
         }
+        Ok(Some(bytes))
+    }
+
+    /// Set the resource limits enforced when reading the archive.
+    /// Bodies larger than `max_body_size` are rejected without being read.
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self {
+            reader: self.reader.with_limits(limits),
+            ..self
+        }
+    }
+
+    /// Resource limits enforced when reading the archive.
+    /// Use these when decoding bodies.
+    pub fn limits(&self) -> &Limits {
+        self.reader.limits()
+    }
+
+    /// Finish reading a memo, resolving its body by hash if the body didn't
+    /// follow the memo
+    fn finish(&mut self, memo: Memo) -> Result<(Memo, Option<Bytes>), Error> {
+        let body = match self.pending_body.take() {
+            Some(body) => Some(body),
+            None => self.read_body(&memo.protected.src)?,
+        };
+        Ok((memo, body))
     }
 }
 
 impl<R: BufRead> Iterator for Unarchiver<R> {
-    type Item = Result<(Memo, Bytes), Error>;
+    type Item = Result<(Memo, Option<Bytes>), Error>;
 
-    /// Returns an unvalidated pair of `(Memo, Bytes)`
+    /// Returns an unvalidated pair of `(Memo, Option<Bytes>)`
     fn next(&mut self) -> Option<Self::Item> {
-        match read_archive_memo_pair(&mut self.reader) {
-            Ok((memo, bytes)) => Some(Ok((memo, bytes))),
-            Err(Error::Core(szdt_core::error::Error::Eof)) => None,
-            Err(err) => Some(Err(err)),
+        loop {
+            let offset = self.reader.position();
+            match self.decoder.read_block(&mut self.reader) {
+                Ok(Block::Memo(memo)) => {
+                    // A memo directly following another memo, or the chunks
+                    // of its body, ends the previous memo.
+                    if let Some(memo) = self.pending.replace(*memo) {
+                        return Some(self.finish(memo));
+                    }
+                }
+                Ok(Block::Value(cbor4ii::core::Value::Bytes(bytes))) => {
+                    let Some(memo) = self.pending.as_ref() else {
+                        return Some(Err(Error::Archive(
+                            "Found bytes block without a memo".to_string(),
+                        )));
+                    };
+                    let is_chunked = match is_chunked(memo) {
+                        Ok(is_chunked) => is_chunked,
+                        Err(err) => return Some(Err(err.into())),
+                    };
+                    let bytes = Bytes(bytes);
+                    let hash = match bytes.to_link() {
+                        Ok(hash) => hash,
+                        Err(err) => return Some(Err(err.into())),
+                    };
+                    if !self.bodies.contains_key(&hash) {
+                        let location = match self.read_at {
+                            Some(_) => BodyLocation::Offset(offset),
+                            None => BodyLocation::Buffered(bytes.clone()),
+                        };
+                        self.bodies.insert(hash, location);
+                    }
+                    if !is_chunked {
+                        return Some(Ok((self.pending.take()?, Some(bytes))));
+                    }
+                    // Chunked bodies are followed by their chunks, so keep
+                    // reading until the next memo. Only the offsets of chunks
+                    // are kept.
+                    if self.pending_body.is_none() {
+                        self.pending_body = Some(bytes);
+                    }
+                }
+                Ok(_) => {
+                    return Some(Err(Error::Archive(
+                        "Unexpected block. Expected memo or bytes.".to_string(),
+                    )));
+                }
+                Err(CoreError::Eof) => {
+                    let memo = self.pending.take()?;
+                    return Some(self.finish(memo));
+                }
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_store::SqliteBlockStore;
+    use crate::test_utils::noise;
+    use szdt_core::chunked::read_chunked_body;
+    use szdt_core::content_encoding::decode_body;
+    use szdt_core::history::History;
+    use szdt_core::nickname::Nickname;
+    use tempfile::tempdir;
+
+    fn create_test_contact() -> Contact {
+        let key_material = Ed25519KeyMaterial::generate_from_entropy(&[0u8; 32]).unwrap();
+        Contact::new(
+            Nickname::parse("alice").unwrap(),
+            key_material.did(),
+            key_material.private_key(),
+        )
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        let entries: Vec<(Memo, Option<Bytes>)> = Unarchiver::new(reader)
+            .map(|result| result.unwrap())
+            .collect();
+
+        assert_eq!(entries.len(), 2);
+        for (memo, bytes) in entries {
+            memo.validate(&ValidationOptions::default()).unwrap();
+            let bytes = bytes.expect("Body should be present");
+            memo.checksum(&bytes.to_link().unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unarchiver_enforces_body_limit() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), vec![0u8; 1024]).unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let limits = Limits {
+            max_body_size: 512,
+            ..Limits::default()
+        };
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        let result = Unarchiver::new(reader).with_limits(limits).next().unwrap();
+        assert!(matches!(
+            result,
+            Err(Error::Core(CoreError::BodyTooLarge(_, 512)))
+        ));
+    }
+
+    #[test]
+    fn test_get_url() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let url = SzdtUrl::for_iss_path(contact.did.clone(), "/docs/a.txt");
+        let (memo, body) = get(&url, &archive_file, &ValidationOptions::default()).unwrap();
+        assert_eq!(memo.protected.path.as_deref(), Some("docs/a.txt"));
+        assert_eq!(body, Some(Bytes(b"a".to_vec())));
+
+        let url = SzdtUrl::for_hash(Bytes(b"b".to_vec()).to_link().unwrap());
+        let (_, body) = get(&url, &archive_file, &ValidationOptions::default()).unwrap();
+        assert_eq!(body, Some(Bytes(b"b".to_vec())));
+
+        let url = SzdtUrl::for_iss_path(contact.did, "missing.txt");
+        assert!(matches!(
+            get(&url, &archive_file, &ValidationOptions::default()),
+            Err(Error::Archive(_))
+        ));
+    }
+
+    #[test]
+    fn test_archive_compression() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        let text = "Hello World. ".repeat(100);
+        fs::write(dir.join("a.txt"), &text).unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        let options = ArchiveOptions {
+            compression: Some(Compression::Gzip),
+            ..Default::default()
+        };
+        archive(&dir, &archive_file, &contact, &options).unwrap();
+
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        let (memo, bytes) = Unarchiver::new(reader).next().unwrap().unwrap();
+        let bytes = bytes.unwrap();
+        // src is the hash of the stored, compressed body
+        memo.checksum(&bytes.to_link().unwrap()).unwrap();
+        assert!(bytes.0.len() < text.len());
+        assert_eq!(
+            memo.protected
+                .get_header::<String>(CONTENT_ENCODING)
+                .unwrap(),
+            Some("gzip".to_string())
+        );
+        assert_eq!(
+            decode_body(&memo, bytes, &Limits::default()).unwrap().0,
+            text.as_bytes()
+        );
+
+        let url = SzdtUrl::for_iss_path(contact.did.clone(), "a.txt");
+        let (_, body) = get(&url, &archive_file, &ValidationOptions::default()).unwrap();
+        assert_eq!(body.unwrap().0, text.as_bytes());
+
+        // An update with the same compression only writes changed bodies
+        let update_file = temp_dir.path().join("data-v2.szdt");
+        let options = ArchiveOptions {
+            prev: Some(read_manifest(&archive_file).unwrap()),
+            ..options
+        };
+        archive(&dir, &update_file, &contact, &options).unwrap();
+        let reader = BufReader::new(File::open(&update_file).unwrap());
+        let (_, bytes) = Unarchiver::new(reader).next().unwrap().unwrap();
+        assert!(bytes.is_none());
+    }
+
+    #[test]
+    fn test_archive_dedup() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"same").unwrap();
+        fs::write(dir.join("b.txt"), b"same").unwrap();
+        fs::write(dir.join("c.txt"), b"different").unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        let options = ArchiveOptions {
+            dedup: true,
+            ..Default::default()
+        };
+        archive(&dir, &archive_file, &contact, &options).unwrap();
+
+        // Each distinct body is written once
+        let mut reader = CborSeqReader::new(BufReader::new(File::open(&archive_file).unwrap()));
+        let mut blocks = 0;
+        while reader.read_block_bytes().is_ok() {
+            blocks += 1;
+        }
+        assert_eq!(blocks, 3 + 2);
+
+        // Duplicate bodies resolve to a body read earlier
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        for result in Unarchiver::new(reader) {
+            let (memo, bytes) = result.unwrap();
+            memo.checksum(&bytes.unwrap().to_link().unwrap()).unwrap();
+        }
+
+        // Every file resolves by hash
+        for path in ["a.txt", "b.txt"] {
+            let url = SzdtUrl::for_iss_path(contact.did.clone(), path);
+            let (_, body) = get(&url, &archive_file, &ValidationOptions::default()).unwrap();
+            assert_eq!(body, Some(Bytes(b"same".to_vec())));
+        }
+    }
+
+    #[test]
+    fn test_unarchiver_resolves_shared_bodies_by_hash() {
+        let same = Bytes(b"same".to_vec());
+        let other = Bytes(b"other".to_vec());
+        let mut memo_a = Memo::for_body(&same).unwrap();
+        memo_a.protected.path = Some("a.txt".to_string());
+        let mut memo_b = Memo::for_body(&same).unwrap();
+        memo_b.protected.path = Some("b.txt".to_string());
+        let mut memo_b2 = Memo::for_body(&other).unwrap();
+        memo_b2.protected.path = Some("b.txt".to_string());
+
+        // a.txt shares its body with the first version of b.txt, which is
+        // followed by another version of b.txt at the same path
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&memo_b).unwrap();
+        writer.write_block(&same).unwrap();
+        writer.write_block(&memo_b2).unwrap();
+        writer.write_block(&other).unwrap();
+        writer.write_block(&memo_a).unwrap();
+
+        let archive = writer.into_inner();
+        let entries: Vec<(Memo, Option<Bytes>)> = Unarchiver::new(std::io::Cursor::new(&archive))
+            .map(|result| result.unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].0.protected.path.as_deref(), Some("a.txt"));
+        assert_eq!(entries[2].1, Some(same.clone()));
+
+        // Streams that can't seek resolve shared bodies in the same pass
+        let streamed: Vec<(Memo, Option<Bytes>)> = Unarchiver::from_stream(archive.as_slice())
+            .map(|result| result.unwrap())
+            .collect();
+        assert_eq!(streamed, entries);
+    }
+
+    #[test]
+    fn test_archive_chunking() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        let data = noise(64 * 1024, 1);
+        fs::write(dir.join("large.bin"), &data).unwrap();
+        fs::write(dir.join("small.txt"), b"small").unwrap();
+
+        let contact = create_test_contact();
+        let v1_file = temp_dir.path().join("v1.szdt");
+        let options = ArchiveOptions {
+            chunking: Some(Chunking::new(256, 1024, 4096).unwrap()),
+            compression: Some(Compression::Zstd),
+            ..Default::default()
+        };
+        archive(&dir, &v1_file, &contact, &options).unwrap();
+
+        let reader = BufReader::new(File::open(&v1_file).unwrap());
+        let mut unarchiver = Unarchiver::new(reader);
+        while let Some(result) = unarchiver.next() {
+            let (memo, bytes) = result.unwrap();
+            let bytes = bytes.unwrap();
+            memo.checksum(&bytes.to_link().unwrap()).unwrap();
+            assert!(unarchiver.missing_chunks(&memo, &bytes).unwrap().is_empty());
+            match memo.protected.path.as_deref() {
+                Some("large.bin") => {
+                    assert!(is_chunked(&memo).unwrap());
+                    assert!(chunk_hashes(&bytes).unwrap().iter().count() > 1);
+                    let limits = *unarchiver.limits();
+                    let body = read_chunked_body(&memo, &bytes, &limits, |hash| {
+                        unarchiver.read_body(hash)
+                    })
+                    .unwrap();
+                    assert_eq!(body.0, data);
+                }
+                _ => assert!(!is_chunked(&memo).unwrap()),
+            }
+        }
+
+        let url = SzdtUrl::for_iss_path(contact.did.clone(), "large.bin");
+        let (_, body) = get(&url, &v1_file, &ValidationOptions::default()).unwrap();
+        assert_eq!(body.unwrap().0, data);
+
+        // Chunks can also be read from a stream that can't seek
+        let archive_bytes = fs::read(&v1_file).unwrap();
+        let mut unarchiver = Unarchiver::from_stream(archive_bytes.as_slice());
+        let (memo, bytes) = unarchiver.next().unwrap().unwrap();
+        assert_eq!(memo.protected.path.as_deref(), Some("large.bin"));
+        let bytes = bytes.unwrap();
+        assert!(unarchiver.missing_chunks(&memo, &bytes).unwrap().is_empty());
+        let limits = *unarchiver.limits();
+        let body =
+            read_chunked_body(&memo, &bytes, &limits, |hash| unarchiver.read_body(hash)).unwrap();
+        assert_eq!(body.0, data);
+
+        // An update after a small edit only writes the chunks around the edit
+        let mut edited = data.clone();
+        edited.splice(100..100, b"edit".iter().copied());
+        fs::write(dir.join("large.bin"), &edited).unwrap();
+        let v2_file = temp_dir.path().join("v2.szdt");
+        let options = ArchiveOptions {
+            prev: Some(read_manifest(&v1_file).unwrap()),
+            prev_chunks: read_chunk_hashes(&v1_file).unwrap(),
+            ..options
+        };
+        archive(&dir, &v2_file, &contact, &options).unwrap();
+
+        let reader = BufReader::new(File::open(&v2_file).unwrap());
+        let mut unarchiver = Unarchiver::new(reader);
+        let (memo, bytes) = unarchiver.next().unwrap().unwrap();
+        assert_eq!(memo.protected.path.as_deref(), Some("large.bin"));
+        let bytes = bytes.unwrap();
+        let chunk_count = chunk_hashes(&bytes).unwrap().iter().count();
+        let missing = unarchiver.missing_chunks(&memo, &bytes).unwrap();
+        assert!(missing.len() < chunk_count);
+        assert!(missing.len() >= chunk_count - 2);
+
+        // The rest of the chunks can be recovered from the previous version
+        let mut base_bodies = ArchiveBodies::open(&[v1_file]).unwrap();
+        assert!(missing.iter().all(|hash| base_bodies.has_body(hash)));
+        let body = read_chunked_body(&memo, &bytes, &Limits::default(), |hash| {
+            match unarchiver.read_body(hash)? {
+                Some(chunk) => Ok(Some(chunk)),
+                None => base_bodies.read_body(hash),
+            }
+        })
+        .unwrap();
+        assert_eq!(body.0, edited);
+    }
+
+    #[test]
+    fn test_archive_validity_window() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+
+        let archive_file = temp_dir.path().join("data.szdt");
+        let contact = create_test_contact();
+        let options = ArchiveOptions {
+            nbf: Some(1000),
+            exp: Some(2000),
+            ..Default::default()
+        };
+        archive(&dir, &archive_file, &contact, &options).unwrap();
+
+        let manifest = read_manifest(&archive_file).unwrap();
+        let memo = &manifest[0];
+        assert_eq!(memo.protected.nbf, Some(1000));
+        assert_eq!(memo.protected.exp, Some(2000));
+        memo.validate(&ValidationOptions::at(1500)).unwrap();
+        assert!(memo.validate(&ValidationOptions::at(999)).is_err());
+        assert!(memo.validate(&ValidationOptions::at(2001)).is_err());
+
+        let options = ArchiveOptions {
+            nbf: Some(2000),
+            exp: Some(1000),
+            ..Default::default()
+        };
+        assert!(archive(&dir, &archive_file, &contact, &options).is_err());
+    }
+
+    #[test]
+    fn test_sign_and_read_memo() {
+        let temp_dir = tempdir().unwrap();
+        let file = temp_dir.path().join("hello.txt");
+        fs::write(&file, b"Hello World").unwrap();
+        let contact = create_test_contact();
+
+        let options = SignOptions {
+            headers: vec![(
+                "x-release".to_string(),
+                cbor4ii::core::Value::Text("1.0".to_string()),
+            )],
+            ..Default::default()
+        };
+        let out_file = temp_dir.path().join("hello.txt.szdt");
+        sign(&file, &out_file, &contact, &options).unwrap();
+
+        let (memo, bytes) = read_memo(&out_file).unwrap();
+        memo.validate(&ValidationOptions::default()).unwrap();
+        assert_eq!(memo.protected.path.as_deref(), Some("hello.txt"));
+        assert_eq!(memo.protected.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(
+            memo.protected.extra.get("x-release"),
+            Some(&cbor4ii::core::Value::Text("1.0".to_string()))
+        );
+        let bytes = bytes.expect("Body should be present");
+        memo.checksum(&bytes.to_link().unwrap()).unwrap();
+
+        // Detached memos omit the body
+        let detached_options = SignOptions {
+            detached: true,
+            ..Default::default()
+        };
+        let memo_file = temp_dir.path().join("hello.txt.memo");
+        sign(&file, &memo_file, &contact, &detached_options).unwrap();
+        let (memo, bytes) = read_memo(&memo_file).unwrap();
+        assert!(bytes.is_none());
+        let body = Bytes(fs::read(&file).unwrap());
+        memo.checksum(&body.to_link().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_annotate_in_place() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+        let archive_file = temp_dir.path().join("data.szdt");
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let annotation = Annotation {
+            set: vec![(
+                "x-mirror".to_string(),
+                cbor4ii::core::Value::Text("https://example.com".to_string()),
+            )],
+            remove: vec![],
+        };
+        let count = annotate(
+            &archive_file,
+            &archive_file,
+            &annotation,
+            &["a.txt".to_string()],
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        for result in Unarchiver::new(reader) {
+            let (memo, bytes) = result.unwrap();
+            memo.validate(&ValidationOptions::default()).unwrap();
+            memo.checksum(&bytes.unwrap().to_link().unwrap()).unwrap();
+            let mirror: Option<String> = memo.unprotected.get_header("x-mirror").unwrap();
+            if memo.protected.path.as_deref() == Some("a.txt") {
+                assert_eq!(mirror.as_deref(), Some("https://example.com"));
+            } else {
+                assert_eq!(mirror, None);
+            }
+        }
+        // Only the data directory and the archive are left
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_history_of_annotated_archive() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"v1").unwrap();
+        let v1_file = temp_dir.path().join("v1.szdt");
+        archive(&dir, &v1_file, &contact, &ArchiveOptions::default()).unwrap();
+        let v1_memo = read_manifest(&v1_file).unwrap().remove(0);
+
+        // Annotate v1, then publish v2 as an update to the annotated v1
+        let annotation = Annotation {
+            set: vec![(
+                "x-mirror".to_string(),
+                cbor4ii::core::Value::Text("https://example.com".to_string()),
+            )],
+            remove: vec![],
+        };
+        annotate(&v1_file, &v1_file, &annotation, &[]).unwrap();
+        let annotated_v1 = read_manifest(&v1_file).unwrap();
+        assert_ne!(
+            annotated_v1[0].to_link().unwrap(),
+            v1_memo.to_link().unwrap()
+        );
+
+        fs::write(dir.join("a.txt"), b"v2").unwrap();
+        let v2_file = temp_dir.path().join("v2.szdt");
+        let options = ArchiveOptions {
+            prev: Some(annotated_v1.clone()),
+            ..Default::default()
+        };
+        archive(&dir, &v2_file, &contact, &options).unwrap();
+        let v2_memo = read_manifest(&v2_file).unwrap().remove(0);
+
+        // v2 links to v1, whether or not v1 was annotated
+        for v1 in [v1_memo.clone(), annotated_v1[0].clone()] {
+            let history = History::from_memos([v1, v2_memo.clone()]).unwrap();
+            let v2_hash = v2_memo.protected.signing_hash().unwrap();
+            assert_eq!(history.heads(), vec![v2_hash]);
+            let ancestors: Vec<Hash> = history.ancestors(&v2_hash).map(|(hash, _)| hash).collect();
+            assert_eq!(ancestors, vec![v1_memo.protected.signing_hash().unwrap()]);
+        }
+    }
+
+    #[test]
+    fn test_import_dedups_across_archives() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"shared").unwrap();
+        let v1_file = temp_dir.path().join("v1.szdt");
+        archive(&dir, &v1_file, &contact, &ArchiveOptions::default()).unwrap();
+        fs::write(dir.join("b.txt"), b"new").unwrap();
+        let v2_file = temp_dir.path().join("v2.szdt");
+        archive(&dir, &v2_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let mut store = SqliteBlockStore::open_in_memory().unwrap();
+        let v1_hashes = import(&v1_file, &mut store).unwrap();
+        let v2_hashes = import(&v2_file, &mut store).unwrap();
+        assert_eq!(v1_hashes.len(), 2);
+        assert_eq!(v2_hashes.len(), 4);
+        // The body of a.txt is stored once
+        let distinct: HashSet<Hash> = v1_hashes.into_iter().chain(v2_hashes).collect();
+        assert!(distinct.len() < 6);
+        assert_eq!(store.iter().unwrap().count(), distinct.len());
+
+        for memo in read_manifest(&v2_file).unwrap() {
+            let body: Bytes = store.get_value(&memo.protected.src).unwrap().unwrap();
+            memo.checksum(&body.to_link().unwrap()).unwrap();
+            assert!(store.has(&memo.to_link().unwrap()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_write_file_atomic() {
+        let temp_dir = tempdir().unwrap();
+        let out_file = temp_dir.path().join("out.szdt");
+        // A file that a fixed temporary file name would clobber
+        let other_file = temp_dir.path().join("out.szdt.tmp");
+        fs::write(&other_file, b"other").unwrap();
+
+        let permissions = fs::metadata(&other_file).unwrap().permissions();
+        let result = write_file_atomic(&out_file, permissions.clone(), |_| {
+            Err(Error::Archive("Write failed".to_string()))
+        });
+        assert!(matches!(result, Err(Error::Archive(_))));
+        assert!(!out_file.exists());
+        // The temporary file is cleaned up
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        let count = write_file_atomic(&out_file, permissions.clone(), |writer| {
+            writer.write_block(&Bytes(b"body".to_vec()))?;
+            Ok(1)
+        })
+        .unwrap();
+        assert_eq!(count, 1);
+        let mut reader = CborSeqReader::new(BufReader::new(File::open(&out_file).unwrap()));
+        let body: Bytes = reader.read_block().unwrap();
+        assert_eq!(body, Bytes(b"body".to_vec()));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+        assert_eq!(fs::read(&other_file).unwrap(), b"other");
+        assert_eq!(fs::metadata(&out_file).unwrap().permissions(), permissions);
+    }
+
+    #[test]
+    fn test_countersign_preserves_issuer_signature() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+        let archive_file = temp_dir.path().join("data.szdt");
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let witness_key = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let witness = Contact::new(
+            Nickname::parse("bob").unwrap(),
+            witness_key.did(),
+            witness_key.private_key(),
+        );
+        let out_file = temp_dir.path().join("countersigned.szdt");
+        let count = countersign(&archive_file, &out_file, &witness).unwrap();
+        assert_eq!(count, 2);
+
+        let reader = BufReader::new(File::open(&out_file).unwrap());
+        for result in Unarchiver::new(reader) {
+            let (memo, bytes) = result.unwrap();
+            memo.validate(&ValidationOptions::default()).unwrap();
+            assert_eq!(memo.protected.iss, Some(contact.did.clone()));
+            memo.checksum(&bytes.unwrap().to_link().unwrap()).unwrap();
+            let witnesses = memo.verify_witnesses().unwrap();
+            assert_eq!(witnesses.signed, vec![witness.did.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_redact_keeps_memos_verifiable() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+        let archive_file = temp_dir.path().join("data.szdt");
+        archive(&dir, &archive_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        let count = redact(&archive_file, &archive_file, &["a.txt".to_string()]).unwrap();
+        assert_eq!(count, 1);
+
+        // Paths that match no memo are an error, and leave the file as it was
+        let result = redact(&archive_file, &archive_file, &["missing.txt".to_string()]);
+        assert!(matches!(result, Err(Error::Archive(_))));
+
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        let entries: Vec<(Memo, Option<Bytes>)> = Unarchiver::new(reader)
+            .map(|entry| entry.unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        for (memo, bytes) in entries {
+            memo.validate(&ValidationOptions::default()).unwrap();
+            if memo.protected.path.as_deref() == Some("a.txt") {
+                assert!(bytes.is_none());
+            } else {
+                memo.checksum(&bytes.unwrap().to_link().unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_redact_keeps_shared_bodies_and_chunks() {
+        let temp_dir = tempdir().unwrap();
+        let contact = create_test_contact();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        let data = noise(16 * 1024, 4);
+        fs::write(dir.join("a.bin"), &data).unwrap();
+        fs::write(dir.join("b.bin"), &data).unwrap();
+        fs::write(dir.join("c.txt"), b"same").unwrap();
+        fs::write(dir.join("d.txt"), b"same").unwrap();
+        let archive_file = temp_dir.path().join("data.szdt");
+        let options = ArchiveOptions {
+            dedup: true,
+            chunking: Some(Chunking::new(256, 1024, 4096).unwrap()),
+            ..Default::default()
+        };
+        archive(&dir, &archive_file, &contact, &options).unwrap();
+
+        let paths = ["a.bin".to_string(), "c.txt".to_string()];
+        let count = redact(&archive_file, &archive_file, &paths).unwrap();
+        assert_eq!(count, 2);
+
+        let reader = BufReader::new(File::open(&archive_file).unwrap());
+        let mut unarchiver = Unarchiver::new(reader);
+        while let Some(result) = unarchiver.next() {
+            let (memo, bytes) = result.unwrap();
+            match memo.protected.path.as_deref().unwrap() {
+                // Redacted files still resolve to bodies shared with files
+                // that weren't redacted, if they come after them
+                "a.bin" | "c.txt" => {}
+                "b.bin" => {
+                    let bytes = bytes.unwrap();
+                    assert!(unarchiver.missing_chunks(&memo, &bytes).unwrap().is_empty());
+                    let body = read_chunked_body(&memo, &bytes, &Limits::default(), |hash| {
+                        unarchiver.read_body(hash)
+                    })
+                    .unwrap();
+                    assert_eq!(body.0, data);
+                }
+                "d.txt" => assert_eq!(bytes, Some(Bytes(b"same".to_vec()))),
+                path => panic!("Unexpected path {path}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_archive_update_only_writes_changed_bodies() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("data");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("same.txt"), b"same").unwrap();
+        fs::write(dir.join("changed.txt"), b"v1").unwrap();
+
+        let contact = create_test_contact();
+        let v1_file = temp_dir.path().join("v1.szdt");
+        archive(&dir, &v1_file, &contact, &ArchiveOptions::default()).unwrap();
+
+        fs::write(dir.join("changed.txt"), b"v2").unwrap();
+        let v1_manifest = read_manifest(&v1_file).unwrap();
+        let v2_file = temp_dir.path().join("v2.szdt");
+        let options = ArchiveOptions {
+            prev: Some(v1_manifest.clone()),
+            ..Default::default()
+        };
+        archive(&dir, &v2_file, &contact, &options).unwrap();
+
+        let reader = BufReader::new(File::open(&v2_file).unwrap());
+        for result in Unarchiver::new(reader) {
+            let (memo, bytes) = result.unwrap();
+            memo.validate(&ValidationOptions::default()).unwrap();
+            let path = memo.protected.path.as_deref().unwrap();
+            let prev_memo = v1_manifest
+                .iter()
+                .find(|prev| prev.protected.path.as_deref() == Some(path))
+                .unwrap();
+            assert_eq!(
+                memo.protected.prev,
+                Some(prev_memo.protected.signing_hash().unwrap())
+            );
+            match path {
+                "same.txt" => assert!(bytes.is_none()),
+                "changed.txt" => assert_eq!(bytes, Some(Bytes(b"v2".to_vec()))),
+                _ => panic!("Unexpected path {path}"),
+            }
+        }
+
+        // Unchanged bodies can be recovered from the previous version
+        let same_memo = read_manifest(&v2_file)
+            .unwrap()
+            .into_iter()
+            .find(|memo| memo.protected.path.as_deref() == Some("same.txt"))
+            .unwrap();
+        let mut base_bodies = ArchiveBodies::open(&[v1_file]).unwrap();
+        assert_eq!(
+            base_bodies.read_body(&same_memo.protected.src).unwrap(),
+            Some(Bytes(b"same".to_vec()))
+        );
+    }
+
+    /// Read and validate every file in an archive, returning the hash of each
+    /// file's content, or `None` if its body or a chunk is absent
+    fn read_contents<R: BufRead>(
+        mut unarchiver: Unarchiver<R>,
+    ) -> Result<Vec<Option<Hash>>, Error> {
+        let mut contents = Vec::new();
+        while let Some(result) = unarchiver.next() {
+            let (memo, bytes) = result?;
+            memo.validate(&ValidationOptions::default())?;
+            let Some(bytes) = bytes else {
+                contents.push(None);
+                continue;
+            };
+            memo.checksum(&bytes.to_link()?)?;
+            if !unarchiver.missing_chunks(&memo, &bytes)?.is_empty() {
+                contents.push(None);
+                continue;
+            }
+            let limits = *unarchiver.limits();
+            let body = read_file(&memo, bytes, &limits, |hash| unarchiver.read_body(hash))?;
+            contents.push(Some(Hash::new(&body.0)));
+        }
+        Ok(contents)
+    }
+
+    #[test]
+    fn test_unarchiver_reads_test_vectors() {
+        use szdt_core::test_vectors::{Expected, generate};
+        for vector in generate().unwrap().vectors {
+            let archive = vector.input.0.as_slice();
+            // Seekable archives and streams read the same
+            let results = [
+                read_contents(Unarchiver::new(std::io::Cursor::new(archive))),
+                read_contents(Unarchiver::from_stream(archive)),
+            ];
+            for result in results {
+                match &vector.expected {
+                    Expected::Archive { contents, .. } => {
+                        assert_eq!(&result.unwrap(), contents, "{}", vector.name)
+                    }
+                    Expected::Invalid { strict: false, .. } => {
+                        assert!(result.is_err(), "{} was accepted", vector.name)
+                    }
+                    _ => {}
+                }
+            }
         }
     }
 }
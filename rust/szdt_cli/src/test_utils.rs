@@ -0,0 +1,14 @@
+//! Shared fixtures for tests
+
+/// Deterministic pseudo-random bytes (xorshift)
+pub fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
@@ -0,0 +1,142 @@
+use crate::error::Error;
+use std::fmt::Write;
+use std::time::{Duration, UNIX_EPOCH};
+use szdt_core::time::now;
+
+/// Parse a point in time, given either as a UNIX timestamp in seconds,
+/// an RFC 3339 timestamp (`2025-01-01T12:00:00Z`, `2025-01-01T14:00:00+02:00`)
+/// or a date (`2025-01-01`). Timestamps without an offset are interpreted as
+/// UTC.
+pub fn parse_time(text: &str) -> Result<u64, Error> {
+    if let Ok(timestamp) = text.parse::<u64>() {
+        return Ok(timestamp);
+    }
+    let (text, offset) = split_offset(text)?;
+    let text = if text.len() == 10 {
+        format!("{text}T00:00:00Z")
+    } else {
+        text.to_string()
+    };
+    let time = humantime::parse_rfc3339_weak(&text).map_err(|err| Error::Time(err.to_string()))?;
+    let timestamp = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| Error::Time(err.to_string()))?
+        .as_secs();
+    // Local time is ahead of UTC by the offset
+    timestamp
+        .checked_add_signed(-offset)
+        .ok_or_else(|| Error::Time(format!("Time is out of range: {text}")))
+}
+
+/// Split a numeric UTC offset (`+02:00`, `-05:30`) off the end of an RFC 3339
+/// timestamp, returning the timestamp and the offset in seconds. humantime
+/// only parses UTC timestamps.
+fn split_offset(text: &str) -> Result<(&str, i64), Error> {
+    // Offsets follow the time, so the date and time come first
+    let Some((time, offset)) = text
+        .len()
+        .checked_sub(6)
+        .filter(|split| *split > 10 && text.is_char_boundary(*split))
+        .map(|split| text.split_at(split))
+    else {
+        return Ok((text, 0));
+    };
+    let sign = match offset.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return Ok((text, 0)),
+    };
+    let invalid = || Error::Time(format!("Invalid UTC offset: {offset}"));
+    let (hours, minutes) = offset[1..].split_once(':').ok_or_else(invalid)?;
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok((time, sign * (hours * 60 * 60 + minutes * 60)))
+}
+
+/// Parse a point in time, given either as a duration from now
+/// (`30d`, `1h 30m`, `2weeks`), or an absolute time (see [`parse_time`]).
+pub fn parse_time_or_duration(text: &str) -> Result<u64, Error> {
+    match humantime::parse_duration(text) {
+        Ok(duration) => Ok(now().saturating_add(duration.as_secs())),
+        Err(_) => parse_time(text),
+    }
+}
+
+/// Format a UNIX timestamp in seconds as an RFC 3339 timestamp.
+/// Timestamps that can't be formatted as RFC 3339 (after the year 9999) are
+/// formatted as the raw number of seconds.
+pub fn format_time(timestamp: u64) -> String {
+    let Some(time) = UNIX_EPOCH.checked_add(Duration::from_secs(timestamp)) else {
+        return timestamp.to_string();
+    };
+    let mut formatted = String::new();
+    match write!(formatted, "{}", humantime::format_rfc3339_seconds(time)) {
+        Ok(()) => formatted,
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_unix_timestamp() {
+        assert_eq!(parse_time("1700000000").unwrap(), 1700000000);
+    }
+
+    #[test]
+    fn test_parse_time_rfc3339() {
+        assert_eq!(parse_time("2024-01-01T00:00:00Z").unwrap(), 1704067200);
+        assert_eq!(parse_time("2024-01-01T00:00:10").unwrap(), 1704067210);
+    }
+
+    #[test]
+    fn test_parse_time_rfc3339_offset() {
+        assert_eq!(parse_time("2024-01-01T02:00:00+02:00").unwrap(), 1704067200);
+        assert_eq!(parse_time("2023-12-31T18:30:00-05:30").unwrap(), 1704067200);
+        assert_eq!(parse_time("2024-01-01T00:00:00+00:00").unwrap(), 1704067200);
+        assert_eq!(
+            parse_time("2024-01-01T02:00:00.5+02:00").unwrap(),
+            1704067200
+        );
+        assert!(parse_time("2024-01-01T00:00:00+24:00").is_err());
+        assert!(parse_time("1970-01-01T00:00:00+01:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_date() {
+        assert_eq!(parse_time("2024-01-01").unwrap(), 1704067200);
+    }
+
+    #[test]
+    fn test_parse_time_or_duration() {
+        let before = now();
+        let timestamp = parse_time_or_duration("30d").unwrap();
+        assert!(timestamp >= before + 30 * 24 * 60 * 60);
+        assert!(timestamp <= now() + 30 * 24 * 60 * 60);
+
+        assert_eq!(
+            parse_time_or_duration("2024-01-01T00:00:00Z").unwrap(),
+            1704067200
+        );
+        assert_eq!(parse_time_or_duration("1704067200").unwrap(), 1704067200);
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(1704067200), "2024-01-01T00:00:00Z");
+        // Out of range for RFC 3339
+        assert_eq!(format_time(u64::MAX), u64::MAX.to_string());
+        assert_eq!(format_time(300_000_000_000), "300000000000");
+    }
+
+    #[test]
+    fn test_parse_time_invalid() {
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("").is_err());
+    }
+}
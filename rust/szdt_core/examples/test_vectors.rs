@@ -0,0 +1,55 @@
+//! Generate or check the cross-implementation test vector suite.
+//!
+//! ```text
+//! cargo run -p szdt_core --example test_vectors -- generate ../test-vectors
+//! cargo run -p szdt_core --example test_vectors -- check ../test-vectors/vectors.cbor
+//! ```
+use std::path::PathBuf;
+use szdt_core::test_vectors::{Suite, generate};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["generate", dir] => {
+            let dir = PathBuf::from(dir);
+            let suite = generate().expect("Unable to generate test vectors");
+            std::fs::create_dir_all(&dir).expect("Unable to create directory");
+            std::fs::write(
+                dir.join("vectors.cbor"),
+                suite.to_cbor().expect("Unable to encode test vectors"),
+            )
+            .expect("Unable to write vectors.cbor");
+            std::fs::write(
+                dir.join("vectors.json"),
+                suite.to_json().expect("Unable to encode test vectors"),
+            )
+            .expect("Unable to write vectors.json");
+            println!("Wrote {} vectors to {}", suite.vectors.len(), dir.display());
+        }
+        ["check", file] => {
+            let bytes = std::fs::read(file).expect("Unable to read test vectors");
+            let suite = Suite::from_cbor(&bytes).expect("Unable to decode test vectors");
+            let failures = suite.check();
+            for (name, failure) in &failures {
+                println!("FAIL {name}: {failure}");
+            }
+            println!(
+                "{} of {} vectors passed",
+                suite.vectors.len() - failures.len(),
+                suite.vectors.len()
+            );
+            if !failures.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("Usage: test_vectors generate DIR | check FILE");
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::cbor_seq::{CborSeqReader, CborSeqWriter};
+use crate::error::Error;
+use crate::header::{self, RESERVED_UNPROTECTED_HEADERS};
+use crate::memo::{Memo, UnprotectedHeaders};
+use crate::value::{Block, BlockDecoder};
+use std::io::{BufRead, Write};
+
+/// Changes to the unprotected headers of a memo.
+///
+/// Unprotected headers aren't covered by the memo signature, so intermediaries
+/// may annotate memos with metadata (like caching hints or routing info)
+/// without invalidating them. Annotations never touch protected headers.
+#[derive(Debug, Clone, Default)]
+pub struct Annotation {
+    /// Headers to set
+    pub set: Vec<(String, cbor4ii::core::Value)>,
+    /// Headers to remove
+    pub remove: Vec<String>,
+}
+
+impl Annotation {
+    /// Is the annotation a no-op?
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+
+    /// Apply the annotation to unprotected headers.
+    /// Headers are removed before new headers are set.
+    pub fn apply(&self, headers: &mut UnprotectedHeaders) -> Result<(), Error> {
+        for key in &self.remove {
+            header::check_key(key, RESERVED_UNPROTECTED_HEADERS)?;
+            headers.remove_header(key);
+        }
+        for (key, value) in &self.set {
+            headers.set_header(key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrite a sequence, applying the annotation to every memo for which
+/// `select` returns true. Other blocks are copied through unchanged.
+/// Returns the number of memos annotated.
+pub fn annotate_seq<R, W, F>(
+    reader: &mut CborSeqReader<R>,
+    writer: &mut CborSeqWriter<W>,
+    annotation: &Annotation,
+    select: F,
+) -> Result<usize, Error>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&Memo) -> bool,
+{
+    rewrite_memos(reader, writer, |memo| {
+        if !select(memo) {
+            return Ok(false);
+        }
+        annotation.apply(&mut memo.unprotected)?;
+        Ok(true)
+    })
+}
+
+/// Rewrite a sequence, calling `update` on every memo. `update` returns
+/// whether it changed the memo. Other blocks are copied through unchanged.
+/// Returns the number of memos changed.
+pub fn rewrite_memos<R, W, F>(
+    reader: &mut CborSeqReader<R>,
+    writer: &mut CborSeqWriter<W>,
+    mut update: F,
+) -> Result<usize, Error>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&mut Memo) -> Result<bool, Error>,
+{
+    let decoder = BlockDecoder::new();
+    let mut count = 0;
+    loop {
+        match decoder.read_block(reader) {
+            Ok(Block::Memo(mut memo)) => {
+                if update(&mut memo)? {
+                    count += 1;
+                }
+                writer.write_block(&memo)?;
+            }
+            Ok(block) => writer.write_block(&block)?,
+            Err(Error::Eof) => return Ok(count),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::ed25519_key_material::Ed25519KeyMaterial;
+    use crate::link::ToLink;
+    use std::io::Cursor;
+
+    fn create_signed_memo(path: &str, body: &Bytes) -> Memo {
+        let key = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let mut memo = Memo::for_body(body).unwrap();
+        memo.protected.path = Some(path.to_string());
+        memo.sign(&key).unwrap();
+        memo
+    }
+
+    fn write_seq(memos: &[(Memo, Bytes)]) -> Vec<u8> {
+        let mut writer = CborSeqWriter::new(Vec::new());
+        for (memo, body) in memos {
+            writer.write_block(memo).unwrap();
+            writer.write_block(body).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn read_memos(data: Vec<u8>) -> Vec<Memo> {
+        let mut reader = CborSeqReader::new(Cursor::new(data));
+        let decoder = BlockDecoder::new();
+        let mut memos = Vec::new();
+        loop {
+            match decoder.read_block(&mut reader) {
+                Ok(Block::Memo(memo)) => memos.push(*memo),
+                Ok(_) => {}
+                Err(Error::Eof) => return memos,
+                Err(err) => panic!("{err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_annotate_selected_memos() {
+        let a_body = Bytes(b"a".to_vec());
+        let b_body = Bytes(b"b".to_vec());
+        let a = create_signed_memo("a.txt", &a_body);
+        let b = create_signed_memo("b.txt", &b_body);
+        let data = write_seq(&[(a.clone(), a_body.clone()), (b.clone(), b_body.clone())]);
+
+        let annotation = Annotation {
+            set: vec![(
+                "cache-control".to_string(),
+                cbor4ii::core::Value::Text("max-age=3600".to_string()),
+            )],
+            remove: vec![],
+        };
+        let mut reader = CborSeqReader::new(Cursor::new(data));
+        let mut writer = CborSeqWriter::new(Vec::new());
+        let count = annotate_seq(&mut reader, &mut writer, &annotation, |memo| {
+            memo.protected.path.as_deref() == Some("a.txt")
+        })
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let output = writer.into_inner();
+        let memos = read_memos(output.clone());
+        assert_eq!(memos.len(), 2);
+        for memo in &memos {
+            memo.verify().unwrap();
+        }
+        assert_eq!(memos[0].protected, a.protected);
+        assert_eq!(
+            memos[0]
+                .unprotected
+                .get_header::<String>("cache-control")
+                .unwrap()
+                .as_deref(),
+            Some("max-age=3600")
+        );
+        assert_eq!(memos[1], b);
+
+        // Bodies are copied through unchanged
+        let mut reader = CborSeqReader::new(Cursor::new(output));
+        let _: Memo = reader.read_block().unwrap();
+        let body: Bytes = reader.read_block().unwrap();
+        assert_eq!(body.to_link().unwrap(), a.protected.src);
+    }
+
+    #[test]
+    fn test_annotate_remove() {
+        let body = Bytes(b"a".to_vec());
+        let mut memo = create_signed_memo("a.txt", &body);
+        memo.unprotected.set_header("x-route", &"eu").unwrap();
+        let data = write_seq(&[(memo, body)]);
+
+        let annotation = Annotation {
+            set: vec![],
+            remove: vec!["x-route".to_string()],
+        };
+        let mut reader = CborSeqReader::new(Cursor::new(data));
+        let mut writer = CborSeqWriter::new(Vec::new());
+        annotate_seq(&mut reader, &mut writer, &annotation, |_| true).unwrap();
+
+        let memos = read_memos(writer.into_inner());
+        assert!(memos[0].unprotected.extra.is_empty());
+        memos[0].verify().unwrap();
+    }
+
+    #[test]
+    fn test_annotate_rejects_signature_header() {
+        let mut headers = UnprotectedHeaders::default();
+        let annotation = Annotation {
+            set: vec![],
+            remove: vec!["sig".to_string()],
+        };
+        assert!(matches!(
+            annotation.apply(&mut headers),
+            Err(Error::HeaderReserved(_))
+        ));
+    }
+}
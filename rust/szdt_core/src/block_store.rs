@@ -0,0 +1,337 @@
+//! Storage for content-addressed blocks.
+//!
+//! A block store is a place to get and put blocks by `Hash`, where the hash is
+//! the Blake3 hash of the block's CBOR/c encoding (see `ToLink`). Blocks are
+//! stored as encoded bytes, so a block read from the store hashes to the same
+//! content address it was stored under.
+use crate::cbor_seq::CborSeqReader;
+use crate::dag::BlockSource;
+use crate::error::Error;
+use crate::hash::Hash;
+use cbor4ii::core::Value;
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Iterator over the hashes of blocks in a store
+pub type BlockIter<'a> = Box<dyn Iterator<Item = Result<Hash, Error>> + 'a>;
+
+/// A place to get and put CBOR blocks by content address
+pub trait BlockStore {
+    /// Get the CBOR/c encoded bytes of a block
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Put the CBOR/c encoded bytes of a block, returning its content address.
+    /// Putting a block that is already stored is a no-op.
+    fn put(&mut self, block: &[u8]) -> Result<Hash, Error>;
+
+    /// Is a block with the given content address stored?
+    fn has(&self, hash: &Hash) -> Result<bool, Error>;
+
+    /// Iterate over the hashes of every block in the store
+    fn iter(&self) -> Result<BlockIter<'_>, Error>;
+
+    /// Serialize a value to CBOR/c and put it in the store
+    fn put_value<T: Serialize>(&mut self, value: &T) -> Result<Hash, Error>
+    where
+        Self: Sized,
+    {
+        let block = serde_cbor_core::to_vec(value)?;
+        self.put(&block)
+    }
+
+    /// Get a block, deserializing it to the given type
+    fn get_value<T: DeserializeOwned>(&self, hash: &Hash) -> Result<Option<T>, Error>
+    where
+        Self: Sized,
+    {
+        match self.get(hash)? {
+            Some(block) => Ok(Some(serde_cbor_core::from_slice(&block)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<S: BlockStore> BlockSource for S {
+    fn get_block(&self, hash: &Hash) -> Result<Option<Value>, Error> {
+        self.get_value(hash)
+    }
+}
+
+/// Put every block in a sequence into a store.
+/// Returns the content addresses of the blocks, in sequence order.
+pub fn import_seq<R, S>(reader: &mut CborSeqReader<R>, store: &mut S) -> Result<Vec<Hash>, Error>
+where
+    R: BufRead,
+    S: BlockStore,
+{
+    let mut hashes = Vec::new();
+    loop {
+        match reader.read_block::<Value>() {
+            Ok(block) => hashes.push(store.put_value(&block)?),
+            Err(Error::Eof) => return Ok(hashes),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Check that block bytes match the content address they were stored under.
+/// Stores that don't derive the address from the bytes on read should call
+/// this in `get`.
+pub fn check_block(hash: &Hash, block: &[u8]) -> Result<(), Error> {
+    let actual = Hash::new(block);
+    if &actual != hash {
+        return Err(Error::IntegrityError(format!(
+            "Block hash does not match content address. Expected {hash}. Got: {actual}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check a block store implementation against the behavior expected of every
+/// `BlockStore`. The store must start out empty. Panics on failure.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn check_store<S: BlockStore>(store: &mut S) {
+    use crate::bytes::Bytes;
+    use crate::link::ToLink;
+
+    let body = Bytes(b"Hello World".to_vec());
+    let hash = store.put_value(&body).unwrap();
+    assert_eq!(hash, body.to_link().unwrap());
+    assert!(store.has(&hash).unwrap());
+    // Putting again is a no-op
+    assert_eq!(store.put_value(&body).unwrap(), hash);
+
+    let stored: Bytes = store.get_value(&hash).unwrap().unwrap();
+    assert_eq!(stored, body);
+    assert_eq!(
+        store.get(&hash).unwrap().unwrap(),
+        serde_cbor_core::to_vec(&body).unwrap()
+    );
+
+    let missing = Hash::new(b"missing");
+    assert!(!store.has(&missing).unwrap());
+    assert_eq!(store.get(&missing).unwrap(), None);
+
+    let other = store.put_value(&"other").unwrap();
+    let mut expected = vec![hash, other];
+    expected.sort();
+    let hashes: Vec<Hash> = store.iter().unwrap().map(|hash| hash.unwrap()).collect();
+    assert_eq!(hashes, expected);
+}
+
+/// In-memory block store
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlockStore {
+    blocks: BTreeMap<Hash, Vec<u8>>,
+}
+
+impl MemoryBlockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.blocks.get(hash).cloned())
+    }
+
+    fn put(&mut self, block: &[u8]) -> Result<Hash, Error> {
+        let hash = Hash::new(block);
+        self.blocks.entry(hash).or_insert_with(|| block.to_vec());
+        Ok(hash)
+    }
+
+    fn has(&self, hash: &Hash) -> Result<bool, Error> {
+        Ok(self.blocks.contains_key(hash))
+    }
+
+    fn iter(&self) -> Result<BlockIter<'_>, Error> {
+        Ok(Box::new(self.blocks.keys().map(|hash| Ok(*hash))))
+    }
+}
+
+/// Block store backed by a directory of files.
+/// Requires the `fs` feature.
+///
+/// Each block is stored in a file named after its content address, in a
+/// subdirectory named after the first two characters of the address:
+///
+/// ```text
+/// blocks/
+///   rx/
+///     rxizkt6bxw3cofdrdakhyhg7ccuedputtxib7omnmshnei3bwgza
+/// ```
+///
+/// Blocks are checked against their content address when read.
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone)]
+pub struct FileBlockStore {
+    root: PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FileBlockStore {
+    /// Open a block store in the given directory, creating it if needed
+    pub fn new(root: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(root)?;
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+
+    fn block_path(&self, hash: &Hash) -> PathBuf {
+        let name = hash.to_string();
+        self.root.join(&name[..2]).join(name)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl BlockStore for FileBlockStore {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(self.block_path(hash)) {
+            Ok(block) => {
+                check_block(hash, &block)?;
+                Ok(Some(block))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put(&mut self, block: &[u8]) -> Result<Hash, Error> {
+        let hash = Hash::new(block);
+        let path = self.block_path(&hash);
+        if path.exists() {
+            return Ok(hash);
+        }
+        let dir = path.parent().expect("Block path should have a parent");
+        fs::create_dir_all(dir)?;
+        // Write to a temporary file, then move it into place, so readers
+        // never see partially-written blocks.
+        // The temporary name is unique to this process and write, so
+        // concurrent writers of the same block don't clobber each other.
+        static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let tmp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, block)?;
+        if let Err(err) = fs::rename(&tmp_path, &path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err.into());
+        }
+        Ok(hash)
+    }
+
+    fn has(&self, hash: &Hash) -> Result<bool, Error> {
+        Ok(self.block_path(hash).exists())
+    }
+
+    fn iter(&self) -> Result<BlockIter<'_>, Error> {
+        let mut hashes = Vec::new();
+        for dir in fs::read_dir(&self.root)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                let file_name = file?.file_name();
+                let Some(hash) = file_name.to_str().and_then(parse_block_name) else {
+                    // Skip temporary files, and anything else that isn't a block
+                    continue;
+                };
+                hashes.push(hash);
+            }
+        }
+        hashes.sort();
+        Ok(Box::new(hashes.into_iter().map(Ok)))
+    }
+}
+
+/// Parse a block file name (a lowercase base32 hash) into a hash
+#[cfg(feature = "fs")]
+fn parse_block_name(name: &str) -> Option<Hash> {
+    // `Hash::parse` also accepts hex, multibase and CIDs. Only the canonical
+    // form is a block file name, since `block_path` is the only way blocks
+    // are found.
+    let hash: Hash = name.parse().ok()?;
+    (hash.to_string() == name).then_some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::cbor_seq::CborSeqWriter;
+    use crate::dag::DagWriter;
+    use crate::memo::Memo;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_memory_block_store() {
+        check_store(&mut MemoryBlockStore::new());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_block_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&mut FileBlockStore::new(&dir.path().join("blocks")).unwrap());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_block_store_lists_only_block_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileBlockStore::new(dir.path()).unwrap();
+        let hash = store.put_value(&"Hello").unwrap();
+        // Other encodings of a hash aren't block file names
+        let dir = store.block_path(&hash).parent().unwrap().to_path_buf();
+        fs::write(dir.join(hash.to_hex()), b"").unwrap();
+        fs::write(dir.join(hash.to_string().to_uppercase()), b"").unwrap();
+        fs::write(dir.join(format!("b{hash}")), b"").unwrap();
+        let hashes: Vec<Hash> = store.iter().unwrap().map(|hash| hash.unwrap()).collect();
+        assert_eq!(hashes, vec![hash]);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_block_store_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = FileBlockStore::new(dir.path()).unwrap();
+        let hash = store.put_value(&"Hello").unwrap();
+        fs::write(store.block_path(&hash), b"corrupt").unwrap();
+        assert!(matches!(store.get(&hash), Err(Error::IntegrityError(_))));
+    }
+
+    #[test]
+    fn test_import_and_export_seq() {
+        let body = Bytes(b"Hello World".to_vec());
+        let memo = Memo::for_body(&body).unwrap();
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&memo).unwrap();
+        writer.write_block(&body).unwrap();
+        // Duplicate block
+        writer.write_block(&body).unwrap();
+
+        let mut store = MemoryBlockStore::new();
+        let mut reader = CborSeqReader::new(Cursor::new(writer.into_inner()));
+        let hashes = import_seq(&mut reader, &mut store).unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(store.iter().unwrap().count(), 2);
+
+        // Re-export the memo and its body from the store
+        let mut dag_writer = DagWriter::new(Vec::new());
+        let count = dag_writer.write_dag(&memo, &store).unwrap();
+        assert_eq!(count, 2);
+    }
+}
@@ -1,26 +1,81 @@
+use crate::conformance;
 use crate::error::Error;
+use crate::limits::{self, Limits};
 use serde::{de::DeserializeOwned, ser::Serialize};
-use std::io::{BufRead, Write};
+use std::io::{BufRead, Seek, SeekFrom, Write};
 
 /// A specialized reader for deserializes SZDT archives.
 /// SZDT archives are CBOR sequences with a particular shape.
 pub struct CborSeqReader<R> {
     reader: R,
+    strict: bool,
+    limits: Limits,
+    position: usize,
 }
 
 impl<R: BufRead> CborSeqReader<R> {
     pub fn new(reader: R) -> Self {
-        Self { reader }
+        Self {
+            reader,
+            strict: false,
+            limits: Limits::default(),
+            position: 0,
+        }
     }
 
-    /// Deserialize next block
+    /// Enable strict mode. In strict mode, every block is checked against the
+    /// full set of CBOR/c rules (see `conformance`), and violations are
+    /// reported with their byte offset in the sequence.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the resource limits enforced when reading blocks.
+    /// Defaults to `Limits::default()`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Resource limits enforced when reading blocks
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Byte offset of the next block in the sequence
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Deserialize next block.
+    /// Blocks must be canonical CBOR/c. Returns `Error::NonCanonical` otherwise,
+    /// or `Error::Conformance` in strict mode.
+    ///
+    /// Blocks that exceed the reader's limits are rejected before they are
+    /// decoded. Once a block has been rejected for exceeding a limit, the
+    /// reader is no longer positioned at a block boundary.
     pub fn read_block<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
-        let result: T = match serde_cbor_core::de::from_reader_once(&mut self.reader) {
-            Ok(value) => value,
-            Err(serde_cbor_core::DecodeError::Eof) => return Err(Error::Eof),
-            Err(err) => return Err(Error::CborDecode(err.to_string())),
-        };
-        Ok(result)
+        let bytes = self.read_block_bytes()?;
+        Ok(serde_cbor_core::from_slice(&bytes)?)
+    }
+
+    /// Read the encoded bytes of the next block, without deserializing it.
+    /// Checked the same way as `read_block`, so the bytes are always
+    /// canonical CBOR/c, and hashing them gives the block's content address.
+    pub fn read_block_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let bytes = limits::read_item(&mut self.reader, &self.limits)?;
+        let offset = self.position;
+        self.position += bytes.len();
+        if self.strict {
+            conformance::check_block(&bytes).map_err(|mut violation| {
+                violation.offset += offset;
+                Error::Conformance(violation)
+            })?;
+        } else {
+            check_canonical(&bytes)?;
+        }
+        Ok(bytes)
     }
 
     /// Unwrap inner reader
@@ -29,6 +84,53 @@ impl<R: BufRead> CborSeqReader<R> {
     }
 }
 
+impl<R: BufRead + Seek> CborSeqReader<R> {
+    /// Deserialize the block at a byte offset in the sequence (see
+    /// `position`), then return to the current position.
+    pub fn read_block_at<T: DeserializeOwned>(&mut self, offset: usize) -> Result<T, Error> {
+        let bytes = self.read_block_bytes_at(offset)?;
+        Ok(serde_cbor_core::from_slice(&bytes)?)
+    }
+
+    /// Read the encoded bytes of the block at a byte offset in the sequence
+    /// (see `position`), then return to the current position.
+    /// Checked the same way as `read_block_bytes`.
+    pub fn read_block_bytes_at(&mut self, offset: usize) -> Result<Vec<u8>, Error> {
+        let resume = self.reader.stream_position()?;
+        let start = resume - self.position as u64;
+        self.reader.seek(SeekFrom::Start(start + offset as u64))?;
+        let position = std::mem::replace(&mut self.position, offset);
+        let result = self.read_block_bytes();
+        self.reader.seek(SeekFrom::Start(resume))?;
+        self.position = position;
+        result
+    }
+}
+
+/// Check that bytes are a single item of canonical CBOR/c: encoded by the
+/// CBOR/c rules (see `conformance::check_encoding`, which leaves float widths
+/// to strict mode), and re-encoding the decoded item produces exactly the
+/// same bytes.
+/// Input that ends mid-item is a decode error rather than a non-canonical one.
+pub fn check_canonical(bytes: &[u8]) -> Result<(), Error> {
+    if let Err(violation) = conformance::check_encoding(bytes) {
+        return Err(match violation.rule {
+            conformance::Rule::Truncated => Error::CborDecode(violation.to_string()),
+            _ => Error::NonCanonical(violation.to_string()),
+        });
+    }
+    let value: cbor4ii::core::Value = serde_cbor_core::from_slice(bytes)?;
+    let canonical = serde_cbor_core::to_vec(&value)?;
+    if canonical != bytes {
+        return Err(Error::NonCanonical(format!(
+            "Expected {} bytes of CBOR/c. Got {} bytes.",
+            canonical.len(),
+            bytes.len()
+        )));
+    }
+    Ok(())
+}
+
 /// Represents the metadata portion of an SZDT archive
 pub struct CborSeqWriter<W> {
     writer: W,
@@ -196,4 +298,132 @@ mod tests {
         assert_eq!(bytes_written, data.len());
         assert_eq!(buffer, data);
     }
+
+    #[test]
+    fn test_reader_rejects_non_canonical() {
+        // 1, encoded as a one-byte integer, followed by 1 in canonical form
+        let cursor = Cursor::new(vec![0x18, 0x01, 0x01]);
+        let mut reader = CborSeqReader::new(cursor);
+        let result: Result<u8, Error> = reader.read_block();
+        assert!(matches!(result, Err(Error::NonCanonical(_))));
+        let value: u8 = reader.read_block().unwrap();
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn test_read_block_bytes() {
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&"Hello").unwrap();
+        let data = writer.into_inner();
+        let mut reader = CborSeqReader::new(Cursor::new(data.clone()));
+        assert_eq!(reader.read_block_bytes().unwrap(), data);
+        assert!(matches!(reader.read_block_bytes(), Err(Error::Eof)));
+
+        // Non-canonical blocks are rejected, as with `read_block`
+        let mut reader = CborSeqReader::new(Cursor::new(vec![0x18, 0x01]));
+        assert!(matches!(
+            reader.read_block_bytes(),
+            Err(Error::NonCanonical(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_canonical_map_order() {
+        // {"b": 1, "a": 2}
+        let unsorted = [0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02];
+        assert!(matches!(
+            check_canonical(&unsorted),
+            Err(Error::NonCanonical(_))
+        ));
+        // {"a": 2, "b": 1}
+        let sorted = [0xa2, 0x61, 0x61, 0x02, 0x61, 0x62, 0x01];
+        check_canonical(&sorted).unwrap();
+    }
+
+    #[test]
+    fn test_check_canonical_encoding_rules() {
+        // {"a": 1, "a": 2} re-encodes to the same bytes, but has duplicate keys
+        for bytes in [
+            &[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02][..],
+            &[0x9f, 0x01, 0xff][..],
+        ] {
+            assert!(matches!(
+                check_canonical(bytes),
+                Err(Error::NonCanonical(_))
+            ));
+        }
+        // Truncated text string
+        assert!(matches!(
+            check_canonical(&[0x65, 0x68, 0x65]),
+            Err(Error::CborDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_written_floats() {
+        // Floats are written as doubles, which the default reader accepts
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&1.5f64).unwrap();
+        writer.write_block(&vec![0.25f64, -2.0]).unwrap();
+        let mut reader = CborSeqReader::new(Cursor::new(writer.into_inner()));
+        assert_eq!(reader.read_block::<f64>().unwrap(), 1.5);
+        assert_eq!(reader.read_block::<Vec<f64>>().unwrap(), vec![0.25, -2.0]);
+        assert!(matches!(reader.read_block::<f64>(), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn test_strict_reader_reports_offset() {
+        // 1, then 1.5 as a double-precision float
+        let mut data = vec![0x01];
+        data.extend(serde_cbor_core::to_vec(&1.5f64).unwrap());
+        let mut reader = CborSeqReader::new(Cursor::new(data)).with_strict(true);
+        let _: u8 = reader.read_block().unwrap();
+        let result: Result<f64, Error> = reader.read_block();
+        let Err(Error::Conformance(violation)) = result else {
+            panic!("Expected conformance error");
+        };
+        assert_eq!(violation.offset, 1);
+        assert_eq!(violation.rule, conformance::Rule::NonShortestFloat);
+    }
+
+    #[test]
+    fn test_reader_enforces_limits() {
+        let mut buffer = Vec::new();
+        let mut writer = CborSeqWriter::new(&mut buffer);
+        writer.write_block(&vec![1, 2, 3]).unwrap();
+        let limits = Limits {
+            max_entries: 2,
+            ..Limits::default()
+        };
+        let mut reader = CborSeqReader::new(Cursor::new(buffer)).with_limits(limits);
+        let result: Result<Vec<u8>, Error> = reader.read_block();
+        assert!(matches!(result, Err(Error::TooManyEntries(3, 2))));
+    }
+
+    #[test]
+    fn test_read_block_bytes_at() {
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&"first").unwrap();
+        writer.write_block(&"second").unwrap();
+        writer.write_block(&"third").unwrap();
+        // Read through a BufReader, so the underlying reader is ahead of
+        // the sequence position
+        let mut reader =
+            CborSeqReader::new(std::io::BufReader::new(Cursor::new(writer.into_inner())));
+        let first = reader.read_block_bytes().unwrap();
+        let offset = reader.position();
+        let second: String = reader.read_block().unwrap();
+        let position = reader.position();
+
+        assert_eq!(reader.read_block_bytes_at(0).unwrap(), first);
+        let block = reader.read_block_bytes_at(offset).unwrap();
+        assert_eq!(
+            serde_cbor_core::from_slice::<String>(&block).unwrap(),
+            second
+        );
+        // Reading resumes where it left off
+        assert_eq!(reader.position(), position);
+        let third: String = reader.read_block().unwrap();
+        assert_eq!(third, "third");
+    }
 }
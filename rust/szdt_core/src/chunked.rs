@@ -0,0 +1,185 @@
+//! Chunked file bodies.
+//!
+//! Large files may be split into chunks. The memo body of a chunked file is a
+//! `HashSeq` of chunk hashes, marked with a protected `chunked` header, and the
+//! chunks are stored as blocks after the body. Chunks are content addressed
+//! like any other body, so chunks shared between files, or between versions of
+//! an archive, only need to be stored and transferred once.
+//!
+//! When a chunked memo also has a `content-encoding`, each chunk is encoded
+//! separately. The `HashSeq` body itself is never encoded.
+use crate::bytes::Bytes;
+use crate::content_encoding::{content_encoding, decode_body};
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::hashseq::HashSeq;
+use crate::limits::Limits;
+use crate::memo::Memo;
+use std::io::{self, Read, Write};
+
+/// Header marking a memo body as a `HashSeq` of chunks
+pub const CHUNKED: &str = "chunked";
+
+/// Is the memo body a `HashSeq` of chunks?
+pub fn is_chunked(memo: &Memo) -> Result<bool, Error> {
+    Ok(memo.protected.get_header::<bool>(CHUNKED)?.unwrap_or(false))
+}
+
+/// Get the chunk hashes listed in a chunked body
+pub fn chunk_hashes(body: &Bytes) -> Result<HashSeq, Error> {
+    HashSeq::new(body.0.clone()).map_err(|err| Error::Chunked(err.to_string()))
+}
+
+/// Reassemble a chunked body, writing it to `writer`. Chunks are looked up
+/// with `get_chunk` one at a time, and decoded if the memo has a
+/// `content-encoding`, so only one chunk is held in memory at once.
+/// Fails if the file would be larger than `limits.max_file_size`. Returns the
+/// size of the file.
+///
+/// Chunks returned by `get_chunk` are trusted to match their hashes. Check the
+/// body against the memo checksum before reassembling it.
+pub fn write_chunked_body<W, F, E>(
+    memo: &Memo,
+    body: &Bytes,
+    limits: &Limits,
+    writer: &mut W,
+    mut get_chunk: F,
+) -> Result<u64, E>
+where
+    W: Write,
+    F: FnMut(&Hash) -> Result<Option<Bytes>, E>,
+    E: From<Error>,
+{
+    let encoding = content_encoding(memo)?;
+    let mut size: u64 = 0;
+    for hash in chunk_hashes(body)?.iter() {
+        let chunk =
+            get_chunk(&hash)?.ok_or_else(|| Error::Chunked(format!("Missing chunk {hash}")))?;
+        let reader: Box<dyn Read> = match encoding {
+            Some(encoding) => encoding.decoder(chunk.0.as_slice())?,
+            None => Box::new(chunk.0.as_slice()),
+        };
+        // Copy up to one byte past the limit, to tell if the file exceeds it
+        let remaining = (limits.max_file_size - size).saturating_add(1);
+        size += io::copy(&mut reader.take(remaining), writer).map_err(Error::from)?;
+        if size > limits.max_file_size {
+            return Err(Error::Chunked(format!(
+                "Chunked file exceeds the size limit of {} bytes",
+                limits.max_file_size
+            ))
+            .into());
+        }
+    }
+    Ok(size)
+}
+
+/// Reassemble a chunked body in memory (see `write_chunked_body`)
+pub fn read_chunked_body<F, E>(
+    memo: &Memo,
+    body: &Bytes,
+    limits: &Limits,
+    get_chunk: F,
+) -> Result<Bytes, E>
+where
+    F: FnMut(&Hash) -> Result<Option<Bytes>, E>,
+    E: From<Error>,
+{
+    let mut bytes = Vec::new();
+    write_chunked_body(memo, body, limits, &mut bytes, get_chunk)?;
+    Ok(Bytes(bytes))
+}
+
+/// Get the content of a file from its memo body: reassembled from its chunks
+/// if it is chunked, and decoded if it has a `content-encoding`.
+/// Check the body against the memo checksum first.
+pub fn read_file<F, E>(memo: &Memo, body: Bytes, limits: &Limits, get_chunk: F) -> Result<Bytes, E>
+where
+    F: FnMut(&Hash) -> Result<Option<Bytes>, E>,
+    E: From<Error>,
+{
+    if is_chunked(memo)? {
+        read_chunked_body(memo, &body, limits, get_chunk)
+    } else {
+        Ok(decode_body(memo, body, limits)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content_encoding::CONTENT_ENCODING;
+    use crate::link::ToLink;
+    use crate::test_vectors::HELLO_ZSTD;
+    use std::collections::HashMap;
+
+    /// Create a chunked memo and body for the given chunks
+    fn chunked_memo(chunks: &[Bytes]) -> (Memo, Bytes) {
+        let hashseq = HashSeq::from(chunks.iter().map(|chunk| chunk.to_link().unwrap()));
+        let body = Bytes(hashseq.into_vec());
+        let mut memo = Memo::for_body(&body).unwrap();
+        memo.protected.set_header(CHUNKED, &true).unwrap();
+        (memo, body)
+    }
+
+    #[test]
+    fn test_read_chunked_body() {
+        let chunks = vec![Bytes(b"Hello ".to_vec()), Bytes(b"World".to_vec())];
+        let (memo, body) = chunked_memo(&chunks);
+        assert!(is_chunked(&memo).unwrap());
+        let store: HashMap<Hash, Bytes> = chunks
+            .iter()
+            .map(|chunk| (chunk.to_link().unwrap(), chunk.clone()))
+            .collect();
+
+        let get_chunk = |hash: &Hash| Ok::<_, Error>(store.get(hash).cloned());
+        let bytes = read_chunked_body(&memo, &body, &Limits::default(), get_chunk).unwrap();
+        assert_eq!(bytes.0, b"Hello World");
+        let bytes = read_file(&memo, body.clone(), &Limits::default(), get_chunk).unwrap();
+        assert_eq!(bytes.0, b"Hello World");
+
+        let missing = read_chunked_body(&memo, &body, &Limits::default(), |_| Ok(None));
+        assert!(matches!(missing, Err(Error::Chunked(_))));
+    }
+
+    #[test]
+    fn test_read_encoded_chunks() {
+        let chunk = Bytes(HELLO_ZSTD.to_vec());
+        let (mut memo, body) = chunked_memo(&[chunk.clone(), chunk.clone()]);
+        memo.protected
+            .set_header(CONTENT_ENCODING, &"zstd")
+            .unwrap();
+        // The HashSeq body itself is not decoded
+        let bytes = read_file(&memo, body, &Limits::default(), |_| {
+            Ok::<_, Error>(Some(chunk.clone()))
+        })
+        .unwrap();
+        assert_eq!(bytes.0, b"Hello WorldHello World");
+    }
+
+    #[test]
+    fn test_chunked_file_size_limit() {
+        // A HashSeq that repeats one chunk many times
+        let chunk = Bytes(HELLO_ZSTD.to_vec());
+        let (mut memo, body) = chunked_memo(&vec![chunk.clone(); 100]);
+        memo.protected
+            .set_header(CONTENT_ENCODING, &"zstd")
+            .unwrap();
+        let get_chunk = |_: &Hash| Ok::<_, Error>(Some(chunk.clone()));
+
+        let limits = Limits {
+            max_file_size: 100 * 11,
+            ..Limits::default()
+        };
+        let mut out = Vec::new();
+        let size = write_chunked_body(&memo, &body, &limits, &mut out, get_chunk).unwrap();
+        assert_eq!(size, 100 * 11);
+        assert_eq!(out.len(), 100 * 11);
+
+        let limits = Limits {
+            max_file_size: 100 * 11 - 1,
+            ..Limits::default()
+        };
+        let result = write_chunked_body(&memo, &body, &limits, &mut io::sink(), get_chunk);
+        assert!(matches!(result, Err(Error::Chunked(_))));
+    }
+}
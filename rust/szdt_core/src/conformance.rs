@@ -0,0 +1,547 @@
+//! Strict CBOR/c conformance checking.
+//!
+//! SZDT requires the deterministic [CBOR/c](https://datatracker.ietf.org/doc/draft-rundgren-cbor-core/)
+//! profile, so that every value has exactly one encoding. The checker walks
+//! the encoded bytes directly (rather than decoding and re-encoding), so it
+//! can report the byte offset of the first violation, and the rule violated.
+//!
+//! Items are walked with an explicit stack rather than by recursion, so deeply
+//! nested input can't overflow the call stack.
+//!
+//! Note that `serde_cbor_core` always encodes floats as 64-bit floats, so
+//! values containing floats that fit in a shorter encoding will not pass the
+//! shortest float rule. Memo headers can't contain floats for this reason
+//! (see `header::check_value`).
+use serde::{Deserialize, Serialize};
+
+/// A CBOR/c rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+pub enum Rule {
+    /// Input ended in the middle of an item
+    Truncated,
+    /// Additional info values 28-30 are reserved
+    ReservedAdditionalInfo,
+    /// Strings, arrays and maps must have definite lengths
+    IndefiniteLength,
+    /// Unexpected "break" outside of an indefinite-length item
+    UnexpectedBreak,
+    /// Integers, lengths and tags must use the shortest encoding
+    NonShortestInteger,
+    /// Floats must use the shortest encoding that preserves their value
+    NonShortestFloat,
+    /// Only `false`, `true` and `null` simple values are allowed
+    InvalidSimpleValue,
+    /// Text strings must be valid UTF-8
+    InvalidUtf8,
+    /// Map keys must be sorted by their encoded bytes
+    UnsortedKeys,
+    /// Map keys must be unique
+    DuplicateKey,
+    /// Memo headers must not be null. Absent optional headers are omitted.
+    NullHeader { key: String },
+    /// Bytes found after the end of the item
+    TrailingBytes,
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rule::Truncated => write!(f, "unexpected end of input"),
+            Rule::ReservedAdditionalInfo => write!(f, "reserved additional info value"),
+            Rule::IndefiniteLength => write!(f, "indefinite length"),
+            Rule::UnexpectedBreak => write!(f, "unexpected break"),
+            Rule::NonShortestInteger => write!(f, "integer not in shortest form"),
+            Rule::NonShortestFloat => write!(f, "float not in shortest form"),
+            Rule::InvalidSimpleValue => write!(f, "invalid simple value"),
+            Rule::InvalidUtf8 => write!(f, "invalid UTF-8 in text string"),
+            Rule::UnsortedKeys => write!(f, "map keys not sorted"),
+            Rule::DuplicateKey => write!(f, "duplicate map key"),
+            Rule::NullHeader { key } => write!(f, "null value for header {key}"),
+            Rule::TrailingBytes => write!(f, "trailing bytes"),
+        }
+    }
+}
+
+/// A rule violation at a byte offset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// Byte offset of the offending item
+    pub offset: usize,
+    pub rule: Rule,
+}
+
+impl Violation {
+    fn new(offset: usize, rule: Rule) -> Self {
+        Self { offset, rule }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.rule, self.offset)
+    }
+}
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const NULL: u8 = 0xf6;
+
+/// Head of a CBOR item
+struct Head {
+    major: u8,
+    /// Additional info
+    info: u8,
+    /// Argument (or raw float bits for floats)
+    arg: u64,
+    /// Offset of the item content, after the head
+    end: usize,
+}
+
+fn read_head(bytes: &[u8], offset: usize) -> Result<Head, Violation> {
+    let Some(&initial) = bytes.get(offset) else {
+        return Err(Violation::new(offset, Rule::Truncated));
+    };
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    let size = match info {
+        0..=23 => 0,
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        28..=30 => return Err(Violation::new(offset, Rule::ReservedAdditionalInfo)),
+        _ => {
+            return match major {
+                MAJOR_SIMPLE => Err(Violation::new(offset, Rule::UnexpectedBreak)),
+                MAJOR_BYTES | MAJOR_TEXT | MAJOR_ARRAY | MAJOR_MAP => {
+                    Err(Violation::new(offset, Rule::IndefiniteLength))
+                }
+                _ => Err(Violation::new(offset, Rule::ReservedAdditionalInfo)),
+            };
+        }
+    };
+    let start = offset + 1;
+    let end = start + size;
+    let Some(arg_bytes) = bytes.get(start..end) else {
+        return Err(Violation::new(offset, Rule::Truncated));
+    };
+    let arg = if size == 0 {
+        info as u64
+    } else {
+        arg_bytes
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+    };
+    Ok(Head {
+        major,
+        info,
+        arg,
+        end,
+    })
+}
+
+/// Is the argument encoded in the fewest bytes possible?
+fn is_shortest_arg(head: &Head) -> bool {
+    match head.info {
+        24 => head.arg >= 24,
+        25 => head.arg > 0xff,
+        26 => head.arg > 0xffff,
+        27 => head.arg > 0xffff_ffff,
+        _ => true,
+    }
+}
+
+/// Can the value be represented exactly as a float with the given number of
+/// significand bits (including the implicit bit), minimum exponent (of the
+/// lowest subnormal bit) and maximum finite value?
+fn fits_float(value: f64, significand_bits: u32, min_exp: i32, max: f64) -> bool {
+    if !value.is_finite() || value == 0.0 {
+        // Infinities and zeros have exact encodings at every width
+        return true;
+    }
+    if value.abs() > max {
+        return false;
+    }
+    let bits = value.to_bits();
+    let exp_bits = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = bits & ((1 << 52) - 1);
+    // value = mantissa * 2^exp
+    let (mut mantissa, mut exp) = if exp_bits == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), exp_bits - 1075)
+    };
+    let zeros = mantissa.trailing_zeros();
+    mantissa >>= zeros;
+    exp += zeros as i32;
+    exp >= min_exp && 64 - mantissa.leading_zeros() <= significand_bits
+}
+
+fn fits_f16(value: f64) -> bool {
+    fits_float(value, 11, -24, 65504.0)
+}
+
+fn fits_f32(value: f64) -> bool {
+    fits_float(value, 24, -149, f32::MAX as f64)
+}
+
+fn f16_is_nan(bits: u16) -> bool {
+    bits & 0x7c00 == 0x7c00 && bits & 0x03ff != 0
+}
+
+/// Check a float is in its shortest form. NaN must be encoded as the
+/// half-precision quiet NaN `0xf97e00`.
+fn is_shortest_float(head: &Head) -> bool {
+    match head.info {
+        25 => !f16_is_nan(head.arg as u16) || head.arg == 0x7e00,
+        26 => {
+            let value = f32::from_bits(head.arg as u32) as f64;
+            !value.is_nan() && !fits_f16(value)
+        }
+        27 => {
+            let value = f64::from_bits(head.arg);
+            !value.is_nan() && !fits_f32(value)
+        }
+        _ => true,
+    }
+}
+
+fn length(head: &Head) -> Result<usize, Violation> {
+    usize::try_from(head.arg).map_err(|_| Violation::new(head.end, Rule::Truncated))
+}
+
+/// An array, map or tag whose items are being checked
+struct Frame {
+    /// Items left to check. Map entries count as two items, key and value.
+    remaining: usize,
+    is_map: bool,
+    /// Offset of the current map key
+    key_start: usize,
+    /// Byte range of the previous map key
+    prev_key: Option<(usize, usize)>,
+}
+
+impl Frame {
+    fn new(remaining: usize, is_map: bool) -> Self {
+        Self {
+            remaining,
+            is_map,
+            key_start: 0,
+            prev_key: None,
+        }
+    }
+
+    /// Is the next item a map key?
+    fn expects_key(&self) -> bool {
+        self.is_map && self.remaining.is_multiple_of(2)
+    }
+}
+
+/// Check a single item starting at `offset`, returning the offset of the end
+/// of the item. Float widths are only checked if `check_floats` is set.
+fn check_item(bytes: &[u8], offset: usize, check_floats: bool) -> Result<usize, Violation> {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut pos = offset;
+    loop {
+        let item_start = pos;
+        if let Some(frame) = stack.last_mut().filter(|frame| frame.expects_key()) {
+            frame.key_start = item_start;
+        }
+        let head = read_head(bytes, item_start)?;
+        if head.major != MAJOR_SIMPLE && !is_shortest_arg(&head) {
+            return Err(Violation::new(item_start, Rule::NonShortestInteger));
+        }
+        match head.major {
+            MAJOR_UNSIGNED | MAJOR_NEGATIVE => pos = head.end,
+            MAJOR_BYTES | MAJOR_TEXT => {
+                let end = head
+                    .end
+                    .checked_add(length(&head)?)
+                    .filter(|end| *end <= bytes.len())
+                    .ok_or(Violation::new(item_start, Rule::Truncated))?;
+                if head.major == MAJOR_TEXT && std::str::from_utf8(&bytes[head.end..end]).is_err() {
+                    return Err(Violation::new(item_start, Rule::InvalidUtf8));
+                }
+                pos = end;
+            }
+            MAJOR_ARRAY | MAJOR_MAP => {
+                let is_map = head.major == MAJOR_MAP;
+                let len = length(&head)?;
+                let items = if is_map {
+                    len.checked_mul(2)
+                } else {
+                    Some(len)
+                }
+                .ok_or(Violation::new(head.end, Rule::Truncated))?;
+                pos = head.end;
+                if items > 0 {
+                    stack.push(Frame::new(items, is_map));
+                    continue;
+                }
+            }
+            MAJOR_TAG => {
+                pos = head.end;
+                stack.push(Frame::new(1, false));
+                continue;
+            }
+            _ => match head.info {
+                20..=22 => pos = head.end,
+                25..=27 if !check_floats || is_shortest_float(&head) => pos = head.end,
+                25..=27 => return Err(Violation::new(item_start, Rule::NonShortestFloat)),
+                _ => return Err(Violation::new(item_start, Rule::InvalidSimpleValue)),
+            },
+        }
+        // The item ending at `pos` is complete. Complete every container it
+        // was the last item of.
+        loop {
+            let Some(frame) = stack.last_mut() else {
+                return Ok(pos);
+            };
+            let was_key = frame.expects_key();
+            frame.remaining -= 1;
+            if was_key {
+                let key = &bytes[frame.key_start..pos];
+                if let Some((prev_start, prev_end)) = frame.prev_key {
+                    match bytes[prev_start..prev_end].cmp(key) {
+                        std::cmp::Ordering::Equal => {
+                            return Err(Violation::new(frame.key_start, Rule::DuplicateKey));
+                        }
+                        std::cmp::Ordering::Greater => {
+                            return Err(Violation::new(frame.key_start, Rule::UnsortedKeys));
+                        }
+                        std::cmp::Ordering::Less => {}
+                    }
+                }
+                frame.prev_key = Some((frame.key_start, pos));
+            }
+            if frame.remaining > 0 {
+                break;
+            }
+            stack.pop();
+        }
+    }
+}
+
+/// Get the entries of a well-formed map at `offset` as
+/// `(key bytes, value offset, value end)`. Returns `None` if the item is not a map.
+fn map_entries(bytes: &[u8], offset: usize) -> Option<Vec<(&[u8], usize, usize)>> {
+    let head = read_head(bytes, offset).ok()?;
+    if head.major != MAJOR_MAP {
+        return None;
+    }
+    let mut entries = Vec::new();
+    let mut pos = head.end;
+    for _ in 0..head.arg {
+        let key_end = check_item(bytes, pos, false).ok()?;
+        let value_end = check_item(bytes, key_end, false).ok()?;
+        entries.push((&bytes[pos..key_end], key_end, value_end));
+        pos = value_end;
+    }
+    Some(entries)
+}
+
+fn text_key(key: &[u8]) -> Option<String> {
+    serde_cbor_core::from_slice(key).ok()
+}
+
+/// Check that a well-formed memo block has no null headers
+fn check_memo_headers(bytes: &[u8], offset: usize) -> Result<(), Violation> {
+    let Some(entries) = map_entries(bytes, offset) else {
+        return Ok(());
+    };
+    let is_memo = entries.iter().any(|(key, start, end)| {
+        text_key(key).as_deref() == Some("type")
+            && text_key(&bytes[*start..*end]).as_deref() == Some("szdt/memo")
+    });
+    if !is_memo {
+        return Ok(());
+    }
+    for (key, start, _) in &entries {
+        if !matches!(text_key(key).as_deref(), Some("protected" | "unprotected")) {
+            continue;
+        }
+        for (header, value_start, _) in map_entries(bytes, *start).unwrap_or_default() {
+            if bytes[value_start] == NULL {
+                return Err(Violation::new(
+                    value_start,
+                    Rule::NullHeader {
+                        key: text_key(header).unwrap_or_default(),
+                    },
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the block starting at `offset` in a sequence, returning the offset of
+/// the end of the block. Memo blocks are also checked for null headers.
+pub fn check_block_at(bytes: &[u8], offset: usize) -> Result<usize, Violation> {
+    let end = check_item(bytes, offset, true)?;
+    check_memo_headers(bytes, offset)?;
+    Ok(end)
+}
+
+/// Check that bytes are exactly one item encoded by the CBOR/c rules.
+/// Unlike `check_block`, memo headers are not checked for nulls, and floats
+/// are not checked for shortest form, since `serde_cbor_core` writes every
+/// float as a double.
+pub fn check_encoding(bytes: &[u8]) -> Result<(), Violation> {
+    let end = check_item(bytes, 0, false)?;
+    if end != bytes.len() {
+        return Err(Violation::new(end, Rule::TrailingBytes));
+    }
+    Ok(())
+}
+
+/// Check that bytes are exactly one conformant block
+pub fn check_block(bytes: &[u8]) -> Result<(), Violation> {
+    let end = check_block_at(bytes, 0)?;
+    if end != bytes.len() {
+        return Err(Violation::new(end, Rule::TrailingBytes));
+    }
+    Ok(())
+}
+
+/// Check every block in a CBOR sequence, returning the number of blocks
+pub fn check_seq(bytes: &[u8]) -> Result<usize, Violation> {
+    let mut offset = 0;
+    let mut count = 0;
+    while offset < bytes.len() {
+        offset = check_block_at(bytes, offset)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::memo::Memo;
+
+    fn violation(bytes: &[u8]) -> Violation {
+        check_block(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn test_canonical_encodings_pass() {
+        let memo = Memo::for_body(Bytes(b"Hello".to_vec())).unwrap();
+        check_block(&serde_cbor_core::to_vec(&memo).unwrap()).unwrap();
+        check_block(&[0x17]).unwrap();
+        check_block(&[0x18, 0x18]).unwrap();
+        check_block(&[0x39, 0x01, 0x00]).unwrap();
+        // 1.5 as a half-precision float
+        check_block(&[0xf9, 0x3e, 0x00]).unwrap();
+        // 100000.0 doesn't fit in a half-precision float
+        check_block(&[0xfa, 0x47, 0xc3, 0x50, 0x00]).unwrap();
+        // 1.1 doesn't fit in a single-precision float
+        check_block(&serde_cbor_core::to_vec(&1.1f64).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_non_shortest_integer() {
+        assert_eq!(
+            violation(&[0x18, 0x17]),
+            Violation::new(0, Rule::NonShortestInteger)
+        );
+        // Array length
+        assert_eq!(
+            violation(&[0x82, 0x01, 0x98, 0x01, 0x01]),
+            Violation::new(2, Rule::NonShortestInteger)
+        );
+    }
+
+    #[test]
+    fn test_non_shortest_float() {
+        // 1.5 as a double-precision float
+        let bytes = serde_cbor_core::to_vec(&1.5f64).unwrap();
+        assert_eq!(violation(&bytes), Violation::new(0, Rule::NonShortestFloat));
+        // NaN must be 0xf97e00
+        assert_eq!(
+            violation(&[0xfa, 0x7f, 0xc0, 0x00, 0x00]),
+            Violation::new(0, Rule::NonShortestFloat)
+        );
+        check_block(&[0xf9, 0x7e, 0x00]).unwrap();
+    }
+
+    #[test]
+    fn test_indefinite_length() {
+        // [_ 1]
+        assert_eq!(
+            violation(&[0x9f, 0x01, 0xff]),
+            Violation::new(0, Rule::IndefiniteLength)
+        );
+    }
+
+    #[test]
+    fn test_map_keys() {
+        // {"b": 1, "a": 2}
+        assert_eq!(
+            violation(&[0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02]),
+            Violation::new(4, Rule::UnsortedKeys)
+        );
+        // {"a": 1, "a": 2}
+        assert_eq!(
+            violation(&[0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02]),
+            Violation::new(4, Rule::DuplicateKey)
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        // A million nested arrays, around a single integer
+        let mut bytes = vec![0x81; 1_000_000];
+        bytes.push(0x01);
+        assert_eq!(check_seq(&bytes), Ok(1));
+        bytes.pop();
+        assert_eq!(violation(&bytes).rule, Rule::Truncated);
+    }
+
+    #[test]
+    fn test_truncated_and_trailing() {
+        assert_eq!(violation(&[0x62, 0x61]), Violation::new(0, Rule::Truncated));
+        assert_eq!(
+            violation(&[0x01, 0x01]),
+            Violation::new(1, Rule::TrailingBytes)
+        );
+        assert_eq!(check_seq(&[0x01, 0x01]).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_null_header() {
+        let memo = Memo::for_body(Bytes(b"Hello".to_vec())).unwrap();
+        let mut value = crate::value::to_value(&memo).unwrap();
+        let cbor4ii::core::Value::Map(entries) = &mut value else {
+            panic!("Memo should serialize to a map");
+        };
+        for (key, value) in entries.iter_mut() {
+            if let (cbor4ii::core::Value::Text(key), cbor4ii::core::Value::Map(headers)) =
+                (key, value)
+                && key == "protected"
+            {
+                headers.push((
+                    cbor4ii::core::Value::Text("iss-nickname".to_string()),
+                    cbor4ii::core::Value::Null,
+                ));
+            }
+        }
+        let bytes = serde_cbor_core::to_vec(&value).unwrap();
+        let violation = violation(&bytes);
+        assert_eq!(
+            violation.rule,
+            Rule::NullHeader {
+                key: "iss-nickname".to_string()
+            }
+        );
+        assert_eq!(bytes[violation.offset], NULL);
+    }
+}
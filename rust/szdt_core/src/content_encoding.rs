@@ -0,0 +1,178 @@
+//! Decoding of encoded (compressed) memo bodies.
+//!
+//! Encoded bodies are marked with a protected `content-encoding` header,
+//! which has the same meaning as the HTTP header. The memo `src` is always the
+//! hash of the body as stored (the encoded bytes), so bodies are verified
+//! before they are decoded, and encoded bodies can be stored, mirrored,
+//! deduplicated and redacted without decoding them.
+//!
+//! Decoders are pure Rust, so decoding works everywhere core does, including
+//! WASM. Decoded output is capped by `Limits::max_body_size`, since a small
+//! encoded body can decode to a very large one.
+use crate::bytes::Bytes;
+use crate::chunked::is_chunked;
+use crate::error::Error;
+use crate::limits::Limits;
+use crate::memo::Memo;
+use std::io::Read;
+
+/// Header marking the encoding of a memo body
+pub const CONTENT_ENCODING: &str = "content-encoding";
+
+/// Content encoding for unencoded bodies
+const IDENTITY: &str = "identity";
+
+/// Supported content encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Zstd,
+    Gzip,
+}
+
+impl ContentEncoding {
+    /// Name of the encoding, as used in the `content-encoding` header
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+
+    /// Get the encoding for a `content-encoding` value.
+    /// Returns `None` for `identity` (no encoding).
+    pub fn from_name(name: &str) -> Result<Option<Self>, Error> {
+        match name {
+            "zstd" => Ok(Some(ContentEncoding::Zstd)),
+            "gzip" => Ok(Some(ContentEncoding::Gzip)),
+            IDENTITY => Ok(None),
+            _ => Err(Error::ContentEncoding(format!(
+                "Unsupported content-encoding: {name}"
+            ))),
+        }
+    }
+
+    /// Wrap a reader of encoded bytes in a reader of decoded bytes.
+    /// The decoder reads no further than the end of the encoded data.
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>, Error> {
+        match self {
+            ContentEncoding::Zstd => Ok(Box::new(
+                ruzstd::decoding::StreamingDecoder::new(reader)
+                    .map_err(|err| Error::ContentEncoding(err.to_string()))?,
+            )),
+            ContentEncoding::Gzip => Ok(Box::new(flate2::read::GzDecoder::new(reader))),
+        }
+    }
+
+    /// Decode bytes, failing if the output would be larger than `max_size`
+    /// bytes.
+    pub fn decode(&self, bytes: &[u8], max_size: u64) -> Result<Vec<u8>, Error> {
+        let mut decoded = Vec::new();
+        self.decoder(bytes)?
+            .take(max_size.saturating_add(1))
+            .read_to_end(&mut decoded)
+            .map_err(|err| Error::ContentEncoding(err.to_string()))?;
+        if decoded.len() as u64 > max_size {
+            return Err(Error::BodyTooLarge(decoded.len() as u64, max_size));
+        }
+        Ok(decoded)
+    }
+}
+
+/// Get the encoding of a memo body, from its `content-encoding` header
+pub fn content_encoding(memo: &Memo) -> Result<Option<ContentEncoding>, Error> {
+    match memo.protected.get_header::<String>(CONTENT_ENCODING)? {
+        Some(name) => ContentEncoding::from_name(&name),
+        None => Ok(None),
+    }
+}
+
+/// Decode a memo body, if it has a `content-encoding`.
+/// Check the body against the memo checksum before decoding.
+///
+/// Chunked bodies are a list of chunk hashes, not file content, so they are
+/// rejected. Reassemble them with `chunked::read_file` instead.
+pub fn decode_body(memo: &Memo, body: Bytes, limits: &Limits) -> Result<Bytes, Error> {
+    if is_chunked(memo)? {
+        return Err(Error::Chunked(
+            "Chunked bodies must be reassembled from their chunks".to_string(),
+        ));
+    }
+    match content_encoding(memo)? {
+        Some(encoding) => Ok(Bytes(encoding.decode(&body.0, limits.max_body_size)?)),
+        None => Ok(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::chunked::CHUNKED;
+    use crate::test_vectors::HELLO_ZSTD;
+
+    // "Hello World", compressed with gzip (without a timestamp)
+    const HELLO_GZIP: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xf3, 0x48, 0xcd, 0xc9, 0xc9,
+        0x57, 0x08, 0xcf, 0x2f, 0xca, 0x49, 0x01, 0x00, 0x56, 0xb1, 0x17, 0x4a, 0x0b, 0x00, 0x00,
+        0x00,
+    ];
+
+    #[test]
+    fn test_decode() {
+        for (encoding, bytes) in [
+            (ContentEncoding::Zstd, HELLO_ZSTD),
+            (ContentEncoding::Gzip, HELLO_GZIP),
+        ] {
+            assert_eq!(encoding.decode(bytes, u64::MAX).unwrap(), b"Hello World");
+            assert!(matches!(
+                encoding.decode(bytes, 10),
+                Err(Error::BodyTooLarge(11, 10))
+            ));
+            assert!(encoding.decode(b"not encoded", u64::MAX).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let compressed = Bytes(HELLO_ZSTD.to_vec());
+        let mut memo = Memo::for_body(&compressed).unwrap();
+        memo.protected
+            .set_header(CONTENT_ENCODING, &"zstd")
+            .unwrap();
+        let limits = Limits::default();
+        assert_eq!(
+            decode_body(&memo, compressed.clone(), &limits).unwrap().0,
+            b"Hello World"
+        );
+        let limits = Limits {
+            max_body_size: 5,
+            ..Limits::default()
+        };
+        assert!(matches!(
+            decode_body(&memo, compressed.clone(), &limits),
+            Err(Error::BodyTooLarge(_, 5))
+        ));
+
+        memo.protected.set_header(CONTENT_ENCODING, &"br").unwrap();
+        assert!(matches!(
+            decode_body(&memo, compressed, &Limits::default()),
+            Err(Error::ContentEncoding(_))
+        ));
+
+        // Bodies without a content-encoding are returned as-is
+        let body = Bytes(b"Hello World".to_vec());
+        let memo = Memo::for_body(&body).unwrap();
+        assert_eq!(
+            decode_body(&memo, body.clone(), &Limits::default()).unwrap(),
+            body
+        );
+
+        // Chunked bodies are chunk hashes, not content
+        let mut memo = Memo::for_body(&body).unwrap();
+        memo.protected.set_header(CHUNKED, &true).unwrap();
+        assert!(matches!(
+            decode_body(&memo, body, &Limits::default()),
+            Err(Error::Chunked(_))
+        ));
+    }
+}
@@ -0,0 +1,506 @@
+//! Content-addressed index of the blocks in a sequence.
+//!
+//! Sequences are not required to have any particular shape. Blocks may appear
+//! in any order, may be duplicated, and the bodies that memos point to may be
+//! missing (an "incomplete DAG"). `DagIndex` reads every block of a sequence,
+//! keyed by content address (the Blake3 hash of its CBOR/c encoding, see
+//! `ToLink`), so that memos can be resolved to their bodies wherever they are.
+//!
+//! `DagWriter` does the reverse, writing a DAG of blocks to a sequence in
+//! depth-first, first-seen order, by following the content addresses embedded
+//! in each block.
+use crate::cbor_seq::{CborSeqReader, CborSeqWriter};
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::link::ToLink;
+use crate::memo::Memo;
+use crate::value::{MEMO_TYPE, block_type, from_value, to_value};
+use cbor4ii::core::Value;
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+
+/// A source of blocks, by content address
+pub trait BlockSource {
+    /// Get the block with the given content address, if available
+    fn get_block(&self, hash: &Hash) -> Result<Option<Value>, Error>;
+}
+
+impl BlockSource for HashMap<Hash, Value> {
+    fn get_block(&self, hash: &Hash) -> Result<Option<Value>, Error> {
+        Ok(self.get(hash).cloned())
+    }
+}
+
+/// A memo read from a sequence, along with its resolved body
+#[derive(Debug, Clone, PartialEq)]
+pub struct DagEntry<'a> {
+    /// Hash of the memo block
+    pub hash: Hash,
+    pub memo: Memo,
+    /// The body block the memo points to via `src`, if present in the sequence
+    pub body: Option<&'a Value>,
+}
+
+/// An index of sequence blocks by content address.
+/// The index holds every block in memory. To stream large archives, read
+/// blocks one at a time with `CborSeqReader` instead.
+#[derive(Debug, Clone, Default)]
+pub struct DagIndex {
+    blocks: HashMap<Hash, Value>,
+    /// Number of times each block appears in the sequence
+    counts: HashMap<Hash, usize>,
+    /// Block hashes, in the order they were first seen
+    order: Vec<Hash>,
+    /// Memo block hashes, in the order they were first seen
+    memos: Vec<Hash>,
+}
+
+impl DagIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read and index every block of a sequence.
+    /// Blocks are keyed by the hash of the bytes read.
+    pub fn read<R: BufRead>(reader: &mut CborSeqReader<R>) -> Result<Self, Error> {
+        let mut index = Self::new();
+        loop {
+            match reader.read_block_bytes() {
+                Ok(bytes) => {
+                    let block: Value = serde_cbor_core::from_slice(&bytes)?;
+                    index.insert_with_hash(Hash::new(&bytes), block);
+                }
+                Err(Error::Eof) => return Ok(index),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Add a block to the index, returning its content address.
+    /// Duplicate blocks are stored once.
+    pub fn insert(&mut self, block: Value) -> Result<Hash, Error> {
+        let hash = block.to_link()?;
+        self.insert_with_hash(hash, block);
+        Ok(hash)
+    }
+
+    /// Add a block whose content address is already known
+    fn insert_with_hash(&mut self, hash: Hash, block: Value) {
+        let count = self.counts.entry(hash).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            if is_memo(&block) {
+                self.memos.push(hash);
+            }
+            self.order.push(hash);
+            self.blocks.insert(hash, block);
+        }
+    }
+
+    /// Get a block by content address
+    pub fn get(&self, hash: &Hash) -> Option<&Value> {
+        self.blocks.get(hash)
+    }
+
+    /// Get a block by content address, deserializing it to the given type
+    pub fn get_as<T: DeserializeOwned>(&self, hash: &Hash) -> Result<Option<T>, Error> {
+        self.blocks.get(hash).map(from_value).transpose()
+    }
+
+    /// Is a block with the given content address in the index?
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    /// Number of times the block appears in the sequence
+    pub fn count(&self, hash: &Hash) -> usize {
+        self.counts.get(hash).copied().unwrap_or(0)
+    }
+
+    /// Number of unique blocks
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Is the index empty?
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Iterate over unique block hashes, in the order they were first seen
+    pub fn hashes(&self) -> impl Iterator<Item = &Hash> {
+        self.order.iter()
+    }
+
+    /// Get the memos in the sequence, in the order they were first seen
+    pub fn memos(&self) -> Result<Vec<(Hash, Memo)>, Error> {
+        self.memos
+            .iter()
+            .map(|hash| Ok((*hash, from_value(&self.blocks[hash])?)))
+            .collect()
+    }
+
+    /// Resolve the body a memo points to via `src`.
+    /// Since blocks are keyed by content address, a resolved body always
+    /// matches the memo checksum.
+    pub fn resolve(&self, memo: &Memo) -> Option<&Value> {
+        self.get(&memo.protected.src)
+    }
+
+    /// Get every memo in the sequence, along with its body (if present)
+    pub fn entries(&self) -> Result<Vec<DagEntry<'_>>, Error> {
+        self.memos()?
+            .into_iter()
+            .map(|(hash, memo)| {
+                let body = self.resolve(&memo);
+                Ok(DagEntry { hash, memo, body })
+            })
+            .collect()
+    }
+
+    /// Get the `src` hashes of memo bodies that are missing from the sequence
+    pub fn missing(&self) -> Result<Vec<Hash>, Error> {
+        let mut missing = Vec::new();
+        for (_, memo) in self.memos()? {
+            let src = memo.protected.src;
+            if !self.contains(&src) && !missing.contains(&src) {
+                missing.push(src);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Is every memo body present?
+    pub fn is_complete(&self) -> Result<bool, Error> {
+        Ok(self.missing()?.is_empty())
+    }
+}
+
+impl BlockSource for DagIndex {
+    fn get_block(&self, hash: &Hash) -> Result<Option<Value>, Error> {
+        Ok(self.get(hash).cloned())
+    }
+}
+
+/// Writes DAGs of blocks to a sequence, in depth-first, first-seen order.
+///
+/// Starting from a root block, the writer writes the block, then walks the
+/// content addresses embedded in it (32-byte byte strings, like `Hash`), in
+/// the order they appear in the block's CBOR/c encoding, writing each linked
+/// block it can find in the block source, and so on. Each block is written
+/// at most once per DAG. Blocks from the block source are checked against the
+/// link used to look them up, and a mismatch is an `IntegrityError`.
+///
+/// Links to blocks that aren't in the block source are skipped, since
+/// sequences may contain incomplete DAGs. Byte strings that happen to be 32
+/// bytes long, but aren't in the block source, are treated the same way.
+///
+/// Each call to `write_dag` writes one DAG, so DAGs are written contiguously.
+pub struct DagWriter<W> {
+    writer: CborSeqWriter<W>,
+    dedup: bool,
+    written: HashSet<Hash>,
+}
+
+impl<W: Write> DagWriter<W> {
+    /// Create a writer. Blocks shared within a DAG are written once, but each
+    /// DAG is written in full, even if its blocks were already written as
+    /// part of an earlier DAG.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: CborSeqWriter::new(writer),
+            dedup: false,
+            written: HashSet::new(),
+        }
+    }
+
+    /// Write each block only once, the first time it is seen, across every DAG
+    /// written with this writer.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Write the DAG rooted at `root`, resolving links from `source`.
+    /// Returns the number of blocks written.
+    pub fn write_dag<T, S>(&mut self, root: &T, source: &S) -> Result<usize, Error>
+    where
+        T: Serialize,
+        S: BlockSource + ?Sized,
+    {
+        let root = to_value(root)?;
+        let hash = root.to_link()?;
+        // Walk the DAG with an explicit stack, so long chains of links can't
+        // overflow the call stack. Blocks already seen in this DAG are
+        // skipped, so shared blocks are written once rather than once per path.
+        let mut seen = HashSet::new();
+        let mut stack = vec![(hash, root)];
+        let mut count = 0;
+        while let Some((hash, block)) = stack.pop() {
+            if !seen.insert(hash) {
+                continue;
+            }
+            if self.dedup && !self.written.insert(hash) {
+                continue;
+            }
+            self.writer.write_block(&block)?;
+            count += 1;
+            // Push children in reverse, so they are popped in encoding order
+            for link in links(&block).into_iter().rev() {
+                if seen.contains(&link) {
+                    continue;
+                }
+                if let Some(child) = source.get_block(&link)? {
+                    let child_hash = child.to_link()?;
+                    if child_hash != link {
+                        return Err(Error::IntegrityError(format!(
+                            "Block source returned block {child_hash} for link {link}"
+                        )));
+                    }
+                    stack.push((link, child));
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    /// Unwrap inner writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+/// Get the content addresses embedded in a block, in encoding order.
+/// Blocks are decoded from CBOR/c, so map entries are in canonical order.
+pub fn links(block: &Value) -> Vec<Hash> {
+    let mut links = Vec::new();
+    collect_links(block, &mut links);
+    links
+}
+
+fn collect_links(value: &Value, links: &mut Vec<Hash>) {
+    match value {
+        Value::Bytes(bytes) => {
+            if let Ok(hash) = <[u8; 32]>::try_from(bytes.as_slice()) {
+                links.push(Hash::from_bytes(hash));
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_links(item, links);
+            }
+        }
+        Value::Map(entries) => {
+            for (key, value) in entries {
+                collect_links(key, links);
+                collect_links(value, links);
+            }
+        }
+        Value::Tag(_, value) => collect_links(value, links),
+        _ => {}
+    }
+}
+
+/// Is the block a memo?
+fn is_memo(block: &Value) -> bool {
+    block_type(block) == Some(MEMO_TYPE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::cbor_seq::CborSeqWriter;
+    use crate::ed25519_key_material::Ed25519KeyMaterial;
+    use std::io::Cursor;
+
+    fn create_memo(body: &Bytes) -> Memo {
+        let key = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let mut memo = Memo::for_body(body).unwrap();
+        memo.sign(&key).unwrap();
+        memo
+    }
+
+    #[test]
+    fn test_resolves_bodies_anywhere() {
+        let a = Bytes(b"a".to_vec());
+        let b = Bytes(b"b".to_vec());
+        let missing = Bytes(b"missing".to_vec());
+        let a_memo = create_memo(&a);
+        let b_memo = create_memo(&b);
+        let missing_memo = create_memo(&missing);
+
+        // Bodies out of order, duplicated, and missing, with an unrelated block
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&b).unwrap();
+        writer.write_block(&a_memo).unwrap();
+        writer.write_block(&"unrelated").unwrap();
+        writer.write_block(&b_memo).unwrap();
+        writer.write_block(&missing_memo).unwrap();
+        writer.write_block(&a).unwrap();
+        writer.write_block(&b).unwrap();
+        let data = writer.into_inner();
+
+        let index = DagIndex::read(&mut CborSeqReader::new(Cursor::new(data))).unwrap();
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.count(&b.to_link().unwrap()), 2);
+
+        let entries = index.entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].memo, a_memo);
+        assert_eq!(entries[0].hash, a_memo.to_link().unwrap());
+        assert_eq!(entries[0].body, Some(&Value::Bytes(b"a".to_vec())));
+        assert_eq!(entries[1].body, Some(&Value::Bytes(b"b".to_vec())));
+        assert_eq!(entries[2].body, None);
+
+        assert_eq!(index.missing().unwrap(), vec![missing.to_link().unwrap()]);
+        assert!(!index.is_complete().unwrap());
+
+        let body: Option<Bytes> = index.get_as(&a.to_link().unwrap()).unwrap();
+        assert_eq!(body, Some(a));
+    }
+
+    #[test]
+    fn test_memo_block_hash_matches_link() {
+        let body = Bytes(b"a".to_vec());
+        let memo = create_memo(&body);
+        let mut index = DagIndex::new();
+        let value = crate::value::to_value(&memo).unwrap();
+        let hash = index.insert(value).unwrap();
+        assert_eq!(hash, memo.to_link().unwrap());
+        let (_, decoded) = &index.memos().unwrap()[0];
+        decoded.verify().unwrap();
+    }
+
+    fn text_map(entries: &[(&str, Value)]) -> Value {
+        Value::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (Value::Text(key.to_string()), value.clone()))
+                .collect(),
+        )
+    }
+
+    fn link(value: &Value) -> Value {
+        Value::Bytes(value.to_link().unwrap().as_bytes().to_vec())
+    }
+
+    fn read_all(data: Vec<u8>) -> Vec<Value> {
+        let mut reader = CborSeqReader::new(Cursor::new(data));
+        let mut blocks = Vec::new();
+        loop {
+            match reader.read_block::<Value>() {
+                Ok(block) => blocks.push(block),
+                Err(Error::Eof) => return blocks,
+                Err(err) => panic!("{err}"),
+            }
+        }
+    }
+
+    fn read_links(data: Vec<u8>) -> Vec<Hash> {
+        read_all(data)
+            .iter()
+            .map(|block| block.to_link().unwrap())
+            .collect()
+    }
+
+    fn links_of(blocks: &[&Value]) -> Vec<Hash> {
+        blocks
+            .iter()
+            .map(|block| block.to_link().unwrap())
+            .collect()
+    }
+
+    /// The branching DAG example from the sequences spec
+    fn create_branching_dag() -> (Value, HashMap<Hash, Value>, [Value; 4]) {
+        let d = text_map(&[("value", Value::Text("d".to_string()))]);
+        let b = text_map(&[("ref", link(&d))]);
+        let c = text_map(&[
+            ("branch", Value::Text("right".to_string())),
+            ("ref", link(&d)),
+        ]);
+        let a = text_map(&[("left", link(&b)), ("right", link(&c))]);
+        let source: HashMap<Hash, Value> = [&b, &c, &d]
+            .into_iter()
+            .map(|block| (block.to_link().unwrap(), block.clone()))
+            .collect();
+        (a.clone(), source, [a, b, c, d])
+    }
+
+    #[test]
+    fn test_write_dag_depth_first() {
+        let (root, source, [a, b, c, d]) = create_branching_dag();
+        let mut writer = DagWriter::new(Vec::new());
+        let count = writer.write_dag(&root, &source).unwrap();
+        assert_eq!(count, 4);
+        // Without dedup, writing again writes the whole DAG again
+        assert_eq!(writer.write_dag(&root, &source).unwrap(), 4);
+        assert_eq!(
+            read_links(writer.into_inner()),
+            links_of(&[&a, &b, &d, &c, &a, &b, &d, &c])
+        );
+    }
+
+    #[test]
+    fn test_write_dag_long_chain() {
+        // A chain deep enough to overflow the stack if walked recursively
+        let mut source = HashMap::new();
+        let mut head = Value::Text("genesis".to_string());
+        for i in 0..100_000 {
+            let block = text_map(&[("i", Value::Integer(i)), ("prev", link(&head))]);
+            source.insert(head.to_link().unwrap(), head);
+            head = block;
+        }
+        let mut writer = DagWriter::new(std::io::sink());
+        assert_eq!(writer.write_dag(&head, &source).unwrap(), 100_001);
+    }
+
+    #[test]
+    fn test_write_dag_rejects_mismatched_blocks() {
+        let (root, mut source, [_, b, c, _]) = create_branching_dag();
+        // Store c under b's address
+        source.insert(b.to_link().unwrap(), c);
+        let mut writer = DagWriter::new(Vec::new());
+        let result = writer.write_dag(&root, &source);
+        assert!(matches!(result, Err(Error::IntegrityError(_))));
+    }
+
+    #[test]
+    fn test_write_dag_dedup() {
+        let (root, source, [a, b, c, d]) = create_branching_dag();
+        let mut writer = DagWriter::new(Vec::new()).with_dedup(true);
+        let count = writer.write_dag(&root, &source).unwrap();
+        assert_eq!(count, 4);
+        // Writing again writes nothing, since every block was already written
+        assert_eq!(writer.write_dag(&root, &source).unwrap(), 0);
+        assert_eq!(read_links(writer.into_inner()), links_of(&[&a, &b, &d, &c]));
+    }
+
+    #[test]
+    fn test_write_multiple_dags_with_missing_blocks() {
+        let body = Bytes(b"a".to_vec());
+        let memo = create_memo(&body);
+        let missing_memo = create_memo(&Bytes(b"missing".to_vec()));
+        let mut source = HashMap::new();
+        source.insert(body.to_link().unwrap(), to_value(&body).unwrap());
+
+        let mut writer = DagWriter::new(Vec::new());
+        writer.write_dag(&memo, &source).unwrap();
+        writer.write_dag(&missing_memo, &source).unwrap();
+        let data = writer.into_inner();
+
+        // Memo and body are contiguous, and the missing body is skipped
+        let index = DagIndex::read(&mut CborSeqReader::new(Cursor::new(data.clone()))).unwrap();
+        assert_eq!(index.len(), 3);
+        let blocks = read_all(data);
+        assert_eq!(blocks[0], to_value(&memo).unwrap());
+        assert_eq!(blocks[1], Value::Bytes(b"a".to_vec()));
+        assert_eq!(blocks[2], to_value(&missing_memo).unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::did;
 use crate::ed25519;
+use crate::hash;
 use crate::nickname;
 use std::{collections::TryReserveError, convert::Infallible};
 use thiserror::Error;
@@ -43,6 +44,8 @@ pub enum Error {
     Ed25519(#[from] ed25519::Error),
     #[error("DID error: {0}")]
     Did(#[from] did::Error),
+    #[error("Hash parse error: {0}")]
+    HashParse(#[from] hash::ParseError),
     #[error("BIP39 error: {0}")]
     Bip39(#[from] bip39::Error),
     #[error("Private key missing: {0}")]
@@ -57,6 +60,54 @@ pub enum Error {
     MemoNbfError(TimestampComparison),
     #[error("Memo has expired (exp time didn't validate): {0}")]
     MemoExpError(TimestampComparison),
+    #[error("Memo was issued in the future (iat time didn't validate): {0}")]
+    MemoIatError(TimestampComparison),
+    #[error("Memo is too old (max age didn't validate): {0}")]
+    MemoMaxAgeError(TimestampComparison),
+    #[error("Memo is missing required headers: {0}")]
+    MemoHeaderMissing(String),
+    #[error("Invalid header key: {0}")]
+    HeaderKeyInvalid(String),
+    #[error("Header is reserved and can't be set as an extension header: {0}")]
+    HeaderReserved(String),
+    #[error("Header value must not be null: {0}")]
+    HeaderValueNull(String),
+    #[error("Header value must not contain floats: {0}")]
+    HeaderValueFloat(String),
+    #[error("Header conflicts with HTTP header semantics: {0}")]
+    HeaderHttpConflict(String),
+    #[error("Memo co-signature is invalid: {0}")]
+    CosigInvalid(String),
+    #[error("Witness policy not satisfied: {0}")]
+    WitnessPolicyError(String),
+    #[error("Invalid witness policy: {0}")]
+    InvalidWitnessPolicy(String),
+    #[error("Not conformant CBOR/c: {0}")]
+    Conformance(crate::conformance::Violation),
+    #[error("Not canonical CBOR/c: {0}")]
+    NonCanonical(String),
+    #[error("Invalid {0} block: {1}")]
+    InvalidBlock(String, String),
+    #[error("Block is too large: {0} bytes exceeds the limit of {1} bytes")]
+    BlockTooLarge(u64, u64),
+    #[error("Body is too large: {0} bytes exceeds the limit of {1} bytes")]
+    BodyTooLarge(u64, u64),
+    #[error("Block is nested too deeply: exceeds the limit of {0} levels")]
+    NestingTooDeep(usize),
+    #[error("Too many entries: {0} entries exceeds the limit of {1}")]
+    TooManyEntries(u64, u64),
+    #[error("Invalid szdt: URL: {0}")]
+    InvalidUrl(String),
+    #[error("Content encoding error: {0}")]
+    ContentEncoding(String),
+    #[error("Chunked body error: {0}")]
+    Chunked(String),
+    #[error("Block store error: {0}")]
+    BlockStoreError(String),
+    #[error("Memo issuer does not match issuer of previous version: {0}")]
+    PrevIssuerMismatch(String),
+    #[error("Memo was issued before previous version: {0}")]
+    PrevIatError(String),
     #[error("Nickname error: {0}")]
     NicknameError(#[from] nickname::NicknameError),
     #[error("EOF")]
@@ -1,7 +1,30 @@
-use data_encoding::BASE32_NOPAD;
+use crate::base58btc;
+use data_encoding::{BASE32_NOPAD, HEXLOWER_PERMISSIVE};
 use serde::de::{self, Unexpected, Visitor};
 use serde::{Deserialize, Serialize};
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The multicodec code for Blake3 hashes is 0x1e.
+/// https://github.com/multiformats/multicodec/blob/master/table.csv
+const MULTIHASH_BLAKE3: u8 = 0x1e;
+
+/// Length of a Blake3 hash in bytes
+const HASH_LEN: usize = 32;
+
+/// CID version 1
+const CID_V1: u8 = 0x01;
+
+/// The multicodec code for CBOR is 0x51.
+/// SZDT hashes are content addresses of CBOR blocks.
+const MULTICODEC_CBOR: u8 = 0x51;
+
+/// Length of a hash encoded as lowercase base32
+const BASE32_LEN: usize = 52;
+
+/// Length of a hash encoded as hex
+const HEX_LEN: usize = 64;
 
 /// Blake3 hash
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -14,18 +37,12 @@ impl Hash {
         Hash(val)
     }
 
-    /// Streaming hash the bytes returned by a reader
-    pub fn from_reader<R: Read>(reader: R) -> Self {
-        let mut hasher = blake3::Hasher::new();
-        let mut buffer = [0; 1024];
-        let mut reader = std::io::BufReader::new(reader);
-        while let Ok(n) = reader.read(&mut buffer) {
-            if n == 0 {
-                break;
-            }
-            hasher.update(&buffer[..n]);
-        }
-        Hash(hasher.finalize())
+    /// Streaming hash the bytes returned by a reader.
+    /// Returns an error if the reader fails before reaching the end.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, io::Error> {
+        let mut reader = HashingReader::new(reader);
+        io::copy(&mut reader, &mut io::sink())?;
+        reader.hash()
     }
 
     /// Construct a hash from a byte array representing the hash.
@@ -43,11 +60,338 @@ impl Hash {
         let byte_array: [u8; 32] = bytes.try_into()?;
         Ok(Self::from_bytes(byte_array))
     }
+
+    /// Parse a hash from a string. Accepts:
+    ///
+    /// - Base32, as printed by `Display` (e.g. `rxizkt6b...`)
+    /// - Hex
+    /// - A multibase-encoded Blake3 multihash (e.g. `bdyqb...`)
+    /// - A CIDv1 with a Blake3 multihash (e.g. `bafir4i...`)
+    ///
+    /// Multibase strings may be base32 (`b`/`B`), hex (`f`/`F`) or
+    /// base58btc (`z`).
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let bytes = match input.len() {
+            BASE32_LEN => decode_base32(input)?,
+            HEX_LEN => HEXLOWER_PERMISSIVE
+                .decode(input.as_bytes())
+                .map_err(|err| ParseError::Encoding(err.to_string()))?,
+            _ => return Self::from_multibase(input),
+        };
+        Self::from_slice(&bytes).map_err(|_| ParseError::Length(bytes.len()))
+    }
+
+    /// Parse a hash from a multibase-encoded multihash or CID
+    fn from_multibase(input: &str) -> Result<Self, ParseError> {
+        let mut chars = input.chars();
+        let base = chars.next().ok_or(ParseError::Length(0))?;
+        let encoded = chars.as_str();
+        let bytes = match base {
+            'b' | 'B' => decode_base32(encoded)?,
+            'f' | 'F' => HEXLOWER_PERMISSIVE
+                .decode(encoded.as_bytes())
+                .map_err(|err| ParseError::Encoding(err.to_string()))?,
+            'z' => {
+                base58btc::decode(encoded).map_err(|err| ParseError::Encoding(err.to_string()))?
+            }
+            _ => {
+                return Err(ParseError::Encoding(format!(
+                    "Unsupported multibase prefix '{base}'"
+                )));
+            }
+        };
+        // CIDs begin with their version, followed by a content codec, then
+        // the multihash.
+        let multihash = match bytes.as_slice() {
+            [CID_V1, codec, multihash @ ..] if *codec < 0x80 => multihash,
+            [CID_V1, ..] => {
+                return Err(ParseError::UnsupportedCodec(
+                    "Only single-byte CID content codecs are supported".to_string(),
+                ));
+            }
+            multihash => multihash,
+        };
+        Self::from_multihash(multihash)
+    }
+
+    /// Construct a hash from multihash bytes. Only Blake3 multihashes of 32
+    /// bytes are supported.
+    pub fn from_multihash(bytes: &[u8]) -> Result<Self, ParseError> {
+        let [code, len, digest @ ..] = bytes else {
+            return Err(ParseError::Length(bytes.len()));
+        };
+        if *code != MULTIHASH_BLAKE3 {
+            return Err(ParseError::UnsupportedCodec(format!(
+                "Only Blake3 multihashes (0x1e) are supported. Got: 0x{code:02x}"
+            )));
+        }
+        if *len as usize != HASH_LEN || digest.len() != HASH_LEN {
+            return Err(ParseError::Length(digest.len()));
+        }
+        Self::from_slice(digest).map_err(|_| ParseError::Length(digest.len()))
+    }
+
+    /// Bytes of the hash as a Blake3 multihash
+    pub fn to_multihash(&self) -> Vec<u8> {
+        [&[MULTIHASH_BLAKE3, HASH_LEN as u8][..], self.as_bytes()].concat()
+    }
+
+    /// The hash as lowercase hex
+    pub fn to_hex(&self) -> String {
+        data_encoding::HEXLOWER.encode(self.as_bytes())
+    }
+
+    /// The hash as a CIDv1 string, for use with IPFS tooling.
+    /// Uses the CBOR codec, since SZDT hashes are content addresses of CBOR
+    /// blocks, and multibase base32, the default for CIDv1.
+    pub fn to_cid(&self) -> String {
+        let cid = [&[CID_V1, MULTICODEC_CBOR][..], &self.to_multihash()].concat();
+        format!("b{}", BASE32_NOPAD.encode(&cid).to_lowercase())
+    }
+}
+
+/// Decode case-insensitive unpadded base32
+fn decode_base32(input: &str) -> Result<Vec<u8>, ParseError> {
+    BASE32_NOPAD
+        .decode(input.to_uppercase().as_bytes())
+        .map_err(|err| ParseError::Encoding(err.to_string()))
+}
+
+impl FromStr for Hash {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Invalid hash encoding: {0}")]
+    Encoding(String),
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodec(String),
+    #[error("Invalid hash length: {0} bytes")]
+    Length(usize),
+}
+
+/// Encode the CBOR header of a byte string with the given length
+fn bytes_header(len: u64) -> Vec<u8> {
+    const MAJOR_BYTES: u8 = 0x40;
+    match len {
+        0..=23 => vec![MAJOR_BYTES | len as u8],
+        24..=0xff => vec![MAJOR_BYTES | 24, len as u8],
+        0x100..=0xffff => [&[MAJOR_BYTES | 25][..], &(len as u16).to_be_bytes()].concat(),
+        0x10000..=0xffff_ffff => [&[MAJOR_BYTES | 26][..], &(len as u32).to_be_bytes()].concat(),
+        _ => [&[MAJOR_BYTES | 27][..], &len.to_be_bytes()].concat(),
+    }
+}
+
+/// Incremental hasher shared by `HashingReader` and `HashingWriter`
+#[derive(Debug, Clone)]
+struct StreamHasher {
+    hasher: blake3::Hasher,
+    /// Number of bytes hashed, not including any CBOR header
+    len: u64,
+    /// Length of the body, when hashing a body as a CBOR byte string
+    body_len: Option<u64>,
+}
+
+impl StreamHasher {
+    fn new() -> Self {
+        Self {
+            hasher: blake3::Hasher::new(),
+            len: 0,
+            body_len: None,
+        }
+    }
+
+    fn for_body(body_len: u64) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&bytes_header(body_len));
+        Self {
+            hasher,
+            len: 0,
+            body_len: Some(body_len),
+        }
+    }
+
+    /// Hash bytes, checking they don't overrun the body length
+    fn update(&mut self, buf: &[u8], kind: io::ErrorKind) -> Result<(), io::Error> {
+        let len = self.len + buf.len() as u64;
+        if let Some(body_len) = self.body_len
+            && len > body_len
+        {
+            return Err(io::Error::new(
+                kind,
+                format!("Body is longer than its declared length of {body_len} bytes"),
+            ));
+        }
+        self.hasher.update(buf);
+        self.len = len;
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Hash, io::Error> {
+        if let Some(body_len) = self.body_len
+            && self.len != body_len
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Body is shorter than its declared length. Expected {body_len} bytes. Got: {}",
+                    self.len
+                ),
+            ));
+        }
+        Ok(Hash(self.hasher.finalize()))
+    }
+}
+
+/// Reader that hashes the bytes read through it.
+///
+/// ```
+/// use std::io::Read;
+/// use szdt_core::hash::{Hash, HashingReader};
+///
+/// let mut reader = HashingReader::new(&b"Hello"[..]);
+/// let mut body = Vec::new();
+/// reader.read_to_end(&mut body).unwrap();
+/// assert_eq!(reader.hash().unwrap(), Hash::new(b"Hello"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HashingReader<R> {
+    reader: R,
+    hasher: StreamHasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Hash the bytes read
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            hasher: StreamHasher::new(),
+        }
+    }
+
+    /// Hash the bytes read as a CBOR byte string of the given length, so that
+    /// the hash is the content address of the body (`Bytes(body).to_link()`).
+    /// Reads stop after `len` bytes, like `Read::take`, so bytes after the
+    /// body are left unread in the inner reader. Reaching the end of input
+    /// before `len` bytes have been read is an error.
+    pub fn for_body(reader: R, len: u64) -> Self {
+        Self {
+            reader,
+            hasher: StreamHasher::for_body(len),
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.hasher.len
+    }
+
+    /// Hash of the bytes read so far.
+    /// For bodies, returns an error if the whole body hasn't been read.
+    pub fn hash(&self) -> Result<Hash, io::Error> {
+        self.hasher.finalize()
+    }
+
+    /// Unwrap inner reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Cap bodies at their length, so the reader can be used mid-stream
+        let max = match self.hasher.body_len {
+            Some(body_len) => buf
+                .len()
+                .min(usize::try_from(body_len - self.hasher.len).unwrap_or(usize::MAX)),
+            None => buf.len(),
+        };
+        let n = self.reader.read(&mut buf[..max])?;
+        self.hasher.update(&buf[..n], io::ErrorKind::InvalidData)?;
+        if n == 0 && max > 0 {
+            // End of input. Check bodies weren't truncated.
+            self.hasher.finalize()?;
+        }
+        Ok(n)
+    }
+}
+
+/// Writer that hashes the bytes written through it.
+#[derive(Debug, Clone)]
+pub struct HashingWriter<W> {
+    writer: W,
+    hasher: StreamHasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Hash the bytes written
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            hasher: StreamHasher::new(),
+        }
+    }
+
+    /// Hash the bytes written as a CBOR byte string of the given length, so
+    /// that the hash is the content address of the body
+    /// (`Bytes(body).to_link()`). Writing more than `len` bytes is an error.
+    ///
+    /// Only the body bytes are passed through to the inner writer, not the
+    /// CBOR header.
+    pub fn for_body(writer: W, len: u64) -> Self {
+        Self {
+            writer,
+            hasher: StreamHasher::for_body(len),
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn bytes_written(&self) -> u64 {
+        self.hasher.len
+    }
+
+    /// Hash of the bytes written so far.
+    /// For bodies, returns an error if the whole body hasn't been written.
+    pub fn hash(&self) -> Result<Hash, io::Error> {
+        self.hasher.finalize()
+    }
+
+    /// Unwrap inner writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Check the length before writing, so the inner writer never sees
+        // bytes past the end of a body.
+        let remaining = self.hasher.body_len.map_or(u64::MAX, |body_len| {
+            body_len.saturating_sub(self.hasher.len)
+        });
+        if !buf.is_empty() && remaining == 0 {
+            self.hasher.update(buf, io::ErrorKind::InvalidInput)?;
+        }
+        let buf = &buf[..buf.len().min(remaining.try_into().unwrap_or(usize::MAX))];
+        let n = self.writer.write(buf)?;
+        self.hasher.update(&buf[..n], io::ErrorKind::InvalidInput)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
 }
 
 impl PartialOrd for Hash {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.0.as_bytes().cmp(other.0.as_bytes()))
+        Some(self.cmp(other))
     }
 }
 
@@ -159,6 +503,128 @@ mod tests {
         assert_eq!(&serialized[2..], hash.as_bytes(), "Hash bytes should match");
     }
 
+    #[test]
+    fn test_parse_roundtrip() {
+        let hash = Hash::new(b"Hello World");
+        let base32 = hash.to_string();
+        assert_eq!(base32.parse::<Hash>().unwrap(), hash);
+        assert_eq!(base32.to_uppercase().parse::<Hash>().unwrap(), hash);
+        assert_eq!(hash.to_hex().parse::<Hash>().unwrap(), hash);
+        assert_eq!(hash.to_cid().parse::<Hash>().unwrap(), hash);
+
+        let multihash = hash.to_multihash();
+        let multibase = format!("b{}", BASE32_NOPAD.encode(&multihash).to_lowercase());
+        assert_eq!(multibase.parse::<Hash>().unwrap(), hash);
+        let multibase = format!("z{}", base58btc::encode(&multihash));
+        assert_eq!(multibase.parse::<Hash>().unwrap(), hash);
+        let multibase = format!("f{}", data_encoding::HEXLOWER.encode(&multihash));
+        assert_eq!(multibase.parse::<Hash>().unwrap(), hash);
+    }
+
+    #[test]
+    fn test_to_cid() {
+        let hash = Hash::from_bytes([0; 32]);
+        // CIDv1, CBOR codec, Blake3 multihash
+        assert_eq!(
+            hash.to_cid(),
+            "bafir4iaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_other_multihashes() {
+        // sha2-256 multihash
+        let multihash = [&[0x12, 0x20][..], &[0; 32]].concat();
+        let multibase = format!("z{}", base58btc::encode(&multihash));
+        assert!(matches!(
+            multibase.parse::<Hash>(),
+            Err(ParseError::UnsupportedCodec(_))
+        ));
+        assert!(matches!(
+            "not a hash".parse::<Hash>(),
+            Err(ParseError::Encoding(_))
+        ));
+        assert!("".parse::<Hash>().is_err());
+    }
+
+    /// Reader that fails after returning some bytes
+    struct FailingReader(usize);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::Error::other("read failed"));
+            }
+            let n = self.0.min(buf.len());
+            buf[..n].fill(0);
+            self.0 -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let data = vec![7u8; 10_000];
+        assert_eq!(Hash::from_reader(&data[..]).unwrap(), Hash::new(&data));
+        assert!(Hash::from_reader(FailingReader(100)).is_err());
+    }
+
+    #[test]
+    fn test_hashing_body_matches_link() {
+        use crate::bytes::Bytes;
+        use crate::link::ToLink;
+
+        for len in [0, 23, 24, 255, 256, 70_000] {
+            let body = vec![1u8; len];
+            let link = Bytes(body.clone()).to_link().unwrap();
+
+            let mut reader = HashingReader::for_body(&body[..], len as u64);
+            io::copy(&mut reader, &mut io::sink()).unwrap();
+            assert_eq!(reader.hash().unwrap(), link);
+
+            let mut writer = HashingWriter::for_body(Vec::new(), len as u64);
+            writer.write_all(&body).unwrap();
+            assert_eq!(writer.hash().unwrap(), link);
+            assert_eq!(writer.into_inner(), body);
+        }
+    }
+
+    #[test]
+    fn test_hashing_body_length_mismatch() {
+        use crate::bytes::Bytes;
+        use crate::link::ToLink;
+
+        // Body shorter than declared
+        let mut reader = HashingReader::for_body(&b"Hello"[..], 10);
+        assert!(io::copy(&mut reader, &mut io::sink()).is_err());
+        assert!(reader.hash().is_err());
+
+        // Reads stop at the declared length, leaving the rest unread
+        let mut reader = HashingReader::for_body(&b"Hello"[..], 2);
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"He");
+        assert_eq!(
+            reader.hash().unwrap(),
+            Bytes(b"He".to_vec()).to_link().unwrap()
+        );
+        assert_eq!(reader.into_inner(), b"llo");
+
+        let mut writer = HashingWriter::for_body(Vec::new(), 2);
+        assert!(writer.write_all(b"Hello").is_err());
+        assert_eq!(writer.bytes_written(), 2);
+        assert_eq!(writer.into_inner(), b"He");
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let mut writer = HashingWriter::new(Vec::new());
+        writer.write_all(b"Hello ").unwrap();
+        writer.write_all(b"World").unwrap();
+        assert_eq!(writer.hash().unwrap(), Hash::new(b"Hello World"));
+        assert_eq!(writer.bytes_written(), 11);
+    }
+
     #[test]
     fn test_hash_serialize_roundtrip() {
         let original_hash = Hash::new(b"roundtrip test data");
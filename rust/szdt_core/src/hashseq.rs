@@ -11,7 +11,7 @@ impl HashSeq {
 
     /// Create a new sequence of hashes.
     pub fn new(bytes: Vec<u8>) -> Result<Self, Error> {
-        if bytes.len() % 32 == 0 {
+        if bytes.len().is_multiple_of(32) {
             Ok(Self(bytes))
         } else {
             Err(Error::InvalidBufferSize)
@@ -0,0 +1,192 @@
+//! Helpers for working with extension headers.
+//!
+//! Memo headers are open-ended CBOR maps. Extension headers must follow the
+//! rules in the memo spec:
+//!
+//! - Keys are lowercase strings
+//! - Values are never null (unused headers are omitted instead)
+//! - Values don't contain floats, since they are always encoded as 64-bit
+//!   floats, which isn't the shortest form CBOR/c requires
+//! - Headers defined by HTTP keep their HTTP semantics. Since HTTP field
+//!   values are strings, these headers must have text values.
+use crate::error::Error;
+use crate::value::{from_value, to_value};
+use cbor4ii::core::Value;
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
+
+/// Protected headers with predefined semantics.
+/// These have typed fields on `ProtectedHeaders`, and can't be set as
+/// extension headers.
+pub const RESERVED_PROTECTED_HEADERS: &[&str] = &[
+    "iss",
+    "iss-nickname",
+    "iat",
+    "nbf",
+    "exp",
+    "prev",
+    "content-type",
+    "path",
+    "src",
+];
+
+/// Unprotected headers with predefined semantics.
+/// These have typed fields on `UnprotectedHeaders`, and can't be set as
+/// extension headers.
+pub const RESERVED_UNPROTECTED_HEADERS: &[&str] = &["sig", "cosigs", "witnesses"];
+
+/// Common HTTP headers that may appear on memos.
+/// These headers have the same semantics as their HTTP counterparts.
+pub const HTTP_HEADERS: &[&str] = &[
+    "cache-control",
+    "content-disposition",
+    "content-encoding",
+    "content-language",
+    "content-length",
+    "content-location",
+    "date",
+    "digest",
+    "etag",
+    "expires",
+    "last-modified",
+    "link",
+    "location",
+    "vary",
+];
+
+/// Check that a header key is valid for an extension header.
+/// Keys must be non-empty, lowercase HTTP field names (tokens), and must not
+/// be one of the given reserved headers.
+pub fn check_key(key: &str, reserved: &[&str]) -> Result<(), Error> {
+    if key.is_empty() {
+        return Err(Error::HeaderKeyInvalid("Key must not be empty".to_string()));
+    }
+    if let Some(c) = key.chars().find(|c| !is_lowercase_token_char(*c)) {
+        return Err(Error::HeaderKeyInvalid(format!(
+            "Key must be a lowercase token. Key {key:?} contains {c:?}"
+        )));
+    }
+    if reserved.contains(&key) {
+        return Err(Error::HeaderReserved(key.to_string()));
+    }
+    Ok(())
+}
+
+fn is_lowercase_token_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// Check that a header value is valid for the given key.
+pub fn check_value(key: &str, value: &Value) -> Result<(), Error> {
+    if has_float(value) {
+        return Err(Error::HeaderValueFloat(key.to_string()));
+    }
+    match value {
+        Value::Null => Err(Error::HeaderValueNull(key.to_string())),
+        Value::Text(_) => Ok(()),
+        _ if HTTP_HEADERS.contains(&key) => Err(Error::HeaderHttpConflict(format!(
+            "{key} is an HTTP header and must have a text value"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn has_float(value: &Value) -> bool {
+    match value {
+        Value::Float(_) => true,
+        Value::Array(items) => items.iter().any(has_float),
+        Value::Map(entries) => entries
+            .iter()
+            .any(|(key, value)| has_float(key) || has_float(value)),
+        Value::Tag(_, value) => has_float(value),
+        _ => false,
+    }
+}
+
+/// Get a typed header value from an extension header map
+pub(crate) fn get<T: DeserializeOwned>(
+    extra: &HashMap<String, Value>,
+    key: &str,
+) -> Result<Option<T>, Error> {
+    extra.get(key).map(from_value).transpose()
+}
+
+/// Set a typed header value on an extension header map, checking the key
+/// and value against the header rules.
+pub(crate) fn set<T: Serialize>(
+    extra: &mut HashMap<String, Value>,
+    reserved: &[&str],
+    key: &str,
+    value: &T,
+) -> Result<(), Error> {
+    check_key(key, reserved)?;
+    let value = to_value(value)?;
+    check_value(key, &value)?;
+    extra.insert(key.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_key() {
+        assert!(check_key("x-release", RESERVED_PROTECTED_HEADERS).is_ok());
+        assert!(check_key("content-encoding", RESERVED_PROTECTED_HEADERS).is_ok());
+        assert!(matches!(
+            check_key("X-Release", RESERVED_PROTECTED_HEADERS),
+            Err(Error::HeaderKeyInvalid(_))
+        ));
+        assert!(matches!(
+            check_key("x release", RESERVED_PROTECTED_HEADERS),
+            Err(Error::HeaderKeyInvalid(_))
+        ));
+        assert!(matches!(
+            check_key("", RESERVED_PROTECTED_HEADERS),
+            Err(Error::HeaderKeyInvalid(_))
+        ));
+        assert!(matches!(
+            check_key("src", RESERVED_PROTECTED_HEADERS),
+            Err(Error::HeaderReserved(_))
+        ));
+        assert!(check_key("src", RESERVED_UNPROTECTED_HEADERS).is_ok());
+    }
+
+    #[test]
+    fn test_check_value() {
+        assert!(check_value("x-count", &Value::Integer(1)).is_ok());
+        assert!(check_value("content-language", &Value::Text("en".to_string())).is_ok());
+        assert!(matches!(
+            check_value("x-count", &Value::Null),
+            Err(Error::HeaderValueNull(_))
+        ));
+        assert!(matches!(
+            check_value("content-language", &Value::Integer(1)),
+            Err(Error::HeaderHttpConflict(_))
+        ));
+        assert!(matches!(
+            check_value("x-release", &Value::Float(1.0)),
+            Err(Error::HeaderValueFloat(_))
+        ));
+        assert!(matches!(
+            check_value("x-ratios", &Value::Array(vec![Value::Float(0.5)])),
+            Err(Error::HeaderValueFloat(_))
+        ));
+    }
+
+    #[test]
+    fn test_value_roundtrip() {
+        let tags = vec!["a".to_string(), "b".to_string()];
+        let value = to_value(&tags).unwrap();
+        assert_eq!(
+            value,
+            Value::Array(vec![
+                Value::Text("a".to_string()),
+                Value::Text("b".to_string())
+            ])
+        );
+        let decoded: Vec<String> = from_value(&value).unwrap();
+        assert_eq!(decoded, tags);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::did::DidKey;
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::memo::Memo;
+use std::collections::{BTreeSet, HashMap};
+
+/// A fork in version history, where more than one memo points to the same
+/// previous version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fork {
+    /// Version hash of the memo that was forked
+    pub base: Hash,
+    /// Version hashes of the memos that point to `base` via `prev`
+    pub branches: Vec<Hash>,
+}
+
+/// A version history graph of memos, linked together via their `prev` headers.
+///
+/// Memos are keyed by their version hash: the signing hash of their protected
+/// headers (`protected.signing_hash()`), which is what `prev` points to. Unlike
+/// the content address of the whole memo, the version hash doesn't change when
+/// unprotected headers are annotated or the memo is co-signed, so versions stay
+/// linked as memos are relayed. Links are validated
+/// as memos are added: a memo must have the same issuer as its previous
+/// version, and must not be issued before it.
+///
+/// Note that the history does not verify memo signatures. Memos should be
+/// validated before they are added.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    memos: HashMap<Hash, Memo>,
+    /// Index of memo hash to the hashes of memos that point to it via `prev`
+    children: HashMap<Hash, BTreeSet<Hash>>,
+}
+
+impl History {
+    /// Create an empty history
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a history from memos.
+    /// Memos may be given in any order.
+    pub fn from_memos<I: IntoIterator<Item = Memo>>(memos: I) -> Result<Self, Error> {
+        let mut history = Self::new();
+        for memo in memos {
+            history.add(memo)?;
+        }
+        Ok(history)
+    }
+
+    /// Add a memo to the history, returning its version hash.
+    /// Returns an error if the memo does not form a valid link with its
+    /// previous or next versions already in the history.
+    pub fn add(&mut self, memo: Memo) -> Result<Hash, Error> {
+        let hash = memo.protected.signing_hash()?;
+        if self.memos.contains_key(&hash) {
+            return Ok(hash);
+        }
+
+        // Check link to previous version
+        if let Some(prev_memo) = memo.protected.prev.and_then(|prev| self.memos.get(&prev)) {
+            check_link(prev_memo, &memo)?;
+        }
+
+        // Check links from next versions
+        for child in self.children.get(&hash).into_iter().flatten() {
+            check_link(&memo, &self.memos[child])?;
+        }
+
+        if let Some(prev) = memo.protected.prev {
+            self.children.entry(prev).or_default().insert(hash);
+        }
+        self.memos.insert(hash, memo);
+        Ok(hash)
+    }
+
+    /// Get a memo by hash
+    pub fn get(&self, hash: &Hash) -> Option<&Memo> {
+        self.memos.get(hash)
+    }
+
+    /// Number of memos in the history
+    pub fn len(&self) -> usize {
+        self.memos.len()
+    }
+
+    /// Is the history empty?
+    pub fn is_empty(&self) -> bool {
+        self.memos.is_empty()
+    }
+
+    /// Hashes of the memos that point to the given memo via `prev`
+    pub fn children(&self, hash: &Hash) -> Vec<Hash> {
+        self.children
+            .get(hash)
+            .map(|children| children.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the heads of the history (memos that are not the previous version
+    /// of any other memo in the history), sorted by hash.
+    pub fn heads(&self) -> Vec<Hash> {
+        let mut heads: Vec<Hash> = self
+            .memos
+            .keys()
+            .filter(|hash| !self.children.contains_key(hash))
+            .copied()
+            .collect();
+        heads.sort();
+        heads
+    }
+
+    /// Get the latest head for the given issuer and path, using last-write-wins
+    /// semantics. The head with the newest `iat` wins. If more than one head
+    /// has the newest `iat`, the head with the largest hash wins.
+    pub fn latest(&self, iss: &DidKey, path: Option<&str>) -> Option<Hash> {
+        self.heads()
+            .into_iter()
+            .filter(|hash| {
+                let memo = &self.memos[hash];
+                memo.protected.iss.as_ref() == Some(iss) && memo.protected.path.as_deref() == path
+            })
+            .max_by_key(|hash| (self.memos[hash].protected.iat, *hash))
+    }
+
+    /// Iterate over the ancestors of a memo, following `prev` links, starting
+    /// with the previous version of the memo. Iteration stops at the first
+    /// ancestor that is missing from the history.
+    pub fn ancestors(&self, hash: &Hash) -> Ancestors<'_> {
+        Ancestors {
+            history: self,
+            next: self.memos.get(hash).and_then(|memo| memo.protected.prev),
+        }
+    }
+
+    /// Get the forks in the history, sorted by base hash.
+    pub fn forks(&self) -> Vec<Fork> {
+        let mut forks: Vec<Fork> = self
+            .children
+            .iter()
+            .filter(|(_, children)| children.len() > 1)
+            .map(|(base, children)| Fork {
+                base: *base,
+                branches: children.iter().copied().collect(),
+            })
+            .collect();
+        forks.sort_by_key(|fork| fork.base);
+        forks
+    }
+
+    /// Is the given memo forked (does more than one memo point to it)?
+    pub fn is_forked(&self, hash: &Hash) -> bool {
+        self.children
+            .get(hash)
+            .is_some_and(|children| children.len() > 1)
+    }
+}
+
+/// Check that `memo` is a valid next version of `prev_memo`
+fn check_link(prev_memo: &Memo, memo: &Memo) -> Result<(), Error> {
+    if prev_memo.protected.iss != memo.protected.iss {
+        return Err(Error::PrevIssuerMismatch(format!(
+            "Expected {}. Got: {}",
+            display_iss(prev_memo.protected.iss.as_ref()),
+            display_iss(memo.protected.iss.as_ref())
+        )));
+    }
+    if memo.protected.iat < prev_memo.protected.iat {
+        return Err(Error::PrevIatError(format!(
+            "Memo iat {} is earlier than previous version iat {}",
+            memo.protected.iat, prev_memo.protected.iat
+        )));
+    }
+    Ok(())
+}
+
+fn display_iss(iss: Option<&DidKey>) -> String {
+    iss.map(|did| did.to_string()).unwrap_or("None".to_string())
+}
+
+/// Iterator over the ancestors of a memo. See [`History::ancestors`].
+pub struct Ancestors<'a> {
+    history: &'a History,
+    next: Option<Hash>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = (Hash, &'a Memo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash = self.next.take()?;
+        let memo = self.history.memos.get(&hash)?;
+        self.next = memo.protected.prev;
+        Some((hash, memo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotate::Annotation;
+    use crate::ed25519_key_material::Ed25519KeyMaterial;
+    use crate::link::ToLink;
+
+    fn create_test_key(seed: u8) -> Ed25519KeyMaterial {
+        Ed25519KeyMaterial::generate_from_entropy(&[seed; 32]).unwrap()
+    }
+
+    fn create_version(key: &Ed25519KeyMaterial, body: &str, iat: u64, prev: Option<&Memo>) -> Memo {
+        let mut memo = Memo::for_body(body).unwrap();
+        memo.protected.iat = iat;
+        memo.protected.path = Some("doc.txt".to_string());
+        memo.protected.prev = prev.map(|prev| prev.protected.signing_hash().unwrap());
+        memo.sign(key).unwrap();
+        memo
+    }
+
+    #[test]
+    fn test_history_chain() {
+        let key = create_test_key(1);
+        let v1 = create_version(&key, "v1", 1, None);
+        let v2 = create_version(&key, "v2", 2, Some(&v1));
+        let v3 = create_version(&key, "v3", 3, Some(&v2));
+
+        // Add out of order
+        let history = History::from_memos([v3.clone(), v1.clone(), v2.clone()]).unwrap();
+        let v3_hash = v3.protected.signing_hash().unwrap();
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.heads(), vec![v3_hash]);
+        assert!(history.forks().is_empty());
+
+        let ancestors: Vec<Hash> = history.ancestors(&v3_hash).map(|(hash, _)| hash).collect();
+        assert_eq!(
+            ancestors,
+            vec![
+                v2.protected.signing_hash().unwrap(),
+                v1.protected.signing_hash().unwrap()
+            ]
+        );
+
+        assert_eq!(history.latest(&key.did(), Some("doc.txt")), Some(v3_hash));
+        assert_eq!(history.latest(&key.did(), Some("other.txt")), None);
+    }
+
+    #[test]
+    fn test_history_ancestors_stop_at_missing_memo() {
+        let key = create_test_key(1);
+        let v1 = create_version(&key, "v1", 1, None);
+        let v2 = create_version(&key, "v2", 2, Some(&v1));
+        let v3 = create_version(&key, "v3", 3, Some(&v2));
+
+        let history = History::from_memos([v1, v3.clone()]).unwrap();
+        assert_eq!(
+            history
+                .ancestors(&v3.protected.signing_hash().unwrap())
+                .count(),
+            0
+        );
+        assert_eq!(history.heads().len(), 2);
+    }
+
+    #[test]
+    fn test_history_forks() {
+        let key = create_test_key(1);
+        let v1 = create_version(&key, "v1", 1, None);
+        let branch_a = create_version(&key, "a", 2, Some(&v1));
+        let branch_b = create_version(&key, "b", 2, Some(&v1));
+
+        let history =
+            History::from_memos([v1.clone(), branch_a.clone(), branch_b.clone()]).unwrap();
+        let v1_hash = v1.protected.signing_hash().unwrap();
+        let a_hash = branch_a.protected.signing_hash().unwrap();
+        let b_hash = branch_b.protected.signing_hash().unwrap();
+
+        assert!(history.is_forked(&v1_hash));
+        let forks = history.forks();
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].base, v1_hash);
+        assert_eq!(forks[0].branches.len(), 2);
+        assert_eq!(history.heads().len(), 2);
+
+        // Same iat, so largest hash wins
+        let latest = history.latest(&key.did(), Some("doc.txt")).unwrap();
+        assert_eq!(latest, a_hash.max(b_hash));
+    }
+
+    #[test]
+    fn test_history_rejects_issuer_mismatch() {
+        let alice = create_test_key(1);
+        let bob = create_test_key(2);
+        let v1 = create_version(&alice, "v1", 1, None);
+        let v2 = create_version(&bob, "v2", 2, Some(&v1));
+
+        let result = History::from_memos([v1.clone(), v2.clone()]);
+        assert!(matches!(result, Err(Error::PrevIssuerMismatch(_))));
+
+        // Also rejected when the next version is added first
+        let result = History::from_memos([v2, v1]);
+        assert!(matches!(result, Err(Error::PrevIssuerMismatch(_))));
+    }
+
+    #[test]
+    fn test_history_rejects_non_monotonic_iat() {
+        let key = create_test_key(1);
+        let v1 = create_version(&key, "v1", 10, None);
+        let v2 = create_version(&key, "v2", 5, Some(&v1));
+
+        let result = History::from_memos([v1, v2]);
+        assert!(matches!(result, Err(Error::PrevIatError(_))));
+    }
+
+    #[test]
+    fn test_history_survives_unprotected_changes() {
+        let key = create_test_key(1);
+        let cosigner = create_test_key(2);
+        let v1 = create_version(&key, "v1", 1, None);
+        let v2 = create_version(&key, "v2", 2, Some(&v1));
+        let v3 = create_version(&key, "v3", 3, Some(&v2));
+
+        // Annotate v1 and co-sign v2 after v2 and v3 were issued
+        let mut annotated_v1 = v1.clone();
+        let annotation = Annotation {
+            set: vec![(
+                "x-mirror".to_string(),
+                cbor4ii::core::Value::Text("https://example.com".to_string()),
+            )],
+            remove: vec![],
+        };
+        annotation.apply(&mut annotated_v1.unprotected).unwrap();
+        let mut cosigned_v2 = v2.clone();
+        cosigned_v2.cosign(&cosigner).unwrap();
+        assert_ne!(annotated_v1.to_link().unwrap(), v1.to_link().unwrap());
+        assert_ne!(cosigned_v2.to_link().unwrap(), v2.to_link().unwrap());
+
+        let history = History::from_memos([annotated_v1, cosigned_v2, v3.clone()]).unwrap();
+        let v3_hash = v3.protected.signing_hash().unwrap();
+        assert_eq!(history.heads(), vec![v3_hash]);
+        let ancestors: Vec<Hash> = history.ancestors(&v3_hash).map(|(hash, _)| hash).collect();
+        assert_eq!(
+            ancestors,
+            vec![
+                v2.protected.signing_hash().unwrap(),
+                v1.protected.signing_hash().unwrap()
+            ]
+        );
+        let (_, memo) = history.ancestors(&v3_hash).last().unwrap();
+        let mirror: Option<String> = memo.unprotected.get_header("x-mirror").unwrap();
+        assert_eq!(mirror.as_deref(), Some("https://example.com"));
+    }
+}
@@ -1,18 +1,31 @@
+pub mod annotate;
 pub mod base58btc;
+pub mod block_store;
 pub mod bytes;
 pub mod cbor_seq;
+pub mod chunked;
+pub mod conformance;
 pub mod contact;
+pub mod content_encoding;
 pub mod content_type;
+pub mod dag;
 pub mod did;
 pub mod ed25519;
 pub mod ed25519_key_material;
 pub mod error;
 pub mod hash;
 pub mod hashseq;
+pub mod header;
+pub mod history;
+pub mod limits;
 pub mod link;
 pub mod memo;
 pub mod mnemonic;
 pub mod nickname;
+pub mod test_vectors;
 pub mod text;
 pub mod time;
+pub mod url;
+pub mod validation;
 pub mod value;
+pub mod witness;
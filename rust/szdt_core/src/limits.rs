@@ -0,0 +1,340 @@
+//! Resource limits for decoding untrusted CBOR.
+//!
+//! CBOR item headers declare the length of the strings, arrays and maps that
+//! follow. A hostile archive can claim lengths far larger than the data it
+//! actually contains, or nest items deeply enough to exhaust the stack.
+//! `read_item` scans an item from a stream head by head, checking each
+//! declared length against `Limits` before reading it, so nothing is
+//! allocated or decoded until the whole item is known to be within limits.
+use crate::error::Error;
+use std::io::{BufRead, Read};
+
+/// Limits on the size and shape of decoded blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Max size in bytes of an encoded block, other than bodies
+    pub max_block_size: u64,
+    /// Max size in bytes of an encoded body. Bodies are blocks that are a
+    /// single byte string, such as the file bodies in an archive.
+    pub max_body_size: u64,
+    /// Max nesting depth of arrays, maps and tags within a block
+    pub max_depth: usize,
+    /// Max number of entries in a single array or map
+    pub max_entries: u64,
+    /// Max total size in bytes of a file reassembled from chunks, after
+    /// decoding. A few small chunks can be repeated or decompressed into a
+    /// very large file.
+    pub max_file_size: u64,
+}
+
+impl Limits {
+    /// No limits. Only use this for trusted data.
+    pub fn none() -> Self {
+        Self {
+            max_block_size: u64::MAX,
+            max_body_size: u64::MAX,
+            max_depth: usize::MAX,
+            max_entries: u64::MAX,
+            max_file_size: u64::MAX,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            // 16 MiB
+            max_block_size: 16 * 1024 * 1024,
+            // 1 GiB
+            max_body_size: 1024 * 1024 * 1024,
+            max_depth: 128,
+            max_entries: 65_536,
+            // 64 GiB
+            max_file_size: 64 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+const INDEFINITE: u8 = 31;
+
+/// Scans a single item, recording its bytes
+struct Scanner<'a, R> {
+    reader: &'a mut R,
+    limits: &'a Limits,
+    /// Is the item a body?
+    is_body: bool,
+    bytes: Vec<u8>,
+}
+
+/// A decoded item head
+struct Head {
+    major: u8,
+    info: u8,
+    arg: u64,
+}
+
+impl<R: BufRead> Scanner<'_, R> {
+    fn check_size(&self, additional: u64) -> Result<(), Error> {
+        let size = (self.bytes.len() as u64).saturating_add(additional);
+        if self.is_body && size > self.limits.max_body_size {
+            return Err(Error::BodyTooLarge(size, self.limits.max_body_size));
+        }
+        if !self.is_body && size > self.limits.max_block_size {
+            return Err(Error::BlockTooLarge(size, self.limits.max_block_size));
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, len: u64) -> Result<(), Error> {
+        self.check_size(len)?;
+        let start = self.bytes.len();
+        // Read through `take`, rather than allocating the declared length up
+        // front, so a truncated item can't cause a large allocation.
+        let read = (&mut *self.reader).take(len).read_to_end(&mut self.bytes)?;
+        if (read as u64) < len {
+            return Err(Error::CborDecode(format!(
+                "Unexpected end of input at byte {}",
+                start + read
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_head(&mut self) -> Result<Head, Error> {
+        self.read_exact(1)?;
+        let initial = self.bytes[self.bytes.len() - 1];
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+        let arg = match info {
+            0..=23 => info as u64,
+            24..=27 => {
+                let len = 1u64 << (info - 24);
+                self.read_exact(len)?;
+                let arg_bytes = &self.bytes[self.bytes.len() - len as usize..];
+                arg_bytes
+                    .iter()
+                    .fold(0u64, |arg, byte| (arg << 8) | *byte as u64)
+            }
+            INDEFINITE => 0,
+            _ => {
+                return Err(Error::CborDecode(format!(
+                    "Reserved additional info {info} at byte {}",
+                    self.bytes.len() - 1
+                )));
+            }
+        };
+        Ok(Head { major, info, arg })
+    }
+
+    fn check_entries(&self, entries: u64) -> Result<(), Error> {
+        if entries > self.limits.max_entries {
+            return Err(Error::TooManyEntries(entries, self.limits.max_entries));
+        }
+        Ok(())
+    }
+
+    /// Scan the rest of an item, given its head. Returns false if the head
+    /// was a break marker, ending an indefinite-length item.
+    fn scan_item(&mut self, head: Head, depth: usize) -> Result<bool, Error> {
+        match (head.major, head.info) {
+            (MAJOR_SIMPLE, INDEFINITE) => return Ok(false),
+            (MAJOR_BYTES | MAJOR_TEXT, INDEFINITE) => {
+                // Chunks of a definite-length string, followed by a break
+                loop {
+                    let chunk = self.read_head()?;
+                    if chunk.major == MAJOR_SIMPLE && chunk.info == INDEFINITE {
+                        break;
+                    }
+                    self.read_exact(chunk.arg)?;
+                }
+            }
+            (MAJOR_BYTES | MAJOR_TEXT, _) => self.read_exact(head.arg)?,
+            (MAJOR_ARRAY | MAJOR_MAP | MAJOR_TAG, _) => {
+                let depth = depth + 1;
+                if depth > self.limits.max_depth {
+                    return Err(Error::NestingTooDeep(self.limits.max_depth));
+                }
+                let per_entry = if head.major == MAJOR_MAP { 2 } else { 1 };
+                if head.major == MAJOR_TAG {
+                    self.scan_child(depth)?;
+                } else if head.info == INDEFINITE {
+                    let mut entries = 0u64;
+                    loop {
+                        let child = self.read_head()?;
+                        if !self.scan_item(child, depth)? {
+                            break;
+                        }
+                        entries += 1;
+                        self.check_entries(entries.div_ceil(per_entry))?;
+                    }
+                } else {
+                    self.check_entries(head.arg)?;
+                    for _ in 0..head.arg.saturating_mul(per_entry) {
+                        self.scan_child(depth)?;
+                    }
+                }
+            }
+            // Integers and simple values have no content beyond the head
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn scan_child(&mut self, depth: usize) -> Result<(), Error> {
+        let head = self.read_head()?;
+        if !self.scan_item(head, depth)? {
+            return Err(Error::CborDecode(format!(
+                "Unexpected break at byte {}",
+                self.bytes.len() - 1
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Read the bytes of the next CBOR item from a stream, enforcing limits.
+/// Returns `Error::Eof` if the stream is at its end.
+///
+/// Items are scanned, not validated. Decode the returned bytes to check that
+/// they are well-formed.
+pub fn read_item<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Vec<u8>, Error> {
+    let Some(&initial) = reader.fill_buf()?.first() else {
+        return Err(Error::Eof);
+    };
+    let mut scanner = Scanner {
+        reader,
+        limits,
+        is_body: initial >> 5 == MAJOR_BYTES,
+        bytes: Vec::new(),
+    };
+    scanner.scan_child(0)?;
+    Ok(scanner.bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use std::io::Cursor;
+
+    fn read(bytes: &[u8], limits: &Limits) -> Result<Vec<u8>, Error> {
+        read_item(&mut Cursor::new(bytes), limits)
+    }
+
+    #[test]
+    fn test_read_item_reads_one_item() {
+        let mut data = serde_cbor_core::to_vec(&vec![vec![1, 2], vec![3]]).unwrap();
+        let first = data.clone();
+        data.extend(serde_cbor_core::to_vec(&"next").unwrap());
+        let mut cursor = Cursor::new(data);
+        assert_eq!(read_item(&mut cursor, &Limits::default()).unwrap(), first);
+        let next = read_item(&mut cursor, &Limits::default()).unwrap();
+        assert_eq!(next, serde_cbor_core::to_vec(&"next").unwrap());
+        assert!(matches!(
+            read_item(&mut cursor, &Limits::default()),
+            Err(Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_huge_declared_length() {
+        // Byte string claiming 2^62 bytes, followed by only a few bytes
+        let data = [0x5b, 0x40, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3];
+        assert!(matches!(
+            read(&data, &Limits::default()),
+            Err(Error::BodyTooLarge(_, _))
+        ));
+        // Text string claiming 2^62 bytes
+        let data = [0x7b, 0x40, 0, 0, 0, 0, 0, 0, 0, 0x61];
+        assert!(matches!(
+            read(&data, &Limits::default()),
+            Err(Error::BlockTooLarge(_, _))
+        ));
+        // Within limits, but truncated
+        let data = [0x5b, 0x40, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3];
+        assert!(matches!(
+            read(&data, &Limits::none()),
+            Err(Error::CborDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_body_size() {
+        let body = serde_cbor_core::to_vec(&Bytes(vec![0; 100])).unwrap();
+        let limits = Limits {
+            max_block_size: 10,
+            max_body_size: 100,
+            ..Limits::default()
+        };
+        // Bodies are checked against the body size limit, not the block size
+        // limit, so a body one byte over the body size limit is rejected...
+        assert!(matches!(
+            read(&body, &limits),
+            Err(Error::BodyTooLarge(_, _))
+        ));
+        // ...and is allowed once it fits the body size limit, even though it
+        // is larger than the block size limit
+        let limits = Limits {
+            max_body_size: body.len() as u64,
+            ..limits
+        };
+        assert_eq!(read(&body, &limits).unwrap(), body);
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        // [[[[[]]]]]
+        let data = [0x81, 0x81, 0x81, 0x81, 0x80];
+        let limits = Limits {
+            max_depth: 4,
+            ..Limits::default()
+        };
+        assert!(matches!(
+            read(&data, &limits),
+            Err(Error::NestingTooDeep(4))
+        ));
+        let limits = Limits {
+            max_depth: 5,
+            ..Limits::default()
+        };
+        assert_eq!(read(&data, &limits).unwrap(), data);
+
+        // Deeply nested tags
+        let data = [0xc1; 1000];
+        assert!(matches!(
+            read(&data, &Limits::default()),
+            Err(Error::NestingTooDeep(_))
+        ));
+    }
+
+    #[test]
+    fn test_entries() {
+        let limits = Limits {
+            max_entries: 2,
+            ..Limits::default()
+        };
+        let array = serde_cbor_core::to_vec(&vec![1, 2, 3]).unwrap();
+        assert!(matches!(
+            read(&array, &limits),
+            Err(Error::TooManyEntries(3, 2))
+        ));
+        // Indefinite-length map with 3 entries
+        let map = [0xbf, 0x01, 0x01, 0x02, 0x02, 0x03, 0x03, 0xff];
+        assert!(matches!(
+            read(&map, &limits),
+            Err(Error::TooManyEntries(3, 2))
+        ));
+        // Array claiming 2^32 entries
+        let data = [0x9a, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(matches!(
+            read(&data, &Limits::default()),
+            Err(Error::TooManyEntries(_, _))
+        ));
+    }
+}
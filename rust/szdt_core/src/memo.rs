@@ -1,12 +1,17 @@
 use crate::bytes::Bytes;
+use crate::cbor_seq::check_canonical;
 use crate::ed25519_key_material::Ed25519KeyMaterial;
 use crate::error::Error;
 use crate::hash::Hash;
+use crate::header::{self, RESERVED_PROTECTED_HEADERS, RESERVED_UNPROTECTED_HEADERS};
 use crate::link::ToLink;
 use crate::time::now;
+use crate::validation::{TimeStatus, ValidationOptions, ValidationReport};
+use crate::witness::{self, Attestation, AttestationReport};
 use crate::{did::DidKey, error::TimestampComparison};
 use cbor4ii::core::Value;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer, de::DeserializeOwned};
 use std::collections::HashMap;
 
 /// Unprotected headers for a memo.
@@ -16,11 +21,61 @@ pub struct UnprotectedHeaders {
     /// Ed25519 signature over protected memo fields
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sig: Option<Bytes>,
+    /// Co-signatures over protected memo fields by parties other than the issuer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cosigs: Vec<Attestation>,
+    /// Witness signatures over protected memo fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub witnesses: Vec<Attestation>,
     /// Additional fields
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
+impl UnprotectedHeaders {
+    /// Get an extension header, deserializing it to the given type.
+    /// Returns `None` if the header is not present.
+    pub fn get_header<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        header::get(&self.extra, key)
+    }
+
+    /// Set an extension header, serializing the value to CBOR.
+    /// Keys must be lowercase and must not be a reserved header. Values must not
+    /// be null, and HTTP headers must have text values.
+    pub fn set_header<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        header::set(&mut self.extra, RESERVED_UNPROTECTED_HEADERS, key, value)
+    }
+
+    /// Remove an extension header, returning its value if present
+    pub fn remove_header(&mut self, key: &str) -> Option<Value> {
+        self.extra.remove(key)
+    }
+}
+
+/// The CBOR/c bytes protected headers were decoded from, along with the
+/// headers as decoded, so that changes made since decoding can be detected
+/// without decoding the bytes again.
+///
+/// Raw bytes are not part of the value of the headers, so they are ignored when
+/// comparing headers for equality.
+#[derive(Clone, Default)]
+struct RawHeaders(Option<(Vec<u8>, Box<ProtectedHeaders>)>);
+
+impl PartialEq for RawHeaders {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for RawHeaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some((bytes, _)) => write!(f, "RawHeaders({} bytes)", bytes.len()),
+            None => write!(f, "RawHeaders(None)"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProtectedHeaders {
     /// Issuer (DID)
@@ -31,6 +86,7 @@ pub struct ProtectedHeaders {
     /// are ultimately chosen by the user, so this value may be used, modified, or
     /// ignored by the user.
     #[serde(rename = "iss-nickname")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub iss_nickname: Option<String>,
     /// Issued at (UNIX timestamp, seconds)
     pub iat: u64,
@@ -55,6 +111,9 @@ pub struct ProtectedHeaders {
     /// Additional fields
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
+    /// Bytes the headers were decoded from, if any
+    #[serde(skip)]
+    raw: RawHeaders,
 }
 
 impl ProtectedHeaders {
@@ -71,12 +130,88 @@ impl ProtectedHeaders {
             path: None,
             src: body,
             extra: HashMap::new(),
+            raw: RawHeaders::default(),
         }
     }
+
+    /// Decode protected headers from CBOR/c bytes, keeping the bytes.
+    /// Returns `Error::NonCanonical` if the bytes are not canonical CBOR/c.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        check_canonical(bytes)?;
+        let mut headers: Self = serde_cbor_core::from_slice(bytes)?;
+        let decoded = Box::new(headers.clone());
+        headers.raw = RawHeaders(Some((bytes.to_vec(), decoded)));
+        Ok(headers)
+    }
+
+    /// The bytes the headers were decoded from, if they were decoded, and
+    /// have not been modified since.
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        let (bytes, decoded) = self.raw.0.as_ref()?;
+        if decoded.as_ref() != self {
+            return None;
+        }
+        Some(bytes)
+    }
+
+    /// The bytes covered by signatures over the headers.
+    ///
+    /// For decoded headers, these are the exact bytes that were decoded, so
+    /// signatures are verified over the bytes that were signed, rather than a
+    /// re-encoding of them. Otherwise (or if the headers have been modified
+    /// since decoding) the headers are encoded as CBOR/c.
+    pub fn signing_bytes(&self) -> Result<Vec<u8>, Error> {
+        match self.raw_bytes() {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Ok(serde_cbor_core::to_vec(self)?),
+        }
+    }
+
+    /// Blake3 hash of the signing bytes.
+    /// This is the hash signed by the issuer, co-signers and witnesses.
+    pub fn signing_hash(&self) -> Result<Hash, Error> {
+        Ok(Hash::new(self.signing_bytes()?))
+    }
+
+    /// Is a header with the given key present?
+    pub fn has_header(&self, key: &str) -> bool {
+        match key {
+            "iss" => self.iss.is_some(),
+            "iss-nickname" => self.iss_nickname.is_some(),
+            "iat" | "src" => true,
+            "nbf" => self.nbf.is_some(),
+            "exp" => self.exp.is_some(),
+            "prev" => self.prev.is_some(),
+            "content-type" => self.content_type.is_some(),
+            "path" => self.path.is_some(),
+            _ => self.extra.contains_key(key),
+        }
+    }
+
+    /// Get an extension header, deserializing it to the given type.
+    /// Returns `None` if the header is not present.
+    pub fn get_header<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        header::get(&self.extra, key)
+    }
+
+    /// Set an extension header, serializing the value to CBOR.
+    /// Keys must be lowercase and must not be a reserved header. Values must not
+    /// be null, and HTTP headers must have text values.
+    ///
+    /// Note that changing protected headers invalidates the signature, so
+    /// headers should be set before signing.
+    pub fn set_header<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        header::set(&mut self.extra, RESERVED_PROTECTED_HEADERS, key, value)
+    }
+
+    /// Remove an extension header, returning its value if present
+    pub fn remove_header(&mut self, key: &str) -> Option<Value> {
+        self.extra.remove(key)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(tag = "type", rename = "szdt/memo")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "RawMemo")]
 pub struct Memo {
     /// Unsigned headers
     pub unprotected: UnprotectedHeaders,
@@ -84,6 +219,75 @@ pub struct Memo {
     pub protected: ProtectedHeaders,
 }
 
+/// Memo with protected headers that have not been decoded yet
+#[derive(Deserialize)]
+#[serde(tag = "type", rename = "szdt/memo")]
+struct RawMemo {
+    unprotected: UnprotectedHeaders,
+    protected: Value,
+}
+
+impl TryFrom<RawMemo> for Memo {
+    type Error = Error;
+
+    /// Serde only exposes decoded values, not the bytes they were decoded
+    /// from, so the protected bytes are recovered by re-encoding the decoded
+    /// value. Encoding sorts map keys, so key order is checked on the decoded
+    /// value first. Other non-canonical encodings (like over-long integers)
+    /// can only be caught with the original bytes. See `Memo::from_slice`.
+    fn try_from(raw: RawMemo) -> Result<Self, Error> {
+        check_key_order(&raw.protected)?;
+        let bytes = serde_cbor_core::to_vec(&raw.protected)?;
+        Ok(Self {
+            unprotected: raw.unprotected,
+            protected: ProtectedHeaders::from_slice(&bytes)?,
+        })
+    }
+}
+
+/// Check that the keys of every map in a decoded value are in canonical order
+/// (bytewise order of their encodings), with no duplicates.
+fn check_key_order(value: &Value) -> Result<(), Error> {
+    match value {
+        Value::Map(entries) => {
+            let mut prev: Option<Vec<u8>> = None;
+            for (key, value) in entries {
+                let key_bytes = serde_cbor_core::to_vec(key)?;
+                if prev.as_ref().is_some_and(|prev| prev >= &key_bytes) {
+                    return Err(Error::NonCanonical(
+                        "Map keys are not in canonical order".to_string(),
+                    ));
+                }
+                prev = Some(key_bytes);
+                check_key_order(value)?;
+            }
+            Ok(())
+        }
+        Value::Array(items) => items.iter().try_for_each(check_key_order),
+        Value::Tag(_, value) => check_key_order(value),
+        _ => Ok(()),
+    }
+}
+
+impl Serialize for Memo {
+    /// Decoded protected headers are written back out as they were decoded,
+    /// so that signatures made by other implementations remain valid.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut memo = serializer.serialize_struct("Memo", 3)?;
+        memo.serialize_field("type", "szdt/memo")?;
+        memo.serialize_field("unprotected", &self.unprotected)?;
+        match self
+            .protected
+            .raw_bytes()
+            .and_then(|bytes| serde_cbor_core::from_slice::<Value>(bytes).ok())
+        {
+            Some(protected) => memo.serialize_field("protected", &protected)?,
+            None => memo.serialize_field("protected", &self.protected)?,
+        }
+        memo.end()
+    }
+}
+
 impl Memo {
     /// Create a new memo with the given hash for the body content.
     pub fn new(body: Hash) -> Self {
@@ -99,6 +303,17 @@ impl Memo {
         Ok(Self::new(body.to_link()?))
     }
 
+    /// Decode a memo from the CBOR/c bytes of a memo block.
+    ///
+    /// The whole block must be canonical CBOR/c, so the protected headers are
+    /// exactly the bytes they were encoded as, and signatures are checked over
+    /// those bytes. Returns `Error::NonCanonical` otherwise. Prefer this over
+    /// deserializing with serde, which can't see the original bytes.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        check_canonical(bytes)?;
+        Ok(serde_cbor_core::from_slice(bytes)?)
+    }
+
     /// Create a memo wrapping empty body content
     pub fn empty() -> Self {
         Self::new(Hash::new([]))
@@ -108,7 +323,7 @@ impl Memo {
     pub fn sign(&mut self, key_material: &Ed25519KeyMaterial) -> Result<(), Error> {
         // Set the issuer DID on the protected headers
         self.protected.iss = Some(key_material.did());
-        let protected_hash = &self.protected.to_link()?;
+        let protected_hash = &self.protected.signing_hash()?;
 
         // Sign
         let sig = key_material.sign(protected_hash.as_bytes())?;
@@ -118,6 +333,40 @@ impl Memo {
         Ok(())
     }
 
+    /// Co-sign the protected headers with the given key material.
+    /// Replaces any previous co-signature by the same key.
+    pub fn cosign(&mut self, key_material: &Ed25519KeyMaterial) -> Result<(), Error> {
+        let attestation = Attestation::sign(key_material, &self.protected.signing_hash()?)?;
+        witness::upsert(&mut self.unprotected.cosigs, attestation);
+        Ok(())
+    }
+
+    /// Witness the protected headers with the given key material.
+    /// Replaces any previous witness signature by the same key.
+    pub fn witness(&mut self, key_material: &Ed25519KeyMaterial) -> Result<(), Error> {
+        let attestation = Attestation::sign(key_material, &self.protected.signing_hash()?)?;
+        witness::upsert(&mut self.unprotected.witnesses, attestation);
+        Ok(())
+    }
+
+    /// Verify co-signatures, reporting which co-signers validly signed
+    pub fn verify_cosigs(&self) -> Result<AttestationReport, Error> {
+        let protected_hash = self.protected.signing_hash()?;
+        Ok(AttestationReport::verify(
+            &self.unprotected.cosigs,
+            &protected_hash,
+        ))
+    }
+
+    /// Verify witness signatures, reporting which witnesses validly signed
+    pub fn verify_witnesses(&self) -> Result<AttestationReport, Error> {
+        let protected_hash = self.protected.signing_hash()?;
+        Ok(AttestationReport::verify(
+            &self.unprotected.witnesses,
+            &protected_hash,
+        ))
+    }
+
     /// Verify the memo signature, returning a result.
     /// In the case that memo is not signed, will return an error of `Error::MemoUnsigned`.
     pub fn verify(&self) -> Result<(), Error> {
@@ -132,7 +381,7 @@ impl Memo {
         let key_material = Ed25519KeyMaterial::try_from(iss)?;
 
         // Construct the signing bytes
-        let protected_hash = self.protected.to_link()?;
+        let protected_hash = self.protected.signing_hash()?;
         // Verify the signature against the signing bytes.
         key_material.verify(protected_hash.as_bytes(), &sig.0)?;
         Ok(())
@@ -155,22 +404,73 @@ impl Memo {
     }
 
     /// Is memo valid?
-    /// Checks if expired or too early, and verifies the signature.
+    /// Checks time bounds and required headers according to the validation
+    /// options, and verifies the signature. Checks that required co-signers
+    /// validly co-signed, and if the options have a witness policy, that the
+    /// policy is satisfied. Other co-signatures and witness signatures are
+    /// unprotected, so anyone can add them, and invalid ones are ignored.
     /// Unsigned memos are considered invalid (untrusted).
-    pub fn validate(&self, now_time: Option<u64>) -> Result<(), Error> {
-        if self.is_expired(now_time) {
+    pub fn validate(&self, options: &ValidationOptions) -> Result<(), Error> {
+        let time = options.time();
+        let exp = options.check_exp(self, time);
+        if exp.status == TimeStatus::Invalid {
             return Err(Error::MemoExpError(TimestampComparison::new(
-                self.protected.exp,
-                now_time,
+                exp.timestamp,
+                Some(time),
             )));
         }
-        if self.is_too_early(now_time) {
+        let nbf = options.check_nbf(self, time);
+        if nbf.status == TimeStatus::Invalid {
             return Err(Error::MemoNbfError(TimestampComparison::new(
-                self.protected.nbf,
-                now_time,
+                nbf.timestamp,
+                Some(time),
+            )));
+        }
+        let iat = options.check_iat(self, time);
+        if iat.status == TimeStatus::Invalid {
+            return Err(Error::MemoIatError(TimestampComparison::new(
+                iat.timestamp,
+                Some(time),
             )));
         }
-        self.verify()
+        let max_age = options.check_max_age(self, time);
+        if max_age.status == TimeStatus::Invalid {
+            return Err(Error::MemoMaxAgeError(TimestampComparison::new(
+                max_age.timestamp,
+                Some(time),
+            )));
+        }
+        let missing_headers = options.missing_headers(self);
+        if !missing_headers.is_empty() {
+            return Err(Error::MemoHeaderMissing(missing_headers.join(", ")));
+        }
+        self.verify()?;
+        let missing_cosigners = options.missing_cosigners(&self.verify_cosigs()?);
+        if !missing_cosigners.is_empty() {
+            let missing: Vec<String> = missing_cosigners
+                .iter()
+                .map(|did| did.to_string())
+                .collect();
+            return Err(Error::CosigInvalid(format!(
+                "required co-signers did not validly co-sign: {}",
+                missing.join(", ")
+            )));
+        }
+        if let Some(policy) = &options.witness_policy {
+            policy.check(&self.verify_witnesses()?)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the memo, returning a report of the result of every check,
+    /// rather than the first failure.
+    /// If `body_hash` is given, it is checked against the `src` of the memo.
+    pub fn validation_report(
+        &self,
+        options: &ValidationOptions,
+        body_hash: Option<&Hash>,
+    ) -> ValidationReport {
+        ValidationReport::for_memo(self, options, body_hash)
     }
 
     /// Check the hash of a serializable value against the `src` field of this memo.
@@ -269,7 +569,7 @@ mod tests {
         let memo = Memo::for_body(body_content).unwrap();
 
         // Unsigned memo should be invalid
-        assert!(memo.validate(None).is_err());
+        assert!(memo.validate(&ValidationOptions::default()).is_err());
     }
 
     #[test]
@@ -285,6 +585,133 @@ mod tests {
         memo.verify().unwrap();
     }
 
+    /// Build a signed memo as another implementation might, with protected
+    /// headers that don't match this implementation's encoding.
+    fn create_foreign_memo(key: &Ed25519KeyMaterial) -> Value {
+        let text = |value: &str| Value::Text(value.to_string());
+        // Includes an explicit null `iss-nickname`, which this implementation
+        // omits when encoding.
+        let protected = Value::Map(vec![
+            (text("iss"), text(&key.did().to_string())),
+            (text("iss-nickname"), Value::Null),
+            (text("iat"), Value::Integer(1)),
+            (
+                text("src"),
+                Value::Bytes(Hash::new(b"body").as_bytes().to_vec()),
+            ),
+        ]);
+        let protected_bytes = serde_cbor_core::to_vec(&protected).unwrap();
+        let sig = key.sign(Hash::new(&protected_bytes).as_bytes()).unwrap();
+        Value::Map(vec![
+            (text("type"), text("szdt/memo")),
+            (
+                text("unprotected"),
+                Value::Map(vec![(text("sig"), Value::Bytes(sig))]),
+            ),
+            (text("protected"), protected),
+        ])
+    }
+
+    #[test]
+    fn test_verify_over_decoded_bytes() {
+        let key = create_test_key();
+        let foreign = serde_cbor_core::to_vec(&create_foreign_memo(&key)).unwrap();
+        let memo: Memo = serde_cbor_core::from_slice(&foreign).unwrap();
+        assert!(memo.protected.iss_nickname.is_none());
+        // Re-encoding the headers would drop the null `iss-nickname`, and
+        // change the signing bytes
+        assert_ne!(
+            serde_cbor_core::to_vec(&memo.protected).unwrap(),
+            memo.protected.signing_bytes().unwrap()
+        );
+        memo.verify().unwrap();
+
+        // Memo is written back out exactly as it was decoded
+        assert_eq!(serde_cbor_core::to_vec(&memo).unwrap(), foreign);
+    }
+
+    #[test]
+    fn test_verify_modified_after_decoding() {
+        let key = create_test_key();
+        let foreign = serde_cbor_core::to_vec(&create_foreign_memo(&key)).unwrap();
+        let mut memo: Memo = serde_cbor_core::from_slice(&foreign).unwrap();
+        memo.protected.path = Some("evil.txt".to_string());
+        assert!(memo.protected.raw_bytes().is_none());
+        assert!(memo.verify().is_err());
+    }
+
+    /// Encode a memo block around the given protected header bytes
+    fn encode_memo(protected: &[u8], unprotected: &UnprotectedHeaders) -> Vec<u8> {
+        // Keys in canonical order: "type", "protected", "unprotected"
+        let mut bytes = vec![0xa3];
+        bytes.extend(serde_cbor_core::to_vec(&"type").unwrap());
+        bytes.extend(serde_cbor_core::to_vec(&"szdt/memo").unwrap());
+        bytes.extend(serde_cbor_core::to_vec(&"protected").unwrap());
+        bytes.extend(protected);
+        bytes.extend(serde_cbor_core::to_vec(&"unprotected").unwrap());
+        bytes.extend(serde_cbor_core::to_vec(unprotected).unwrap());
+        bytes
+    }
+
+    #[test]
+    fn test_memo_from_slice_rejects_non_canonical_protected_headers() {
+        let key = create_test_key();
+        let mut memo = Memo::new(Hash::new(b"body"));
+        memo.protected.iat = 1;
+        memo.protected.nbf = None;
+        memo.sign(&key).unwrap();
+        let bytes = serde_cbor_core::to_vec(&memo).unwrap();
+        assert_eq!(
+            encode_memo(&memo.protected.signing_bytes().unwrap(), &memo.unprotected),
+            bytes
+        );
+        assert_eq!(Memo::from_slice(&bytes).unwrap(), memo);
+
+        // Encode `iat` as an eight-byte integer, and sign over the
+        // non-canonical bytes
+        let mut protected = memo.protected.signing_bytes().unwrap();
+        let iat = [0x63, b'i', b'a', b't', 0x01];
+        let at = protected.windows(5).position(|w| w == iat).unwrap() + 4;
+        protected.splice(at..at + 1, [0x1b, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let sig = key.sign(Hash::new(&protected).as_bytes()).unwrap();
+        memo.unprotected.sig = Some(Bytes(sig));
+        let bytes = encode_memo(&protected, &memo.unprotected);
+        assert!(matches!(
+            Memo::from_slice(&bytes),
+            Err(Error::NonCanonical(_))
+        ));
+    }
+
+    #[test]
+    fn test_memo_deserialize_rejects_unsorted_protected_headers() {
+        // {"src": h'00..', "iat": 1}, with keys out of order
+        let mut protected = vec![0xa2];
+        protected.extend(serde_cbor_core::to_vec(&"src").unwrap());
+        protected.extend(serde_cbor_core::to_vec(&Hash::new(b"body")).unwrap());
+        protected.extend(serde_cbor_core::to_vec(&"iat").unwrap());
+        protected.push(0x01);
+        let bytes = encode_memo(&protected, &UnprotectedHeaders::default());
+        assert!(serde_cbor_core::from_slice::<Memo>(&bytes).is_err());
+        assert!(matches!(
+            Memo::from_slice(&bytes),
+            Err(Error::NonCanonical(_))
+        ));
+    }
+
+    #[test]
+    fn test_protected_headers_reject_non_canonical() {
+        let headers = ProtectedHeaders::new(Hash::new(b"body"));
+        let mut bytes = serde_cbor_core::to_vec(&headers).unwrap();
+        ProtectedHeaders::from_slice(&bytes).unwrap();
+        // Encode the map length with a needless extra byte
+        let len = bytes[0] & 0x1f;
+        bytes.splice(0..1, [0xb8, len]);
+        assert!(matches!(
+            ProtectedHeaders::from_slice(&bytes),
+            Err(Error::NonCanonical(_))
+        ));
+    }
+
     #[test]
     fn test_signed_memo_validate() {
         let key = create_test_key();
@@ -292,7 +719,7 @@ mod tests {
         let mut memo = Memo::for_body(&body_content).unwrap();
 
         memo.sign(&key).unwrap();
-        memo.validate(None).unwrap();
+        memo.validate(&ValidationOptions::default()).unwrap();
     }
 
     #[test]
@@ -304,7 +731,7 @@ mod tests {
         memo.protected.exp = Some(now() - 3600); // Expired
         memo.sign(&key).unwrap();
 
-        assert!(memo.validate(None).is_err());
+        assert!(memo.validate(&ValidationOptions::default()).is_err());
     }
 
     #[test]
@@ -345,4 +772,103 @@ mod tests {
         }
         panic!("Serialized memo is not a map");
     }
+
+    #[test]
+    fn test_cosign_and_witness() {
+        let issuer = create_test_key();
+        let cosigner = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let witness_a = Ed25519KeyMaterial::generate_from_entropy(&[2u8; 32]).unwrap();
+        let witness_b = Ed25519KeyMaterial::generate_from_entropy(&[3u8; 32]).unwrap();
+
+        let mut memo = Memo::for_body(create_test_body()).unwrap();
+        memo.sign(&issuer).unwrap();
+        memo.cosign(&cosigner).unwrap();
+        memo.witness(&witness_a).unwrap();
+        memo.witness(&witness_b).unwrap();
+        // Witnessing again replaces the previous signature
+        memo.witness(&witness_b).unwrap();
+
+        // Survives roundtrip, and the issuer signature is still valid
+        let cbor_bytes = serde_cbor_core::to_vec(&memo).unwrap();
+        let decoded: Memo = serde_cbor_core::from_slice(&cbor_bytes).unwrap();
+        assert_eq!(decoded, memo);
+        decoded.verify().unwrap();
+
+        let cosigs = decoded.verify_cosigs().unwrap();
+        assert_eq!(cosigs.signed, vec![cosigner.did()]);
+        let witnesses = decoded.verify_witnesses().unwrap();
+        assert_eq!(witnesses.signed, vec![witness_a.did(), witness_b.did()]);
+        assert!(witnesses.is_valid());
+
+        // Changing the protected headers invalidates all signatures
+        let mut tampered = decoded.clone();
+        tampered.protected.path = Some("evil.txt".to_string());
+        assert!(tampered.verify().is_err());
+        assert!(tampered.verify_cosigs().unwrap().signed.is_empty());
+        let witnesses = tampered.verify_witnesses().unwrap();
+        assert!(witnesses.signed.is_empty());
+        assert_eq!(witnesses.invalid.len(), 2);
+    }
+
+    #[test]
+    fn test_typed_headers() {
+        let mut memo = Memo::empty();
+        memo.protected
+            .set_header("x-tags", &vec!["a".to_string(), "b".to_string()])
+            .unwrap();
+        memo.protected.set_header("x-count", &3u64).unwrap();
+        memo.unprotected.set_header("x-note", &"hi").unwrap();
+
+        let tags: Option<Vec<String>> = memo.protected.get_header("x-tags").unwrap();
+        assert_eq!(tags, Some(vec!["a".to_string(), "b".to_string()]));
+        let count: Option<u64> = memo.protected.get_header("x-count").unwrap();
+        assert_eq!(count, Some(3));
+        let missing: Option<u64> = memo.protected.get_header("x-missing").unwrap();
+        assert_eq!(missing, None);
+        let note: Option<String> = memo.unprotected.get_header("x-note").unwrap();
+        assert_eq!(note.as_deref(), Some("hi"));
+
+        // Wrong type is an error
+        let result: Result<Option<u64>, Error> = memo.protected.get_header("x-tags");
+        assert!(result.is_err());
+
+        // Survives roundtrip with signature intact
+        let key = create_test_key();
+        memo.sign(&key).unwrap();
+        let cbor_bytes = serde_cbor_core::to_vec(&memo).unwrap();
+        let decoded: Memo = serde_cbor_core::from_slice(&cbor_bytes).unwrap();
+        decoded.verify().unwrap();
+        let count: Option<u64> = decoded.protected.get_header("x-count").unwrap();
+        assert_eq!(count, Some(3));
+
+        assert!(memo.protected.remove_header("x-count").is_some());
+        assert!(!memo.protected.has_header("x-count"));
+    }
+
+    #[test]
+    fn test_set_header_enforces_rules() {
+        let mut memo = Memo::empty();
+        assert!(matches!(
+            memo.protected.set_header("X-Tags", &"a"),
+            Err(Error::HeaderKeyInvalid(_))
+        ));
+        assert!(matches!(
+            memo.protected.set_header("src", &"a"),
+            Err(Error::HeaderReserved(_))
+        ));
+        assert!(matches!(
+            memo.unprotected.set_header("sig", &"a"),
+            Err(Error::HeaderReserved(_))
+        ));
+        assert!(matches!(
+            memo.protected.set_header("x-missing", &None::<u64>),
+            Err(Error::HeaderValueNull(_))
+        ));
+        assert!(matches!(
+            memo.protected.set_header("content-encoding", &1u64),
+            Err(Error::HeaderHttpConflict(_))
+        ));
+        assert!(memo.protected.extra.is_empty());
+        assert!(memo.unprotected.extra.is_empty());
+    }
 }
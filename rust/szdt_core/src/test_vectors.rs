@@ -0,0 +1,997 @@
+//! Cross-implementation test vectors.
+//!
+//! The suite is generated from this crate, and written to `test-vectors/` at
+//! the root of the repository, as `vectors.cbor` (the canonical suite) and
+//! `vectors.json` (the same suite, with byte strings as hex, for convenience).
+//! Other implementations can decode each vector's `input` and check their
+//! results against its expectations. See `test-vectors/README.md`.
+//!
+//! Regenerate the suite with `cargo run -p szdt_core --example test_vectors -- generate ../test-vectors`.
+use crate::bytes::Bytes;
+use crate::cbor_seq::CborSeqReader;
+use crate::chunked::{CHUNKED, chunk_hashes, is_chunked, read_file};
+use crate::conformance;
+use crate::content_encoding::CONTENT_ENCODING;
+use crate::dag::DagIndex;
+use crate::did::DidKey;
+use crate::ed25519_key_material::Ed25519KeyMaterial;
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::hashseq::HashSeq;
+use crate::limits::Limits;
+use crate::link::ToLink;
+use crate::memo::{Memo, UnprotectedHeaders};
+use crate::value::{Block, BlockDecoder, to_value};
+use crate::witness::Attestation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// Version of the test vector format
+pub const VERSION: u64 = 3;
+
+/// "Hello World", compressed with zstd
+pub(crate) const HELLO_ZSTD: &[u8] = &[
+    0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x59, 0x00, 0x00, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x57,
+    0x6f, 0x72, 0x6c, 0x64, 0xc2, 0x5b, 0x24, 0x19,
+];
+
+/// Fixed timestamp used for all generated memos
+const TIMESTAMP: u64 = 1_700_000_000;
+
+/// A suite of test vectors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suite {
+    pub version: u64,
+    pub vectors: Vec<Vector>,
+}
+
+/// A test vector: some input bytes and the results expected from decoding them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vector {
+    pub name: String,
+    pub description: String,
+    /// Encoded input
+    pub input: Bytes,
+    #[serde(flatten)]
+    pub expected: Expected,
+}
+
+/// Results expected from decoding a vector, by kind of vector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+pub enum Expected {
+    /// A single memo block
+    Memo {
+        /// Hash of the memo block
+        hash: Hash,
+        /// Hash of the protected header bytes. This is what signatures sign.
+        signing_hash: Hash,
+        /// Hash of the memo body
+        src: Hash,
+        /// Issuer, if signed. The issuer signature must verify.
+        iss: Option<DidKey>,
+        /// DIDs of valid co-signers
+        cosigners: Vec<DidKey>,
+        /// DIDs of valid witnesses
+        witnesses: Vec<DidKey>,
+    },
+    /// A single block of any CBOR value, and its content address
+    Link { hash: Hash },
+    /// A HashSeq block
+    HashSeq {
+        /// Hash of the HashSeq block
+        hash: Hash,
+        /// Hash of the raw HashSeq bytes
+        root: Hash,
+        /// The hashes in the sequence
+        hashes: Vec<Hash>,
+    },
+    /// An archive. Every memo must verify, and every body must match its memo.
+    Archive {
+        /// Paths of files, in order
+        paths: Vec<String>,
+        /// Paths of files whose bodies are absent
+        absent: Vec<String>,
+        /// Hash of the content of each file, in order: its body, reassembled
+        /// from chunks and decoded. Null if the body or a chunk is absent.
+        contents: Vec<Option<Hash>>,
+    },
+    /// Input that must be rejected
+    Invalid {
+        /// Class of error expected. One of `decode`, `non-canonical`,
+        /// `invalid-block`, `invalid-signature` or `integrity`.
+        error: String,
+        /// For `non-canonical` errors, the CBOR/c rule violated
+        rule: Option<String>,
+        /// Only rejected by strict conformance checking. Lenient readers may
+        /// accept the input.
+        strict: bool,
+    },
+}
+
+impl Suite {
+    /// Encode the suite as CBOR/c
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_cbor_core::to_vec(self)?)
+    }
+
+    /// Decode a suite from CBOR/c
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(serde_cbor_core::from_slice(bytes)?)
+    }
+
+    /// Encode the suite as JSON, with byte strings as lowercase hex
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut json = String::new();
+        write_json(&mut json, &to_value(self)?, 0);
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Check every vector, returning the names and failures of vectors that
+    /// failed.
+    pub fn check(&self) -> Vec<(String, String)> {
+        self.vectors
+            .iter()
+            .filter_map(|vector| {
+                vector
+                    .check()
+                    .err()
+                    .map(|failure| (vector.name.clone(), failure))
+            })
+            .collect()
+    }
+}
+
+fn write_json(out: &mut String, value: &cbor4ii::core::Value, indent: usize) {
+    use cbor4ii::core::Value;
+    let pad = |depth: usize| "  ".repeat(depth);
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(value) => out.push_str(&value.to_string()),
+        Value::Integer(value) => out.push_str(&value.to_string()),
+        Value::Float(value) => out.push_str(&value.to_string()),
+        Value::Bytes(bytes) => write_json_string(out, &data_encoding::HEXLOWER.encode(bytes)),
+        Value::Text(text) => write_json_string(out, text),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Array(items) => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                write_json(out, item, indent + 1);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&pad(indent));
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push_str("{\n");
+            for (i, (key, value)) in entries.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                match key {
+                    Value::Text(key) => write_json_string(out, key),
+                    _ => write_json_string(out, "?"),
+                }
+                out.push_str(": ");
+                write_json(out, value, indent + 1);
+                out.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+        }
+        Value::Tag(_, value) => write_json(out, value, indent),
+        _ => out.push_str("null"),
+    }
+}
+
+fn write_json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn check_eq<T: PartialEq + std::fmt::Debug>(
+    what: &str,
+    expected: &T,
+    actual: &T,
+) -> Result<(), String> {
+    if expected != actual {
+        return Err(format!(
+            "{what} mismatch. Expected {expected:?}. Got: {actual:?}"
+        ));
+    }
+    Ok(())
+}
+
+/// Kebab-case name of a conformance rule, as used in vectors
+fn rule_name(rule: &conformance::Rule) -> Option<String> {
+    let cbor4ii::core::Value::Map(entries) = to_value(rule).ok()? else {
+        return None;
+    };
+    entries
+        .into_iter()
+        .find_map(|(key, value)| match (key, value) {
+            (cbor4ii::core::Value::Text(key), cbor4ii::core::Value::Text(value))
+                if key == "rule" =>
+            {
+                Some(value)
+            }
+            _ => None,
+        })
+}
+
+/// A decoded archive file: its memo, whether its body is present, and the
+/// hash of its content, if the body and every chunk are present
+type ArchiveFile = (Memo, bool, Option<Hash>);
+
+/// Class of error, and CBOR/c rule violated, for rejected input
+type Rejection = (String, Option<String>);
+
+fn rejection(error: &str) -> Rejection {
+    (error.to_string(), None)
+}
+
+/// Classify an error from the readers in this crate
+fn classify(err: Error, input: &[u8]) -> Rejection {
+    match err {
+        Error::Conformance(violation) => ("non-canonical".to_string(), rule_name(&violation.rule)),
+        // Lenient readers only report that a block isn't canonical. The
+        // conformance checker names the rule.
+        Error::NonCanonical(_) => (
+            "non-canonical".to_string(),
+            conformance::check_seq(input)
+                .err()
+                .and_then(|violation| rule_name(&violation.rule)),
+        ),
+        Error::InvalidBlock(_, _) => rejection("invalid-block"),
+        _ => rejection("decode"),
+    }
+}
+
+/// Read and verify an archive with the same readers as the `szdt` CLI,
+/// classifying the first error. Bodies are resolved by hash, so a memo may
+/// share the body of an earlier memo.
+fn read_archive(input: &[u8], strict: bool) -> Result<Vec<ArchiveFile>, Rejection> {
+    let decoder = BlockDecoder::new();
+    let mut reader = CborSeqReader::new(Cursor::new(input)).with_strict(strict);
+    // Memos, and the bodies that follow them
+    let mut files: Vec<(Memo, Option<Bytes>)> = Vec::new();
+    // Every body and chunk, by hash
+    let mut bodies: HashMap<Hash, Bytes> = HashMap::new();
+    loop {
+        match decoder.read_block(&mut reader) {
+            Ok(Block::Memo(memo)) => {
+                if memo.verify().is_err() {
+                    return Err(rejection("invalid-signature"));
+                }
+                files.push((*memo, None));
+            }
+            Ok(Block::Value(cbor4ii::core::Value::Bytes(bytes))) => {
+                let Some((memo, body)) = files.last_mut() else {
+                    return Err(rejection("decode"));
+                };
+                let bytes = Bytes(bytes);
+                let hash = bytes.to_link().map_err(|err| classify(err, input))?;
+                match body {
+                    None => {
+                        if memo.checksum(&hash).is_err() {
+                            return Err(rejection("integrity"));
+                        }
+                        *body = Some(bytes.clone());
+                    }
+                    // Only chunked bodies are followed by more bytes blocks
+                    Some(_) if !is_chunked(memo).map_err(|err| classify(err, input))? => {
+                        return Err(rejection("decode"));
+                    }
+                    Some(_) => {}
+                }
+                bodies.insert(hash, bytes);
+            }
+            Ok(_) => return Err(rejection("decode")),
+            Err(Error::Eof) => break,
+            Err(err) => return Err(classify(err, input)),
+        }
+    }
+    files
+        .into_iter()
+        .map(|(memo, body)| {
+            let Some(body) = body.or_else(|| bodies.get(&memo.protected.src).cloned()) else {
+                return Ok((memo, false, None));
+            };
+            let content = read_content(&memo, body, &bodies)?;
+            Ok((memo, true, content))
+        })
+        .collect::<Result<_, Error>>()
+        .map_err(|err| classify(err, input))
+}
+
+/// Get the hash of a file's content. Returns `None` if a chunk is absent.
+fn read_content(
+    memo: &Memo,
+    body: Bytes,
+    bodies: &HashMap<Hash, Bytes>,
+) -> Result<Option<Hash>, Error> {
+    if is_chunked(memo)?
+        && !chunk_hashes(&body)?
+            .iter()
+            .all(|hash| bodies.contains_key(&hash))
+    {
+        return Ok(None);
+    }
+    let content = read_file(memo, body, &Limits::default(), |hash| {
+        Ok::<_, Error>(bodies.get(hash).cloned())
+    })?;
+    Ok(Some(Hash::new(&content.0)))
+}
+
+impl Vector {
+    /// Check this crate's results for the vector against its expectations
+    pub fn check(&self) -> Result<(), String> {
+        let input = &self.input.0;
+        if !matches!(self.expected, Expected::Invalid { .. }) {
+            conformance::check_seq(input).map_err(|err| err.to_string())?;
+        }
+        match &self.expected {
+            Expected::Memo {
+                hash,
+                signing_hash,
+                src,
+                iss,
+                cosigners,
+                witnesses,
+            } => {
+                let memo = Memo::from_slice(input).map_err(|err| err.to_string())?;
+                check_eq("hash", hash, &Hash::new(input))?;
+                let actual = memo
+                    .protected
+                    .signing_hash()
+                    .map_err(|err| err.to_string())?;
+                check_eq("signing hash", signing_hash, &actual)?;
+                check_eq("src", src, &memo.protected.src)?;
+                check_eq("iss", iss, &memo.protected.iss)?;
+                if iss.is_some() {
+                    memo.verify().map_err(|err| err.to_string())?;
+                }
+                let cosigs = memo.verify_cosigs().map_err(|err| err.to_string())?;
+                check_eq("cosigners", cosigners, &cosigs.signed)?;
+                let actual = memo.verify_witnesses().map_err(|err| err.to_string())?;
+                check_eq("witnesses", witnesses, &actual.signed)?;
+                Ok(())
+            }
+            Expected::Link { hash } => {
+                let value: cbor4ii::core::Value =
+                    serde_cbor_core::from_slice(input).map_err(|err| err.to_string())?;
+                check_eq("hash", hash, &Hash::new(input))?;
+                let link = value.to_link().map_err(|err| err.to_string())?;
+                check_eq("link", hash, &link)
+            }
+            Expected::HashSeq { hash, root, hashes } => {
+                let hashseq: HashSeq =
+                    serde_cbor_core::from_slice(input).map_err(|err| err.to_string())?;
+                let hashseq = HashSeq::new(hashseq.into_vec()).map_err(|err| err.to_string())?;
+                check_eq("hash", hash, &Hash::new(input))?;
+                check_eq("hashes", hashes, &hashseq.iter().collect())?;
+                check_eq("root", root, &Hash::from(hashseq))
+            }
+            Expected::Archive {
+                paths,
+                absent,
+                contents,
+            } => {
+                let files = read_archive(input, false).map_err(|(class, _)| class)?;
+                let path = |memo: &Memo| memo.protected.path.clone().unwrap_or_default();
+                let actual_paths: Vec<String> =
+                    files.iter().map(|(memo, _, _)| path(memo)).collect();
+                check_eq("paths", paths, &actual_paths)?;
+                let actual_absent: Vec<String> = files
+                    .iter()
+                    .filter(|(_, present, _)| !present)
+                    .map(|(memo, _, _)| path(memo))
+                    .collect();
+                check_eq("absent", absent, &actual_absent)?;
+                let actual_contents: Vec<Option<Hash>> =
+                    files.iter().map(|(_, _, content)| *content).collect();
+                check_eq("contents", contents, &actual_contents)?;
+
+                // The index resolves the same bodies
+                let mut reader = CborSeqReader::new(Cursor::new(input));
+                let index = DagIndex::read(&mut reader).map_err(|err| err.to_string())?;
+                let entries = index.entries().map_err(|err| err.to_string())?;
+                let index_absent: Vec<String> = entries
+                    .iter()
+                    .filter(|entry| entry.body.is_none())
+                    .map(|entry| path(&entry.memo))
+                    .collect();
+                check_eq("index absent", absent, &index_absent)
+            }
+            Expected::Invalid {
+                error,
+                rule,
+                strict,
+            } => {
+                match read_archive(input, *strict) {
+                    Ok(_) => return Err(format!("Expected {error} error. Input was accepted.")),
+                    Err((actual_error, actual_rule)) => {
+                        check_eq("error", error, &actual_error)?;
+                        check_eq("rule", rule, &actual_rule)?;
+                    }
+                }
+                // Malformed blocks must be rejected by every reader, not just
+                // the archive reader
+                if *strict
+                    || !matches!(error.as_str(), "decode" | "non-canonical" | "invalid-block")
+                {
+                    return Ok(());
+                }
+                let mut reader = CborSeqReader::new(Cursor::new(input));
+                if DagIndex::read(&mut reader)
+                    .and_then(|index| index.entries().map(|_| ()))
+                    .is_ok()
+                {
+                    return Err(format!("Expected {error} error. DagIndex accepted input."));
+                }
+                if Memo::from_slice(input).is_ok() {
+                    return Err(format!(
+                        "Expected {error} error. Memo::from_slice accepted input."
+                    ));
+                }
+                if serde_cbor_core::from_slice::<Memo>(input).is_ok() {
+                    return Err(format!(
+                        "Expected {error} error. Memo deserialize accepted input."
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn test_key(seed: u8) -> Ed25519KeyMaterial {
+    Ed25519KeyMaterial::generate_from_entropy(&[seed; 32])
+        .expect("Should be able to generate key from fixed entropy")
+}
+
+/// Create a memo with fixed timestamps
+fn fixed_memo(body: &Bytes, path: &str) -> Result<Memo, Error> {
+    let mut memo = Memo::for_body(body)?;
+    memo.protected.iat = TIMESTAMP;
+    memo.protected.nbf = Some(TIMESTAMP);
+    memo.protected.path = Some(path.to_string());
+    memo.protected.content_type = Some("text/plain".to_string());
+    Ok(memo)
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    Ok(serde_cbor_core::to_vec(value)?)
+}
+
+/// Create a signed memo for a file split into the given chunks, returning the
+/// memo and its HashSeq body
+fn chunked_memo(
+    chunks: &[&Bytes],
+    path: &str,
+    key: &Ed25519KeyMaterial,
+) -> Result<(Memo, Bytes), Error> {
+    let hashes = chunks
+        .iter()
+        .map(|chunk| chunk.to_link())
+        .collect::<Result<Vec<_>, _>>()?;
+    let body = Bytes(HashSeq::from(hashes.into_iter()).into_vec());
+    let mut memo = fixed_memo(&body, path)?;
+    memo.protected.set_header(CHUNKED, &true)?;
+    memo.sign(key)?;
+    Ok((memo, body))
+}
+
+/// Encode a memo block around the given protected header bytes
+fn encode_memo(protected: &[u8], unprotected: &UnprotectedHeaders) -> Result<Vec<u8>, Error> {
+    // Keys in canonical order: "type", "protected", "unprotected"
+    Ok([
+        vec![0xa3],
+        encode(&"type")?,
+        encode(&"szdt/memo")?,
+        encode(&"protected")?,
+        protected.to_vec(),
+        encode(&"unprotected")?,
+        encode(unprotected)?,
+    ]
+    .concat())
+}
+
+fn memo_vector(name: &str, description: &str, memo: &Memo) -> Result<Vector, Error> {
+    let input = encode(memo)?;
+    Ok(Vector {
+        name: name.to_string(),
+        description: description.to_string(),
+        expected: Expected::Memo {
+            hash: Hash::new(&input),
+            signing_hash: memo.protected.signing_hash()?,
+            src: memo.protected.src,
+            iss: memo.protected.iss.clone(),
+            cosigners: memo.verify_cosigs()?.signed,
+            witnesses: memo.verify_witnesses()?.signed,
+        },
+        input: Bytes(input),
+    })
+}
+
+fn link_vector<T: Serialize>(name: &str, description: &str, value: &T) -> Result<Vector, Error> {
+    let input = encode(value)?;
+    Ok(Vector {
+        name: name.to_string(),
+        description: description.to_string(),
+        expected: Expected::Link {
+            hash: value.to_link()?,
+        },
+        input: Bytes(input),
+    })
+}
+
+fn hashseq_vector(name: &str, description: &str, hashes: Vec<Hash>) -> Result<Vector, Error> {
+    let hashseq = HashSeq::from(hashes.clone().into_iter());
+    let input = encode(&hashseq)?;
+    Ok(Vector {
+        name: name.to_string(),
+        description: description.to_string(),
+        expected: Expected::HashSeq {
+            hash: Hash::new(&input),
+            root: Hash::from(hashseq),
+            hashes,
+        },
+        input: Bytes(input),
+    })
+}
+
+fn invalid_vector(
+    name: &str,
+    description: &str,
+    input: Vec<u8>,
+    error: &str,
+    rule: Option<&str>,
+    strict: bool,
+) -> Vector {
+    Vector {
+        name: name.to_string(),
+        description: description.to_string(),
+        input: Bytes(input),
+        expected: Expected::Invalid {
+            error: error.to_string(),
+            rule: rule.map(str::to_string),
+            strict,
+        },
+    }
+}
+
+/// Generate the test vector suite.
+/// Generation is deterministic: keys, timestamps and content are fixed.
+pub fn generate() -> Result<Suite, Error> {
+    let issuer = test_key(1);
+    let cosigner = test_key(2);
+    let witness = test_key(3);
+    let hello = Bytes(b"Hello World".to_vec());
+    let goodbye = Bytes(b"Goodbye World".to_vec());
+
+    let mut vectors = Vec::new();
+
+    // Memos
+    let unsigned = fixed_memo(&hello, "hello.txt")?;
+    vectors.push(memo_vector(
+        "memo/unsigned",
+        "Unsigned memo with common protected headers",
+        &unsigned,
+    )?);
+
+    let mut signed = unsigned.clone();
+    signed.sign(&issuer)?;
+    vectors.push(memo_vector(
+        "memo/signed",
+        "Memo signed by the key generated from 32 bytes of 0x01 entropy",
+        &signed,
+    )?);
+
+    let mut extended = fixed_memo(&hello, "hello.txt")?;
+    extended.protected.exp = Some(TIMESTAMP + 3600);
+    extended.protected.prev = Some(signed.protected.signing_hash()?);
+    extended.protected.set_header("x-version", &2)?;
+    extended
+        .unprotected
+        .set_header("cache-control", &"max-age=60")?;
+    extended.sign(&issuer)?;
+    vectors.push(memo_vector(
+        "memo/signed-extension-headers",
+        "Signed memo with exp, prev, and protected and unprotected extension headers",
+        &extended,
+    )?);
+
+    let mut attested = signed.clone();
+    attested.cosign(&cosigner)?;
+    attested.witness(&witness)?;
+    vectors.push(memo_vector(
+        "memo/cosigned-witnessed",
+        "Signed memo, co-signed by the 0x02 key and witnessed by the 0x03 key",
+        &attested,
+    )?);
+
+    let mut bad_cosig = signed.clone();
+    // Co-signature over the wrong hash
+    bad_cosig
+        .unprotected
+        .cosigs
+        .push(Attestation::sign(&cosigner, &Hash::new(b"other"))?);
+    vectors.push(memo_vector(
+        "memo/invalid-cosignature",
+        "Signed memo with a co-signature that does not verify. Co-signatures are unprotected, \
+         so the memo is still valid, and the co-signer is not listed.",
+        &bad_cosig,
+    )?);
+
+    // Links
+    vectors.push(link_vector(
+        "link/text",
+        "Content address of a text string",
+        &"Hello World",
+    )?);
+    vectors.push(link_vector(
+        "link/bytes",
+        "Content address of a byte string",
+        &hello,
+    )?);
+    vectors.push(link_vector(
+        "link/integers",
+        "Integers at encoding size boundaries",
+        &vec![
+            0i64, 23, 24, 255, 256, 65535, 65536, 4294967295, 4294967296, -1, -24, -25, -256, -257,
+        ],
+    )?);
+    let nested = cbor4ii::core::Value::Map(vec![
+        (
+            cbor4ii::core::Value::Text("bb".to_string()),
+            cbor4ii::core::Value::Bool(true),
+        ),
+        (
+            cbor4ii::core::Value::Text("a".to_string()),
+            cbor4ii::core::Value::Array(vec![
+                cbor4ii::core::Value::Null,
+                cbor4ii::core::Value::Bytes(hello.to_link()?.as_bytes().to_vec()),
+            ]),
+        ),
+    ]);
+    vectors.push(link_vector(
+        "link/map",
+        "Content address of a map. Keys are sorted by their encoded bytes.",
+        &nested,
+    )?);
+
+    // HashSeqs
+    vectors.push(hashseq_vector("hashseq/empty", "Empty HashSeq", vec![])?);
+    vectors.push(hashseq_vector(
+        "hashseq/three",
+        "HashSeq of three hashes",
+        vec![hello.to_link()?, goodbye.to_link()?, signed.to_link()?],
+    )?);
+
+    // Archives
+    let mut goodbye_memo = fixed_memo(&goodbye, "sub/goodbye.txt")?;
+    goodbye_memo.sign(&issuer)?;
+    vectors.push(Vector {
+        name: "archive/two-files".to_string(),
+        description: "Archive of two signed files, each memo followed by its body".to_string(),
+        input: Bytes(
+            [
+                encode(&signed)?,
+                encode(&hello)?,
+                encode(&goodbye_memo)?,
+                encode(&goodbye)?,
+            ]
+            .concat(),
+        ),
+        expected: Expected::Archive {
+            paths: vec!["hello.txt".to_string(), "sub/goodbye.txt".to_string()],
+            absent: vec![],
+            contents: vec![Some(Hash::new(&hello.0)), Some(Hash::new(&goodbye.0))],
+        },
+    });
+    vectors.push(Vector {
+        name: "archive/body-absent".to_string(),
+        description: "Archive where the first memo is not followed by its body".to_string(),
+        input: Bytes([encode(&signed)?, encode(&goodbye_memo)?, encode(&goodbye)?].concat()),
+        expected: Expected::Archive {
+            paths: vec!["hello.txt".to_string(), "sub/goodbye.txt".to_string()],
+            absent: vec!["hello.txt".to_string()],
+            contents: vec![None, Some(Hash::new(&goodbye.0))],
+        },
+    });
+
+    let mut copy_memo = fixed_memo(&hello, "copy/hello.txt")?;
+    copy_memo.sign(&issuer)?;
+    vectors.push(Vector {
+        name: "archive/dedup".to_string(),
+        description: "Archive of two files with the same content. The body is only written once, \
+                      after the first memo, and the second memo references it by hash."
+            .to_string(),
+        input: Bytes([encode(&signed)?, encode(&hello)?, encode(&copy_memo)?].concat()),
+        expected: Expected::Archive {
+            paths: vec!["hello.txt".to_string(), "copy/hello.txt".to_string()],
+            absent: vec![],
+            contents: vec![Some(Hash::new(&hello.0)), Some(Hash::new(&hello.0))],
+        },
+    });
+
+    let compressed = Bytes(HELLO_ZSTD.to_vec());
+    let mut compressed_memo = fixed_memo(&compressed, "hello.txt")?;
+    compressed_memo
+        .protected
+        .set_header(CONTENT_ENCODING, &"zstd")?;
+    compressed_memo.sign(&issuer)?;
+    vectors.push(Vector {
+        name: "archive/content-encoding".to_string(),
+        description: "Archive of a file with a zstd content-encoding. The memo src is the hash of \
+                      the encoded body, and the content is the decoded body."
+            .to_string(),
+        input: Bytes([encode(&compressed_memo)?, encode(&compressed)?].concat()),
+        expected: Expected::Archive {
+            paths: vec!["hello.txt".to_string()],
+            absent: vec![],
+            contents: vec![Some(Hash::new(&hello.0))],
+        },
+    });
+
+    let hello_chunk = Bytes(b"Hello ".to_vec());
+    let goodbye_chunk = Bytes(b"Goodbye ".to_vec());
+    let world_chunk = Bytes(b"World".to_vec());
+    let (hello_chunked, hello_chunks) =
+        chunked_memo(&[&hello_chunk, &world_chunk], "hello.txt", &issuer)?;
+    let (goodbye_chunked, goodbye_chunks) =
+        chunked_memo(&[&goodbye_chunk, &world_chunk], "goodbye.txt", &issuer)?;
+    vectors.push(Vector {
+        name: "archive/chunked".to_string(),
+        description: "Archive of two chunked files. Each memo is followed by its HashSeq body, \
+                      then by the chunks not already written. The files share a chunk."
+            .to_string(),
+        input: Bytes(
+            [
+                encode(&hello_chunked)?,
+                encode(&hello_chunks)?,
+                encode(&hello_chunk)?,
+                encode(&world_chunk)?,
+                encode(&goodbye_chunked)?,
+                encode(&goodbye_chunks)?,
+                encode(&goodbye_chunk)?,
+            ]
+            .concat(),
+        ),
+        expected: Expected::Archive {
+            paths: vec!["hello.txt".to_string(), "goodbye.txt".to_string()],
+            absent: vec![],
+            contents: vec![Some(Hash::new(&hello.0)), Some(Hash::new(&goodbye.0))],
+        },
+    });
+    vectors.push(Vector {
+        name: "archive/chunk-absent".to_string(),
+        description: "Archive of a chunked file where one of its chunks is absent".to_string(),
+        input: Bytes(
+            [
+                encode(&goodbye_chunked)?,
+                encode(&goodbye_chunks)?,
+                encode(&goodbye_chunk)?,
+            ]
+            .concat(),
+        ),
+        expected: Expected::Archive {
+            paths: vec!["goodbye.txt".to_string()],
+            absent: vec![],
+            contents: vec![None],
+        },
+    });
+
+    // Invalid inputs
+    vectors.push(invalid_vector(
+        "invalid/indefinite-length",
+        "Indefinite-length array [_ 1]",
+        vec![0x9f, 0x01, 0xff],
+        "non-canonical",
+        Some("indefinite-length"),
+        false,
+    ));
+    vectors.push(invalid_vector(
+        "invalid/non-shortest-integer",
+        "23 encoded with a one-byte argument",
+        vec![0x18, 0x17],
+        "non-canonical",
+        Some("non-shortest-integer"),
+        false,
+    ));
+    vectors.push(invalid_vector(
+        "invalid/non-shortest-float",
+        "1.5 encoded as a double-precision float. Only rejected in strict mode, since lenient \
+         readers accept floats as doubles.",
+        vec![0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0],
+        "non-canonical",
+        Some("non-shortest-float"),
+        true,
+    ));
+    vectors.push(invalid_vector(
+        "invalid/unsorted-keys",
+        "Map {\"b\": 1, \"a\": 2}",
+        vec![0xa2, 0x61, 0x62, 0x01, 0x61, 0x61, 0x02],
+        "non-canonical",
+        Some("unsorted-keys"),
+        false,
+    ));
+    vectors.push(invalid_vector(
+        "invalid/duplicate-key",
+        "Map {\"a\": 1, \"a\": 2}",
+        vec![0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02],
+        "non-canonical",
+        Some("duplicate-key"),
+        false,
+    ));
+    vectors.push(invalid_vector(
+        "invalid/truncated",
+        "Text string shorter than its declared length",
+        vec![0x65, 0x68, 0x65],
+        "decode",
+        None,
+        false,
+    ));
+
+    let mut null_header = to_value(&signed)?;
+    if let cbor4ii::core::Value::Map(entries) = &mut null_header {
+        for (key, value) in entries.iter_mut() {
+            if let (cbor4ii::core::Value::Text(key), cbor4ii::core::Value::Map(headers)) =
+                (key, value)
+                && key == "protected"
+            {
+                headers.push((
+                    cbor4ii::core::Value::Text("exp".to_string()),
+                    cbor4ii::core::Value::Null,
+                ));
+            }
+        }
+    }
+    vectors.push(invalid_vector(
+        "invalid/null-header",
+        "Memo with a null exp header. Absent optional headers must be omitted. \
+         Only rejected in strict mode, since lenient readers read null headers as absent.",
+        encode(&null_header)?,
+        "non-canonical",
+        Some("null-header"),
+        true,
+    ));
+
+    // Sign over protected headers with their keys in reverse order
+    let cbor4ii::core::Value::Map(headers) =
+        serde_cbor_core::from_slice(&signed.protected.signing_bytes()?)?
+    else {
+        unreachable!("Protected headers are a map");
+    };
+    let mut protected = vec![0xa0 | headers.len() as u8];
+    for (key, value) in headers.iter().rev() {
+        protected.extend(encode(key)?);
+        protected.extend(encode(value)?);
+    }
+    let mut unprotected = signed.unprotected.clone();
+    unprotected.sig = Some(Bytes(issuer.sign(Hash::new(&protected).as_bytes())?));
+    vectors.push(invalid_vector(
+        "invalid/memo-non-canonical-protected-headers",
+        "Signed memo whose protected headers have unsorted keys. The signature is over the \
+         unsorted bytes.",
+        encode_memo(&protected, &unprotected)?,
+        "non-canonical",
+        Some("unsorted-keys"),
+        false,
+    ));
+
+    let mut missing_src = to_value(&unsigned)?;
+    if let cbor4ii::core::Value::Map(entries) = &mut missing_src {
+        for (key, value) in entries.iter_mut() {
+            if let (cbor4ii::core::Value::Text(key), cbor4ii::core::Value::Map(headers)) =
+                (key, value)
+                && key == "protected"
+            {
+                headers.retain(|(key, _)| key != &cbor4ii::core::Value::Text("src".to_string()));
+            }
+        }
+    }
+    vectors.push(invalid_vector(
+        "invalid/memo-missing-src",
+        "Block claiming to be a memo, without the required src header",
+        encode(&missing_src)?,
+        "invalid-block",
+        None,
+        false,
+    ));
+
+    let mut tampered = signed.clone();
+    tampered.protected.path = Some("evil.txt".to_string());
+    vectors.push(invalid_vector(
+        "invalid/tampered-signature",
+        "Signed memo whose path was changed after signing",
+        encode(&tampered)?,
+        "invalid-signature",
+        None,
+        false,
+    ));
+
+    vectors.push(invalid_vector(
+        "invalid/body-mismatch",
+        "Archive where a memo is followed by a body that doesn't match its src",
+        [encode(&signed)?, encode(&goodbye)?].concat(),
+        "integrity",
+        None,
+        false,
+    ));
+
+    Ok(Suite {
+        version: VERSION,
+        vectors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE_CBOR: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../test-vectors/vectors.cbor"
+    );
+    const SUITE_JSON: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../test-vectors/vectors.json"
+    );
+
+    #[test]
+    fn test_generated_vectors_pass() {
+        let suite = generate().unwrap();
+        assert_eq!(suite.check(), vec![]);
+    }
+
+    #[test]
+    fn test_committed_vectors_pass() {
+        let bytes = std::fs::read(SUITE_CBOR).unwrap();
+        let suite = Suite::from_cbor(&bytes).unwrap();
+        assert_eq!(suite.version, VERSION);
+        assert_eq!(suite.check(), vec![]);
+    }
+
+    #[test]
+    fn test_committed_vectors_are_current() {
+        // If this fails, regenerate the suite (see module docs) and review
+        // the changes. Changes to existing vectors are breaking changes.
+        let suite = generate().unwrap();
+        assert_eq!(std::fs::read(SUITE_CBOR).unwrap(), suite.to_cbor().unwrap());
+        assert_eq!(
+            std::fs::read_to_string(SUITE_JSON).unwrap(),
+            suite.to_json().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_checker_detects_mismatch() {
+        let mut suite = generate().unwrap();
+        let hash = suite
+            .vectors
+            .iter_mut()
+            .find_map(|vector| match &mut vector.expected {
+                Expected::Link { hash } => Some(hash),
+                _ => None,
+            })
+            .unwrap();
+        *hash = Hash::new(b"wrong");
+        assert_eq!(suite.check().len(), 1);
+    }
+}
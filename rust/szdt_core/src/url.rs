@@ -0,0 +1,413 @@
+//! `szdt:` URLs for linking to content in archives.
+//!
+//! URLs take one of three forms:
+//!
+//! ```text
+//! szdt:<hash>                 Content with the given hash
+//! szdt:<hash>/<path>          File at path, with the given hash
+//! szdt:did:key:<key>/<path>   File at path, signed by the given issuer
+//! ```
+//!
+//! A hash may be the content address of a file body (the memo `src`), or of
+//! the memo itself. Hashes are printed as base32, but may be given in any
+//! form accepted by `Hash::parse`. Paths are percent-encoded, and are matched
+//! against the memo `path` header, ignoring any leading `/`.
+use crate::dag::{DagEntry, DagIndex};
+use crate::did::DidKey;
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::memo::Memo;
+use crate::validation::ValidationOptions;
+use std::str::FromStr;
+
+/// URL scheme for SZDT URLs
+pub const SCHEME: &str = "szdt:";
+
+const DID_KEY_PREFIX: &str = "did:key:";
+
+/// A `szdt:` URL.
+/// URLs name either a hash or an issuer, never both, so fields are only set
+/// through the constructors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SzdtUrl {
+    /// Issuer that must have signed the memo
+    iss: Option<DidKey>,
+    /// Hash of the file body or memo
+    hash: Option<Hash>,
+    /// Path of the file within the archive, without a leading `/`
+    path: Option<String>,
+}
+
+impl SzdtUrl {
+    /// URL for content with the given hash
+    pub fn for_hash(hash: Hash) -> Self {
+        Self {
+            iss: None,
+            hash: Some(hash),
+            path: None,
+        }
+    }
+
+    /// URL for a file at a path, with the given hash
+    pub fn for_hash_path(hash: Hash, path: &str) -> Self {
+        Self {
+            iss: None,
+            hash: Some(hash),
+            path: Some(normalize_path(path).to_string()),
+        }
+    }
+
+    /// URL for a file at a path, signed by the given issuer
+    pub fn for_iss_path(iss: DidKey, path: &str) -> Self {
+        Self {
+            iss: Some(iss),
+            hash: None,
+            path: Some(normalize_path(path).to_string()),
+        }
+    }
+
+    /// Issuer that must have signed the memo, if any
+    pub fn iss(&self) -> Option<&DidKey> {
+        self.iss.as_ref()
+    }
+
+    /// Hash of the file body or memo, if any
+    pub fn hash(&self) -> Option<&Hash> {
+        self.hash.as_ref()
+    }
+
+    /// Path of the file within the archive, if any
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Parse a `szdt:` URL
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let rest = input
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| Error::InvalidUrl(format!("URL must start with {SCHEME}")))?;
+        let (id, path) = match rest.split_once('/') {
+            Some((id, path)) => (id, Some(normalize_path(&percent_decode(path)?).to_string())),
+            None => (rest, None),
+        };
+        if path.as_deref() == Some("") {
+            return Err(Error::InvalidUrl("Path must not be empty".to_string()));
+        }
+        if id.starts_with(DID_KEY_PREFIX) {
+            let iss = DidKey::parse(id)?;
+            let Some(path) = path else {
+                return Err(Error::InvalidUrl(
+                    "URLs with an issuer must include a path".to_string(),
+                ));
+            };
+            return Ok(Self {
+                iss: Some(iss),
+                hash: None,
+                path: Some(path),
+            });
+        }
+        let hash = Hash::parse(id)?;
+        Ok(Self {
+            iss: None,
+            hash: Some(hash),
+            path,
+        })
+    }
+
+    /// Does the memo match this URL? `hash` is the content address of the
+    /// memo itself, since hash URLs may point at a memo or at its body.
+    pub fn matches(&self, hash: &Hash, memo: &Memo) -> bool {
+        if let Some(url_hash) = &self.hash
+            && &memo.protected.src != url_hash
+            && hash != url_hash
+        {
+            return false;
+        }
+        if let Some(path) = &self.path
+            && memo.protected.path.as_deref().map(normalize_path) != Some(path.as_str())
+        {
+            return false;
+        }
+        if let Some(iss) = &self.iss
+            && memo.protected.iss.as_ref() != Some(iss)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Resolve the URL against the memos in a sequence, returning the latest
+    /// matching memo that is valid according to `options`.
+    ///
+    /// If more than one memo matches, for example when a sequence contains
+    /// several versions of a file, memos are validated before picking one,
+    /// so an expired or badly signed newer version doesn't hide a valid older
+    /// one. Memos with bodies are preferred over those without (for example,
+    /// redacted versions), then the most recently issued is returned. The
+    /// body, if present, always matches the memo checksum (see
+    /// `DagIndex::resolve`).
+    pub fn resolve<'a>(
+        &self,
+        index: &'a DagIndex,
+        options: &ValidationOptions,
+    ) -> Result<Option<DagEntry<'a>>, Error> {
+        let entry = index
+            .entries()?
+            .into_iter()
+            .filter(|entry| {
+                self.matches(&entry.hash, &entry.memo) && entry.memo.validate(options).is_ok()
+            })
+            // max_by_key returns the last of equal elements, so later memos
+            // win ties.
+            .max_by_key(|entry| (entry.body.is_some(), entry.memo.protected.iat));
+        Ok(entry)
+    }
+}
+
+impl std::fmt::Display for SzdtUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{SCHEME}")?;
+        match (&self.iss, &self.hash) {
+            (Some(iss), _) => write!(f, "{iss}")?,
+            (None, Some(hash)) => write!(f, "{hash}")?,
+            (None, None) => {}
+        }
+        if let Some(path) = &self.path {
+            write!(f, "/{}", percent_encode(path))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SzdtUrl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Strip the leading `/` from archive paths, which is optional in memos
+fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+/// Characters allowed unescaped in URL paths (RFC 3986 `pchar`, plus `/`)
+fn is_path_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte)
+}
+
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if is_path_char(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(path: &str) -> Result<String, Error> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // `from_str_radix` accepts a leading sign, so check for two hex
+            // digits first
+            let byte = path
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::InvalidUrl(format!("Invalid percent-encoding in {path}")))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|_| Error::InvalidUrl(format!("Path is not valid UTF-8: {path}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::cbor_seq::{CborSeqReader, CborSeqWriter};
+    use crate::ed25519_key_material::Ed25519KeyMaterial;
+    use crate::link::ToLink;
+    use crate::memo::Memo;
+    use std::io::Cursor;
+
+    fn key_material(seed: u8) -> Ed25519KeyMaterial {
+        Ed25519KeyMaterial::generate_from_entropy(&[seed; 32]).unwrap()
+    }
+
+    fn signed_memo(body: &Bytes, path: &str, iat: u64, seed: u8) -> Memo {
+        let mut memo = Memo::for_body(body).unwrap();
+        memo.protected.path = Some(path.to_string());
+        memo.protected.iat = iat;
+        memo.protected.nbf = None;
+        memo.sign(&key_material(seed)).unwrap();
+        memo
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let hash = Hash::new(b"Hello");
+        let did = key_material(1).did();
+
+        let url = SzdtUrl::for_hash(hash);
+        assert_eq!(url.to_string(), format!("szdt:{hash}"));
+        assert_eq!(url.to_string().parse::<SzdtUrl>().unwrap(), url);
+
+        let url = SzdtUrl::for_hash_path(hash, "/docs/a b.pdf");
+        assert_eq!(url.to_string(), format!("szdt:{hash}/docs/a%20b.pdf"));
+        assert_eq!(url.to_string().parse::<SzdtUrl>().unwrap(), url);
+
+        let url = SzdtUrl::for_iss_path(did.clone(), "docs/a.pdf");
+        assert_eq!(url.to_string(), format!("szdt:{did}/docs/a.pdf"));
+        assert_eq!(url.to_string().parse::<SzdtUrl>().unwrap(), url);
+
+        // Hashes may be given in other encodings
+        let url = SzdtUrl::parse(&format!("szdt:{}", hash.to_cid())).unwrap();
+        assert_eq!(url.hash(), Some(&hash));
+
+        // Paths are normalized, as with the constructors
+        let url = SzdtUrl::parse(&format!("szdt:{hash}//docs/a.pdf")).unwrap();
+        assert_eq!(url, SzdtUrl::for_hash_path(hash, "docs/a.pdf"));
+        assert_eq!(url.path(), Some("docs/a.pdf"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let did = key_material(1).did();
+        assert!(SzdtUrl::parse("https://example.com").is_err());
+        assert!(SzdtUrl::parse("szdt:").is_err());
+        assert!(SzdtUrl::parse("szdt:nothash/a.txt").is_err());
+        // Issuer URLs need a path
+        assert!(SzdtUrl::parse(&format!("szdt:{did}")).is_err());
+        let hash = Hash::new(b"Hello");
+        assert!(SzdtUrl::parse(&format!("szdt:{hash}/a%zz")).is_err());
+        assert!(SzdtUrl::parse(&format!("szdt:{hash}/a%+1")).is_err());
+        assert!(SzdtUrl::parse(&format!("szdt:{hash}/a%2")).is_err());
+        assert!(SzdtUrl::parse(&format!("szdt:{hash}//")).is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let v1 = Bytes(b"v1".to_vec());
+        let v2 = Bytes(b"v2".to_vec());
+        let other = Bytes(b"other".to_vec());
+        let memo_v1 = signed_memo(&v1, "docs/a.txt", 1000, 1);
+        let memo_v2 = signed_memo(&v2, "docs/a.txt", 2000, 1);
+        // Same path, different issuer
+        let memo_other = signed_memo(&other, "docs/a.txt", 3000, 2);
+
+        let mut writer = CborSeqWriter::new(Vec::new());
+        for (memo, body) in [(&memo_v1, &v1), (&memo_v2, &v2), (&memo_other, &other)] {
+            writer.write_block(memo).unwrap();
+            writer.write_block(body).unwrap();
+        }
+        let data = writer.into_inner();
+        let index = DagIndex::read(&mut CborSeqReader::new(Cursor::new(data))).unwrap();
+
+        // Issuer and path resolves to the latest version by that issuer
+        let url = SzdtUrl::for_iss_path(key_material(1).did(), "/docs/a.txt");
+        let entry = url
+            .resolve(&index, &ValidationOptions::at(5000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.memo, memo_v2);
+
+        // Hash resolves a specific version, by body or memo hash
+        let url = SzdtUrl::for_hash(v1.to_link().unwrap());
+        let entry = url
+            .resolve(&index, &ValidationOptions::at(5000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.memo, memo_v1);
+        let url = SzdtUrl::for_hash(memo_v1.to_link().unwrap());
+        assert_eq!(
+            url.resolve(&index, &ValidationOptions::at(5000))
+                .unwrap()
+                .unwrap()
+                .memo,
+            memo_v1
+        );
+
+        let url = SzdtUrl::for_hash_path(v1.to_link().unwrap(), "other.txt");
+        assert!(
+            url.resolve(&index, &ValidationOptions::at(5000))
+                .unwrap()
+                .is_none()
+        );
+
+        let url = SzdtUrl::for_iss_path(key_material(3).did(), "docs/a.txt");
+        assert!(
+            url.resolve(&index, &ValidationOptions::at(5000))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_resolve_skips_invalid_signatures() {
+        let body = Bytes(b"Hello".to_vec());
+        let mut memo = signed_memo(&body, "a.txt", 1000, 1);
+        // Tamper with the memo after signing
+        memo.protected.iat = 2000;
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&memo).unwrap();
+        writer.write_block(&body).unwrap();
+        let data = writer.into_inner();
+        let index = DagIndex::read(&mut CborSeqReader::new(Cursor::new(data))).unwrap();
+
+        let url = SzdtUrl::for_iss_path(key_material(1).did(), "a.txt");
+        assert!(
+            url.resolve(&index, &ValidationOptions::at(5000))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_resolve_skips_invalid_newer_versions() {
+        let v1 = Bytes(b"v1".to_vec());
+        let v2 = Bytes(b"v2".to_vec());
+        let v3 = Bytes(b"v3".to_vec());
+        let memo_v1 = signed_memo(&v1, "a.txt", 1000, 1);
+        // Expired
+        let mut memo_v2 = signed_memo(&v2, "a.txt", 2000, 1);
+        memo_v2.protected.exp = Some(3000);
+        memo_v2.sign(&key_material(1)).unwrap();
+        // Redacted (body absent)
+        let memo_v3 = signed_memo(&v3, "a.txt", 3000, 1);
+
+        let mut writer = CborSeqWriter::new(Vec::new());
+        writer.write_block(&memo_v1).unwrap();
+        writer.write_block(&v1).unwrap();
+        writer.write_block(&memo_v2).unwrap();
+        writer.write_block(&v2).unwrap();
+        writer.write_block(&memo_v3).unwrap();
+        let data = writer.into_inner();
+        let index = DagIndex::read(&mut CborSeqReader::new(Cursor::new(data))).unwrap();
+
+        let url = SzdtUrl::for_iss_path(key_material(1).did(), "a.txt");
+        let entry = url
+            .resolve(&index, &ValidationOptions::at(5000))
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.memo, memo_v1);
+        // Before v2 expired, it was the latest version with a body
+        let entry = url
+            .resolve(&index, &ValidationOptions::at(2500))
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.memo, memo_v2);
+    }
+}
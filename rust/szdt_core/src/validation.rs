@@ -0,0 +1,641 @@
+use crate::did::DidKey;
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::memo::Memo;
+use crate::time::now;
+use crate::witness::{AttestationReport, PolicyResult, WitnessPolicy};
+use cbor4ii::core::Value;
+use serde::{Deserialize, Serialize};
+
+/// Options for validating memos
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationOptions {
+    /// Time to validate against (UNIX timestamp, seconds).
+    /// Defaults to the current time. Set this to verify a memo as of some
+    /// point in history.
+    pub time: Option<u64>,
+    /// Allowed clock skew (seconds).
+    /// Applied as a slush factor when checking `nbf`, `exp` and `iat`.
+    pub clock_skew: u64,
+    /// Maximum age of a memo, measured from `iat` (seconds)
+    pub max_age: Option<u64>,
+    /// Reject memos with an `iat` in the future
+    pub iat_not_in_future: bool,
+    /// Protected headers that must be present
+    pub required_headers: Vec<String>,
+    /// Co-signers that must have validly co-signed. Other co-signatures that
+    /// don't verify are reported, but don't make a memo invalid, since anyone
+    /// relaying a memo can add them.
+    pub required_cosigners: Vec<DidKey>,
+    /// Require witness signatures from k of n trusted witnesses
+    pub witness_policy: Option<WitnessPolicy>,
+}
+
+impl ValidationOptions {
+    /// Create options for validating at the given time
+    pub fn at(time: u64) -> Self {
+        Self {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+
+    /// Get the time to validate against
+    pub fn time(&self) -> u64 {
+        self.time.unwrap_or_else(now)
+    }
+
+    /// Check the `nbf` header of a memo against the given time
+    pub fn check_nbf(&self, memo: &Memo, time: u64) -> TimeCheck {
+        let timestamp = memo.protected.nbf;
+        let status = match timestamp {
+            None => TimeStatus::Absent,
+            Some(nbf) if nbf > time.saturating_add(self.clock_skew) => TimeStatus::Invalid,
+            Some(_) => TimeStatus::Valid,
+        };
+        TimeCheck { status, timestamp }
+    }
+
+    /// Check the `exp` header of a memo against the given time
+    pub fn check_exp(&self, memo: &Memo, time: u64) -> TimeCheck {
+        let timestamp = memo.protected.exp;
+        let status = match timestamp {
+            None => TimeStatus::Absent,
+            Some(exp) if exp.saturating_add(self.clock_skew) < time => TimeStatus::Invalid,
+            Some(_) => TimeStatus::Valid,
+        };
+        TimeCheck { status, timestamp }
+    }
+
+    /// Check that the `iat` of a memo is not in the future, if required
+    pub fn check_iat(&self, memo: &Memo, time: u64) -> TimeCheck {
+        let iat = memo.protected.iat;
+        let status = if !self.iat_not_in_future {
+            TimeStatus::Absent
+        } else if iat > time.saturating_add(self.clock_skew) {
+            TimeStatus::Invalid
+        } else {
+            TimeStatus::Valid
+        };
+        TimeCheck {
+            status,
+            timestamp: Some(iat),
+        }
+    }
+
+    /// Check that a memo is not older than the max age, if given.
+    /// The timestamp of the check is the time at which the memo becomes too old.
+    pub fn check_max_age(&self, memo: &Memo, time: u64) -> TimeCheck {
+        let Some(max_age) = self.max_age else {
+            return TimeCheck {
+                status: TimeStatus::Absent,
+                timestamp: None,
+            };
+        };
+        let stale_at = memo.protected.iat.saturating_add(max_age);
+        let status = if stale_at.saturating_add(self.clock_skew) < time {
+            TimeStatus::Invalid
+        } else {
+            TimeStatus::Valid
+        };
+        TimeCheck {
+            status,
+            timestamp: Some(stale_at),
+        }
+    }
+
+    /// Get the required headers that are missing from a memo
+    pub fn missing_headers(&self, memo: &Memo) -> Vec<String> {
+        self.required_headers
+            .iter()
+            .filter(|key| !memo.protected.has_header(key))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the required co-signers that did not validly co-sign
+    pub fn missing_cosigners(&self, cosigs: &AttestationReport) -> Vec<DidKey> {
+        self.required_cosigners
+            .iter()
+            .filter(|did| !cosigs.has_signed(did))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Status of the memo signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignatureStatus {
+    /// Signature is valid for the issuer
+    Valid,
+    /// Signature did not verify
+    Invalid,
+    /// Memo has no signature
+    Unsigned,
+    /// Memo has no issuer to verify the signature against
+    IssuerMissing,
+}
+
+/// Status of a timestamp check (`nbf` or `exp`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeStatus {
+    /// Timestamp is within bounds
+    Valid,
+    /// Timestamp is out of bounds
+    Invalid,
+    /// Header is not present, or check is not enabled
+    Absent,
+}
+
+/// Result of checking a timestamp header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimeCheck {
+    pub status: TimeStatus,
+    /// The timestamp in the header (if any)
+    pub timestamp: Option<u64>,
+}
+
+/// Status of the body checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChecksumStatus {
+    /// Body hash matches `src`
+    Valid,
+    /// Body hash does not match `src`
+    Invalid,
+    /// No body was given to check
+    Unchecked,
+}
+
+/// Spec-conformance issues that do not make a memo invalid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Warning {
+    /// Optional header is serialized as null, rather than omitted
+    NullHeader { header: String },
+    /// Custom header key is not lowercase
+    NonLowercaseHeader { header: String },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::NullHeader { header } => {
+                write!(
+                    f,
+                    "Header {header} is null. Optional headers should be omitted."
+                )
+            }
+            Warning::NonLowercaseHeader { header } => {
+                write!(f, "Header {header} should be lowercase")
+            }
+        }
+    }
+}
+
+/// A report of every validation check performed on a memo.
+/// Unlike `Memo::validate`, which returns the first failure, the report
+/// collects the result of all checks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// Status of the signature
+    pub signature: SignatureStatus,
+    /// Reason the signature did not verify (if any)
+    pub signature_error: Option<String>,
+    /// Issuer DID (if present)
+    pub iss: Option<DidKey>,
+    /// The time used to check `nbf` and `exp` (UNIX timestamp, seconds)
+    pub time: u64,
+    /// Not-before check
+    pub nbf: TimeCheck,
+    /// Expiration check
+    pub exp: TimeCheck,
+    /// Check that `iat` is not in the future
+    pub iat: TimeCheck,
+    /// Max age check
+    pub max_age: TimeCheck,
+    /// Required headers that are missing
+    pub missing_headers: Vec<String>,
+    /// Status of the body checksum
+    pub checksum: ChecksumStatus,
+    /// Co-signers that validly signed, and co-signatures that didn't verify
+    pub cosigs: AttestationReport,
+    /// Required co-signers that did not validly co-sign
+    pub missing_cosigners: Vec<DidKey>,
+    /// Witnesses that validly signed, and witness signatures that didn't verify
+    pub witnesses: AttestationReport,
+    /// Result of the witness policy (if one was given)
+    pub witness_policy: Option<PolicyResult>,
+    /// Spec-conformance warnings
+    pub warnings: Vec<Warning>,
+}
+
+impl ValidationReport {
+    /// Validate a memo, reporting the result of every check.
+    /// If `body_hash` is given, it is checked against the `src` of the memo.
+    pub fn for_memo(memo: &Memo, options: &ValidationOptions, body_hash: Option<&Hash>) -> Self {
+        let time = options.time();
+
+        let (signature, signature_error) = match memo.verify() {
+            Ok(()) => (SignatureStatus::Valid, None),
+            Err(Error::MemoUnsigned) => (SignatureStatus::Unsigned, None),
+            Err(Error::MemoIssMissing) => (SignatureStatus::IssuerMissing, None),
+            Err(err) => (SignatureStatus::Invalid, Some(err.to_string())),
+        };
+
+        let checksum = match body_hash {
+            None => ChecksumStatus::Unchecked,
+            Some(body_hash) if memo.checksum(body_hash).is_ok() => ChecksumStatus::Valid,
+            Some(_) => ChecksumStatus::Invalid,
+        };
+
+        let cosigs = memo.verify_cosigs().unwrap_or_default();
+        let witnesses = memo.verify_witnesses().unwrap_or_default();
+        let witness_policy = options
+            .witness_policy
+            .as_ref()
+            .map(|policy| policy.evaluate(&witnesses));
+
+        Self {
+            signature,
+            signature_error,
+            iss: memo.protected.iss.clone(),
+            time,
+            nbf: options.check_nbf(memo, time),
+            exp: options.check_exp(memo, time),
+            iat: options.check_iat(memo, time),
+            max_age: options.check_max_age(memo, time),
+            missing_headers: options.missing_headers(memo),
+            checksum,
+            missing_cosigners: options.missing_cosigners(&cosigs),
+            cosigs,
+            witnesses,
+            witness_policy,
+            warnings: conformance_warnings(memo),
+        }
+    }
+
+    /// Did every check pass?
+    /// Unchecked bodies and warnings do not make a report invalid.
+    /// Invalid co-signatures only make a report invalid if they are from
+    /// required co-signers, and invalid witness signatures only if the witness
+    /// policy is not satisfied.
+    pub fn is_valid(&self) -> bool {
+        self.signature == SignatureStatus::Valid
+            && self.nbf.status != TimeStatus::Invalid
+            && self.exp.status != TimeStatus::Invalid
+            && self.iat.status != TimeStatus::Invalid
+            && self.max_age.status != TimeStatus::Invalid
+            && self.missing_headers.is_empty()
+            && self.checksum != ChecksumStatus::Invalid
+            && self.missing_cosigners.is_empty()
+            && self
+                .witness_policy
+                .as_ref()
+                .is_none_or(|result| result.satisfied)
+    }
+}
+
+/// Check memo headers for spec-conformance issues
+fn conformance_warnings(memo: &Memo) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = null_protected_headers(memo)
+        .into_iter()
+        .map(|header| Warning::NullHeader { header })
+        .collect();
+
+    let mut extra: Vec<(&String, &Value)> = memo
+        .protected
+        .extra
+        .iter()
+        .chain(memo.unprotected.extra.iter())
+        .collect();
+    extra.sort_by_key(|(key, _)| *key);
+
+    for (key, value) in extra {
+        if *value == Value::Null {
+            warnings.push(Warning::NullHeader {
+                header: key.clone(),
+            });
+        }
+        if key.chars().any(|c| c.is_uppercase()) {
+            warnings.push(Warning::NonLowercaseHeader {
+                header: key.clone(),
+            });
+        }
+    }
+
+    warnings
+}
+
+/// Get the reserved protected headers that were decoded as null, sorted by key.
+/// Null optional headers decode to `None`, so they can only be found in the
+/// bytes the headers were decoded from. Null extension headers are kept in
+/// `extra`, and checked from there.
+fn null_protected_headers(memo: &Memo) -> Vec<String> {
+    let Some(bytes) = memo.protected.raw_bytes() else {
+        return Vec::new();
+    };
+    let Ok(Value::Map(entries)) = serde_cbor_core::from_slice::<Value>(bytes) else {
+        return Vec::new();
+    };
+    let mut headers: Vec<String> = entries
+        .into_iter()
+        .filter_map(|(key, value)| match (key, value) {
+            (Value::Text(key), Value::Null) if !memo.protected.extra.contains_key(&key) => {
+                Some(key)
+            }
+            _ => None,
+        })
+        .collect();
+    headers.sort();
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ed25519_key_material::Ed25519KeyMaterial;
+    use crate::link::ToLink;
+
+    fn create_test_key() -> Ed25519KeyMaterial {
+        Ed25519KeyMaterial::generate_from_entropy(&[0u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_witness_policy() {
+        let witness_a = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let witness_b = Ed25519KeyMaterial::generate_from_entropy(&[2u8; 32]).unwrap();
+        let mut memo = Memo::for_body("Hello World").unwrap();
+        memo.sign(&create_test_key()).unwrap();
+        memo.witness(&witness_a).unwrap();
+
+        let one_of_two = ValidationOptions {
+            witness_policy: Some(
+                WitnessPolicy::new(1, vec![witness_a.did(), witness_b.did()]).unwrap(),
+            ),
+            ..Default::default()
+        };
+        let report = memo.validation_report(&one_of_two, None);
+        assert!(report.is_valid());
+        assert_eq!(report.witnesses.signed, vec![witness_a.did()]);
+        let result = report.witness_policy.unwrap();
+        assert!(result.satisfied);
+        assert_eq!(result.missing, vec![witness_b.did()]);
+        memo.validate(&one_of_two).unwrap();
+
+        let two_of_two = ValidationOptions {
+            witness_policy: Some(
+                WitnessPolicy::new(2, vec![witness_a.did(), witness_b.did()]).unwrap(),
+            ),
+            ..Default::default()
+        };
+        let report = memo.validation_report(&two_of_two, None);
+        assert!(!report.is_valid());
+        assert!(matches!(
+            memo.validate(&two_of_two),
+            Err(Error::WitnessPolicyError(_))
+        ));
+
+        // Satisfied once the second witness signs
+        memo.witness(&witness_b).unwrap();
+        memo.validate(&two_of_two).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_cosig() {
+        let cosigner = Ed25519KeyMaterial::generate_from_entropy(&[1u8; 32]).unwrap();
+        let mut memo = Memo::for_body("Hello World").unwrap();
+        // Co-signed before the protected headers were final
+        memo.cosign(&cosigner).unwrap();
+        memo.sign(&create_test_key()).unwrap();
+
+        // Anyone can add an invalid co-signature, so it is reported, but
+        // doesn't make the memo invalid
+        let report = memo.validation_report(&ValidationOptions::default(), None);
+        assert_eq!(report.signature, SignatureStatus::Valid);
+        assert_eq!(report.cosigs.invalid.len(), 1);
+        assert!(report.is_valid());
+        memo.validate(&ValidationOptions::default()).unwrap();
+
+        // Unless the co-signer is required
+        let options = ValidationOptions {
+            required_cosigners: vec![cosigner.did()],
+            ..Default::default()
+        };
+        let report = memo.validation_report(&options, None);
+        assert_eq!(report.missing_cosigners, vec![cosigner.did()]);
+        assert!(!report.is_valid());
+        assert!(matches!(
+            memo.validate(&options),
+            Err(Error::CosigInvalid(_))
+        ));
+
+        memo.cosign(&cosigner).unwrap();
+        memo.validate(&options).unwrap();
+    }
+
+    #[test]
+    fn test_report_valid_memo() {
+        let body = b"Hello World".to_vec();
+        let mut memo = Memo::for_body(&body).unwrap();
+        memo.protected.iss_nickname = Some("alice".to_string());
+        memo.sign(&create_test_key()).unwrap();
+
+        let body_hash = body.to_link().unwrap();
+        let report = memo.validation_report(&ValidationOptions::default(), Some(&body_hash));
+
+        assert!(report.is_valid());
+        assert_eq!(report.signature, SignatureStatus::Valid);
+        assert_eq!(report.iss, Some(create_test_key().did()));
+        assert_eq!(report.nbf.status, TimeStatus::Valid);
+        assert_eq!(report.exp.status, TimeStatus::Absent);
+        assert_eq!(report.checksum, ChecksumStatus::Valid);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_report_collects_every_failure() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected.nbf = Some(2000);
+        memo.protected.exp = Some(1000);
+
+        let report =
+            memo.validation_report(&ValidationOptions::at(1500), Some(&Hash::new(b"wrong")));
+
+        assert!(!report.is_valid());
+        assert_eq!(report.signature, SignatureStatus::IssuerMissing);
+        assert_eq!(report.time, 1500);
+        assert_eq!(report.nbf.status, TimeStatus::Invalid);
+        assert_eq!(report.nbf.timestamp, Some(2000));
+        assert_eq!(report.exp.status, TimeStatus::Invalid);
+        assert_eq!(report.checksum, ChecksumStatus::Invalid);
+    }
+
+    #[test]
+    fn test_report_invalid_signature() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.sign(&create_test_key()).unwrap();
+        // Tamper with protected headers after signing
+        memo.protected.path = Some("tampered".to_string());
+
+        let report = memo.validation_report(&ValidationOptions::default(), None);
+        assert_eq!(report.signature, SignatureStatus::Invalid);
+        assert!(report.signature_error.is_some());
+        assert_eq!(report.checksum, ChecksumStatus::Unchecked);
+    }
+
+    #[test]
+    fn test_report_warnings() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected
+            .extra
+            .insert("X-Custom".to_string(), Value::Null);
+        memo.sign(&create_test_key()).unwrap();
+
+        let report = memo.validation_report(&ValidationOptions::default(), None);
+        // Warnings do not make the memo invalid
+        assert!(report.is_valid());
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::NullHeader {
+                    header: "X-Custom".to_string()
+                },
+                Warning::NonLowercaseHeader {
+                    header: "X-Custom".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_warns_on_null_optional_headers() {
+        // Encode the memo as older writers did, with null optional headers
+        let key = create_test_key();
+        let text = |value: &str| Value::Text(value.to_string());
+        let protected = Value::Map(vec![
+            (text("iss"), text(&key.did().to_string())),
+            (text("iss-nickname"), Value::Null),
+            (text("iat"), Value::Integer(1)),
+            (text("nbf"), Value::Null),
+            (
+                text("src"),
+                Value::Bytes(Hash::new(b"body").as_bytes().to_vec()),
+            ),
+        ]);
+        let protected_bytes = serde_cbor_core::to_vec(&protected).unwrap();
+        let sig = key.sign(Hash::new(&protected_bytes).as_bytes()).unwrap();
+        let memo = Value::Map(vec![
+            (text("type"), text("szdt/memo")),
+            (
+                text("unprotected"),
+                Value::Map(vec![(text("sig"), Value::Bytes(sig))]),
+            ),
+            (text("protected"), protected),
+        ]);
+        let memo: Memo =
+            serde_cbor_core::from_slice(&serde_cbor_core::to_vec(&memo).unwrap()).unwrap();
+        assert!(memo.protected.nbf.is_none());
+
+        let report = memo.validation_report(&ValidationOptions::default(), None);
+        assert!(report.is_valid());
+        assert_eq!(
+            report.warnings,
+            vec![
+                Warning::NullHeader {
+                    header: "iss-nickname".to_string()
+                },
+                Warning::NullHeader {
+                    header: "nbf".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_options_clock_skew() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected.nbf = Some(1001);
+        memo.protected.exp = Some(999);
+        memo.sign(&create_test_key()).unwrap();
+
+        let strict = ValidationOptions::at(1000);
+        assert!(memo.validate(&strict).is_err());
+
+        let skewed = ValidationOptions {
+            clock_skew: 1,
+            ..ValidationOptions::at(1000)
+        };
+        memo.validate(&skewed).unwrap();
+    }
+
+    #[test]
+    fn test_options_iat_not_in_future() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected.iat = 2000;
+        memo.protected.nbf = None;
+        memo.sign(&create_test_key()).unwrap();
+
+        // Not checked by default
+        memo.validate(&ValidationOptions::at(1000)).unwrap();
+
+        let options = ValidationOptions {
+            iat_not_in_future: true,
+            ..ValidationOptions::at(1000)
+        };
+        assert!(matches!(
+            memo.validate(&options),
+            Err(Error::MemoIatError(_))
+        ));
+        let report = memo.validation_report(&options, None);
+        assert_eq!(report.iat.status, TimeStatus::Invalid);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_options_max_age() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected.iat = 1000;
+        memo.protected.nbf = None;
+        memo.sign(&create_test_key()).unwrap();
+
+        let options = ValidationOptions {
+            max_age: Some(100),
+            ..ValidationOptions::at(1100)
+        };
+        memo.validate(&options).unwrap();
+
+        let options = ValidationOptions {
+            max_age: Some(100),
+            ..ValidationOptions::at(1101)
+        };
+        assert!(matches!(
+            memo.validate(&options),
+            Err(Error::MemoMaxAgeError(_))
+        ));
+        let report = memo.validation_report(&options, None);
+        assert_eq!(report.max_age.status, TimeStatus::Invalid);
+        assert_eq!(report.max_age.timestamp, Some(1100));
+    }
+
+    #[test]
+    fn test_options_required_headers() {
+        let mut memo = Memo::for_body(b"Hello World".to_vec()).unwrap();
+        memo.protected.path = Some("hello.txt".to_string());
+        memo.sign(&create_test_key()).unwrap();
+
+        let options = ValidationOptions {
+            required_headers: vec!["path".to_string(), "content-type".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            memo.validate(&options),
+            Err(Error::MemoHeaderMissing(_))
+        ));
+        let report = memo.validation_report(&options, None);
+        assert_eq!(report.missing_headers, vec!["content-type".to_string()]);
+    }
+}
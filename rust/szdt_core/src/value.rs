@@ -1,11 +1,242 @@
+//! Decoding of typed blocks.
+//!
+//! Blocks may declare their type with a `type` field (e.g. `"szdt/memo"`).
+//! The `BlockDecoder` dispatches on this field, rather than guessing a block's
+//! type from its shape, so a block is only decoded as a memo if it claims to be
+//! one, and a block that claims to be a memo but fails to parse is an error.
+use crate::cbor_seq::CborSeqReader;
+use crate::error::Error;
 use crate::memo::Memo;
-use serde::{Deserialize, Serialize};
+use serde::{Serialize, Serializer, de::DeserializeOwned};
+use std::collections::HashMap;
+use std::io::BufRead;
 
-/// Represents a value in the SZDT format.
-/// Either Memo or a CBOR Value.
-#[derive(Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum Value {
+/// Block type of memos
+pub const MEMO_TYPE: &str = "szdt/memo";
+
+/// Namespace reserved for block types defined by SZDT
+pub const SZDT_TYPE_PREFIX: &str = "szdt/";
+
+/// A decoded block
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    /// A `szdt/memo` block
     Memo(Box<Memo>),
+    /// A block with an app-defined type registered with the decoder.
+    /// The value has been checked against the registered type.
+    Typed(String, cbor4ii::core::Value),
+    /// Any other block, e.g. bytes, or maps with an unrecognized type
     Value(cbor4ii::core::Value),
 }
+
+impl Block {
+    /// The type declared by the block, if any
+    pub fn block_type(&self) -> Option<&str> {
+        match self {
+            Block::Memo(_) => Some(MEMO_TYPE),
+            Block::Typed(block_type, _) => Some(block_type),
+            Block::Value(value) => block_type(value),
+        }
+    }
+}
+
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Block::Memo(memo) => memo.serialize(serializer),
+            Block::Typed(_, value) => value.serialize(serializer),
+            Block::Value(value) => value.serialize(serializer),
+        }
+    }
+}
+
+/// Get the `type` field of a map block, if any
+pub fn block_type(value: &cbor4ii::core::Value) -> Option<&str> {
+    let cbor4ii::core::Value::Map(entries) = value else {
+        return None;
+    };
+    entries.iter().find_map(|(key, value)| match (key, value) {
+        (cbor4ii::core::Value::Text(key), cbor4ii::core::Value::Text(value)) if key == "type" => {
+            Some(value.as_str())
+        }
+        _ => None,
+    })
+}
+
+/// Checks that a value parses as a registered block type
+type TypeCheck = fn(&cbor4ii::core::Value) -> Result<(), Error>;
+
+fn check_type<T: DeserializeOwned>(value: &cbor4ii::core::Value) -> Result<(), Error> {
+    from_value::<T>(value)?;
+    Ok(())
+}
+
+/// Decodes blocks, dispatching on their `type` field.
+///
+/// Memos are always recognized. Apps may register their own block types,
+/// outside of the `szdt/` namespace.
+#[derive(Debug, Clone, Default)]
+pub struct BlockDecoder {
+    types: HashMap<String, TypeCheck>,
+}
+
+impl BlockDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an app-defined block type. Blocks declaring this type must
+    /// deserialize to `T`.
+    pub fn register<T: DeserializeOwned>(&mut self, block_type: &str) -> Result<(), Error> {
+        if block_type.starts_with(SZDT_TYPE_PREFIX) {
+            return Err(Error::InvalidBlock(
+                block_type.to_string(),
+                format!("Types in the {SZDT_TYPE_PREFIX} namespace are reserved"),
+            ));
+        }
+        self.types.insert(block_type.to_string(), check_type::<T>);
+        Ok(())
+    }
+
+    /// Is the block type known to this decoder?
+    pub fn is_registered(&self, block_type: &str) -> bool {
+        block_type == MEMO_TYPE || self.types.contains_key(block_type)
+    }
+
+    /// Decode a block.
+    /// Returns `Error::InvalidBlock` if the block claims a known type, but
+    /// fails to parse as that type.
+    pub fn decode(&self, value: cbor4ii::core::Value) -> Result<Block, Error> {
+        let Some(block_type) = block_type(&value) else {
+            return Ok(Block::Value(value));
+        };
+        if block_type == MEMO_TYPE {
+            let memo: Memo = from_value(&value)
+                .map_err(|err| Error::InvalidBlock(MEMO_TYPE.to_string(), err.to_string()))?;
+            return Ok(Block::Memo(Box::new(memo)));
+        }
+        match self.types.get(block_type) {
+            Some(check) => {
+                let block_type = block_type.to_string();
+                if let Err(err) = check(&value) {
+                    return Err(Error::InvalidBlock(block_type, err.to_string()));
+                }
+                Ok(Block::Typed(block_type, value))
+            }
+            None => Ok(Block::Value(value)),
+        }
+    }
+
+    /// Read and decode the next block from a sequence
+    pub fn read_block<R: BufRead>(&self, reader: &mut CborSeqReader<R>) -> Result<Block, Error> {
+        let value = reader.read_block::<cbor4ii::core::Value>()?;
+        self.decode(value)
+    }
+}
+
+/// Serialize a value to a CBOR value
+pub fn to_value<T: Serialize>(value: &T) -> Result<cbor4ii::core::Value, Error> {
+    let cbor_bytes = serde_cbor_core::to_vec(value)?;
+    let value = serde_cbor_core::from_slice(&cbor_bytes)?;
+    Ok(value)
+}
+
+/// Deserialize a CBOR value
+pub fn from_value<T: DeserializeOwned>(value: &cbor4ii::core::Value) -> Result<T, Error> {
+    let cbor_bytes = serde_cbor_core::to_vec(value)?;
+    let value = serde_cbor_core::from_slice(&cbor_bytes)?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::Bytes;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Post {
+        r#type: String,
+        title: String,
+    }
+
+    fn map(entries: Vec<(&str, cbor4ii::core::Value)>) -> cbor4ii::core::Value {
+        cbor4ii::core::Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (cbor4ii::core::Value::Text(key.to_string()), value))
+                .collect(),
+        )
+    }
+
+    fn text(value: &str) -> cbor4ii::core::Value {
+        cbor4ii::core::Value::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_decode_memo() {
+        let memo = Memo::for_body(Bytes(b"Hello".to_vec())).unwrap();
+        let block = BlockDecoder::new()
+            .decode(to_value(&memo).unwrap())
+            .unwrap();
+        assert_eq!(block, Block::Memo(Box::new(memo)));
+        assert_eq!(block.block_type(), Some(MEMO_TYPE));
+    }
+
+    #[test]
+    fn test_decode_invalid_memo_is_error() {
+        // Claims to be a memo, but is missing the protected headers
+        let value = map(vec![("type", text(MEMO_TYPE))]);
+        let result = BlockDecoder::new().decode(value);
+        assert!(
+            matches!(result, Err(Error::InvalidBlock(block_type, _)) if block_type == MEMO_TYPE)
+        );
+    }
+
+    #[test]
+    fn test_decode_memo_shaped_map_without_type() {
+        // Fits the shape of a memo, but doesn't claim to be one
+        let memo = Memo::for_body(Bytes(b"Hello".to_vec())).unwrap();
+        let cbor4ii::core::Value::Map(entries) = to_value(&memo).unwrap() else {
+            panic!("Memo should serialize to a map");
+        };
+        let value = cbor4ii::core::Value::Map(
+            entries
+                .into_iter()
+                .filter(|(key, _)| key != &text("type"))
+                .collect(),
+        );
+        let block = BlockDecoder::new().decode(value.clone()).unwrap();
+        assert_eq!(block, Block::Value(value));
+    }
+
+    #[test]
+    fn test_decode_registered_type() {
+        let mut decoder = BlockDecoder::new();
+        decoder.register::<Post>("example/post").unwrap();
+        assert!(decoder.is_registered("example/post"));
+
+        let value = map(vec![("type", text("example/post")), ("title", text("Hi"))]);
+        let block = decoder.decode(value.clone()).unwrap();
+        assert_eq!(block, Block::Typed("example/post".to_string(), value));
+
+        let invalid = map(vec![("type", text("example/post"))]);
+        assert!(matches!(
+            decoder.decode(invalid),
+            Err(Error::InvalidBlock(block_type, _)) if block_type == "example/post"
+        ));
+
+        // Unregistered types are passed through as values
+        let other = map(vec![("type", text("example/other"))]);
+        assert_eq!(decoder.decode(other.clone()).unwrap(), Block::Value(other));
+    }
+
+    #[test]
+    fn test_register_reserved_type() {
+        let mut decoder = BlockDecoder::new();
+        assert!(matches!(
+            decoder.register::<Post>("szdt/post"),
+            Err(Error::InvalidBlock(_, _))
+        ));
+    }
+}
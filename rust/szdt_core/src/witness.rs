@@ -0,0 +1,298 @@
+//! Co-signatures and witness signatures.
+//!
+//! The issuer signature is bound to the `iss` protected header. Additional
+//! parties may also sign over the protected headers, and store their signature,
+//! along with their DID, in the unprotected headers. Since unprotected headers
+//! aren't covered by the issuer signature, these signatures can be added after
+//! the fact, without invalidating the memo.
+//!
+//! - Co-signatures (`cosigs`) are made by parties attesting to the memo
+//!   alongside the issuer, e.g. co-authors.
+//! - Witness signatures (`witnesses`) are made by third parties attesting that
+//!   they have seen the memo, e.g. timestamping or transparency services.
+use crate::bytes::Bytes;
+use crate::did::DidKey;
+use crate::ed25519_key_material::Ed25519KeyMaterial;
+use crate::error::Error;
+use crate::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// A signature over the protected headers of a memo by a party other than
+/// the issuer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// DID of the signer
+    pub iss: DidKey,
+    /// Ed25519 signature over the hash of the protected headers
+    pub sig: Bytes,
+}
+
+impl Attestation {
+    /// Sign the hash of the protected headers
+    pub fn sign(key_material: &Ed25519KeyMaterial, protected_hash: &Hash) -> Result<Self, Error> {
+        let sig = key_material.sign(protected_hash.as_bytes())?;
+        Ok(Self {
+            iss: key_material.did(),
+            sig: Bytes(sig),
+        })
+    }
+
+    /// Verify the signature against the hash of the protected headers
+    pub fn verify(&self, protected_hash: &Hash) -> Result<(), Error> {
+        let key_material = Ed25519KeyMaterial::try_from(&self.iss)?;
+        key_material.verify(protected_hash.as_bytes(), &self.sig.0)?;
+        Ok(())
+    }
+}
+
+/// Add an attestation to a list, replacing any previous attestation by the
+/// same signer.
+pub(crate) fn upsert(attestations: &mut Vec<Attestation>, attestation: Attestation) {
+    match attestations.iter_mut().find(|a| a.iss == attestation.iss) {
+        Some(existing) => *existing = attestation,
+        None => attestations.push(attestation),
+    }
+}
+
+/// An attestation that failed verification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvalidAttestation {
+    pub iss: DidKey,
+    pub error: String,
+}
+
+/// Which parties validly signed a memo
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttestationReport {
+    /// DIDs with valid signatures
+    pub signed: Vec<DidKey>,
+    /// Signatures that failed verification
+    pub invalid: Vec<InvalidAttestation>,
+}
+
+impl AttestationReport {
+    /// Verify attestations against the hash of the protected headers
+    pub fn verify(attestations: &[Attestation], protected_hash: &Hash) -> Self {
+        let mut report = Self::default();
+        for attestation in attestations {
+            match attestation.verify(protected_hash) {
+                Ok(()) => {
+                    if !report.signed.contains(&attestation.iss) {
+                        report.signed.push(attestation.iss.clone());
+                    }
+                }
+                Err(err) => report.invalid.push(InvalidAttestation {
+                    iss: attestation.iss.clone(),
+                    error: err.to_string(),
+                }),
+            }
+        }
+        report
+    }
+
+    /// Did the given DID validly sign?
+    pub fn has_signed(&self, did: &DidKey) -> bool {
+        self.signed.contains(did)
+    }
+
+    /// Are all signatures valid?
+    pub fn is_valid(&self) -> bool {
+        self.invalid.is_empty()
+    }
+}
+
+/// A k-of-n policy, requiring valid signatures from at least `threshold` of
+/// the trusted `signers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawWitnessPolicy")]
+pub struct WitnessPolicy {
+    /// Minimum number of trusted signers required
+    threshold: usize,
+    /// DIDs of trusted signers, without duplicates
+    signers: Vec<DidKey>,
+}
+
+/// Witness policy fields, before they are checked
+#[derive(Deserialize)]
+struct RawWitnessPolicy {
+    threshold: usize,
+    signers: Vec<DidKey>,
+}
+
+impl TryFrom<RawWitnessPolicy> for WitnessPolicy {
+    type Error = Error;
+
+    fn try_from(raw: RawWitnessPolicy) -> Result<Self, Self::Error> {
+        WitnessPolicy::new(raw.threshold, raw.signers)
+    }
+}
+
+/// Result of evaluating a `WitnessPolicy`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyResult {
+    /// Is the policy satisfied?
+    pub satisfied: bool,
+    /// Minimum number of trusted signers required
+    pub threshold: usize,
+    /// Trusted signers with valid signatures
+    pub signed: Vec<DidKey>,
+    /// Trusted signers without valid signatures
+    pub missing: Vec<DidKey>,
+}
+
+impl WitnessPolicy {
+    /// Create a policy. Duplicate signers are removed, so each signer counts
+    /// once towards the threshold. The threshold must be between 1 and the
+    /// number of distinct signers.
+    pub fn new(threshold: usize, signers: Vec<DidKey>) -> Result<Self, Error> {
+        let mut unique: Vec<DidKey> = Vec::with_capacity(signers.len());
+        for did in signers {
+            if !unique.contains(&did) {
+                unique.push(did);
+            }
+        }
+        if threshold == 0 || threshold > unique.len() {
+            return Err(Error::InvalidWitnessPolicy(format!(
+                "Threshold must be between 1 and the number of distinct signers ({}). Got: {threshold}",
+                unique.len()
+            )));
+        }
+        Ok(Self {
+            threshold,
+            signers: unique,
+        })
+    }
+
+    /// Minimum number of trusted signers required
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// DIDs of trusted signers
+    pub fn signers(&self) -> &[DidKey] {
+        &self.signers
+    }
+
+    /// Evaluate the policy against a report.
+    /// Signatures from DIDs that aren't trusted signers are ignored.
+    pub fn evaluate(&self, report: &AttestationReport) -> PolicyResult {
+        let (signed, missing): (Vec<DidKey>, Vec<DidKey>) = self
+            .signers
+            .iter()
+            .cloned()
+            .partition(|did| report.has_signed(did));
+        PolicyResult {
+            satisfied: signed.len() >= self.threshold,
+            threshold: self.threshold,
+            signed,
+            missing,
+        }
+    }
+
+    /// Check the policy against a report, returning an error if it isn't
+    /// satisfied.
+    pub fn check(&self, report: &AttestationReport) -> Result<PolicyResult, Error> {
+        let result = self.evaluate(report);
+        if !result.satisfied {
+            return Err(Error::WitnessPolicyError(format!(
+                "Expected signatures from {} of {} signers. Got: {}",
+                self.threshold,
+                self.signers.len(),
+                result.signed.len()
+            )));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_key(seed: u8) -> Ed25519KeyMaterial {
+        Ed25519KeyMaterial::generate_from_entropy(&[seed; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_attestation_sign_and_verify() {
+        let key = create_test_key(1);
+        let hash = Hash::new(b"protected");
+        let attestation = Attestation::sign(&key, &hash).unwrap();
+        assert_eq!(attestation.iss, key.did());
+        attestation.verify(&hash).unwrap();
+        assert!(attestation.verify(&Hash::new(b"other")).is_err());
+    }
+
+    #[test]
+    fn test_policy_evaluate() {
+        let a = create_test_key(1);
+        let b = create_test_key(2);
+        let c = create_test_key(3);
+        let untrusted = create_test_key(4);
+        let hash = Hash::new(b"protected");
+
+        let attestations = vec![
+            Attestation::sign(&a, &hash).unwrap(),
+            Attestation::sign(&untrusted, &hash).unwrap(),
+            // Signed over the wrong hash
+            Attestation::sign(&c, &Hash::new(b"other")).unwrap(),
+        ];
+        let report = AttestationReport::verify(&attestations, &hash);
+        assert_eq!(report.signed, vec![a.did(), untrusted.did()]);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].iss, c.did());
+
+        let policy = WitnessPolicy::new(1, vec![a.did(), b.did(), c.did()]).unwrap();
+        let result = policy.evaluate(&report);
+        assert!(result.satisfied);
+        assert_eq!(result.signed, vec![a.did()]);
+        assert_eq!(result.missing, vec![b.did(), c.did()]);
+
+        // Untrusted signatures don't count towards the threshold
+        let policy = WitnessPolicy::new(2, vec![a.did(), b.did(), c.did()]).unwrap();
+        assert!(!policy.evaluate(&report).satisfied);
+        assert!(matches!(
+            policy.check(&report),
+            Err(Error::WitnessPolicyError(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_counts_each_signer_once() {
+        let a = create_test_key(1);
+        let b = create_test_key(2);
+        let hash = Hash::new(b"protected");
+        let report = AttestationReport::verify(&[Attestation::sign(&a, &hash).unwrap()], &hash);
+
+        // A repeated signer is one signer, so 2-of-n can't be met by one
+        // signature.
+        assert!(matches!(
+            WitnessPolicy::new(2, vec![a.did(), a.did()]),
+            Err(Error::InvalidWitnessPolicy(_))
+        ));
+        let policy = WitnessPolicy::new(2, vec![a.did(), a.did(), b.did()]).unwrap();
+        assert_eq!(policy.signers(), &[a.did(), b.did()]);
+        assert!(!policy.evaluate(&report).satisfied);
+
+        assert!(matches!(
+            WitnessPolicy::new(0, vec![a.did()]),
+            Err(Error::InvalidWitnessPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn test_upsert_replaces_by_signer() {
+        let a = create_test_key(1);
+        let mut attestations = vec![];
+        upsert(
+            &mut attestations,
+            Attestation::sign(&a, &Hash::new(b"1")).unwrap(),
+        );
+        upsert(
+            &mut attestations,
+            Attestation::sign(&a, &Hash::new(b"2")).unwrap(),
+        );
+        assert_eq!(attestations.len(), 1);
+        attestations[0].verify(&Hash::new(b"2")).unwrap();
+    }
+}
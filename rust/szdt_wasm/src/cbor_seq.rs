@@ -3,6 +3,7 @@ use cbor4ii::core::Value;
 use std::io::Cursor;
 use szdt_core::cbor_seq::{CborSeqReader as CoreCborSeqReader, CborSeqWriter as CoreCborSeqWriter};
 use szdt_core::error::Error as CoreError;
+use szdt_core::limits::Limits;
 use wasm_bindgen::prelude::*;
 
 /// WASM wrapper for reading CBOR sequences
@@ -11,6 +12,7 @@ pub struct CborSeqReader {
     // Store the data and current position for JavaScript compatibility
     data: Vec<u8>,
     position: usize,
+    limits: Limits,
 }
 
 #[wasm_bindgen]
@@ -21,9 +23,32 @@ impl CborSeqReader {
         Self {
             data: data.to_vec(),
             position: 0,
+            limits: Limits::default(),
         }
     }
 
+    /// Set the resource limits enforced when reading blocks.
+    /// Sizes are in bytes. Bodies are blocks that are a single byte string.
+    /// `max_file_size` caps the size of files reassembled from chunks, and is
+    /// a `BigInt`, since files may be larger than 4 GiB.
+    #[wasm_bindgen]
+    pub fn set_limits(
+        &mut self,
+        max_block_size: usize,
+        max_body_size: usize,
+        max_depth: usize,
+        max_entries: usize,
+        max_file_size: u64,
+    ) {
+        self.limits = Limits {
+            max_block_size: max_block_size as u64,
+            max_body_size: max_body_size as u64,
+            max_depth,
+            max_entries: max_entries as u64,
+            max_file_size,
+        };
+    }
+
     /// Read the next memo from the sequence
     #[wasm_bindgen]
     pub fn read_memo(&mut self) -> Result<Memo, JsError> {
@@ -33,7 +58,7 @@ impl CborSeqReader {
 
         let remaining_data = &self.data[self.position..];
         let cursor = Cursor::new(remaining_data);
-        let mut reader = CoreCborSeqReader::new(cursor);
+        let mut reader = CoreCborSeqReader::new(cursor).with_limits(self.limits);
 
         let core_memo: szdt_core::memo::Memo = reader.read_block().map_err(|e| match e {
             CoreError::Eof => JsError::new("End of sequence reached"),
@@ -41,8 +66,7 @@ impl CborSeqReader {
         })?;
 
         // Update position - we need to track how many bytes were consumed
-        let consumed = remaining_data.len() - reader.into_inner().into_inner().len();
-        self.position += consumed;
+        self.position += reader.position();
 
         Ok(Memo::from_core(core_memo))
     }
@@ -56,7 +80,7 @@ impl CborSeqReader {
 
         let remaining_data = &self.data[self.position..];
         let cursor = Cursor::new(remaining_data);
-        let mut reader = CoreCborSeqReader::new(cursor);
+        let mut reader = CoreCborSeqReader::new(cursor).with_limits(self.limits);
 
         // Read as raw CBOR value and serialize back to bytes
         let value: Value = reader.read_block().map_err(|e| match e {
@@ -65,8 +89,7 @@ impl CborSeqReader {
         })?;
 
         // Update position
-        let consumed = remaining_data.len() - reader.into_inner().into_inner().len();
-        self.position += consumed;
+        self.position += reader.position();
 
         // Serialize the value back to CBOR
         let cbor_bytes =
@@ -35,6 +35,14 @@ impl Hash {
         })
     }
 
+    /// Parse a hash from a string. Accepts base32 (as printed by `toString`),
+    /// hex, a multibase-encoded Blake3 multihash, or a CIDv1.
+    #[wasm_bindgen]
+    pub fn parse(input: &str) -> Result<Hash, JsError> {
+        let inner = CoreHash::parse(input)?;
+        Ok(Self { inner })
+    }
+
     /// Get the hash as a byte array
     #[wasm_bindgen]
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -49,6 +57,24 @@ impl Hash {
         self.inner.to_string()
     }
 
+    /// Get the hash as a lowercase hex string
+    #[wasm_bindgen]
+    pub fn to_hex(&self) -> String {
+        self.inner.to_hex()
+    }
+
+    /// Get the hash as Blake3 multihash bytes
+    #[wasm_bindgen]
+    pub fn to_multihash(&self) -> Vec<u8> {
+        self.inner.to_multihash()
+    }
+
+    /// Get the hash as a CIDv1 string, for use with IPFS tooling
+    #[wasm_bindgen]
+    pub fn to_cid(&self) -> String {
+        self.inner.to_cid()
+    }
+
     /// Check if two hashes are equal
     #[wasm_bindgen]
     pub fn equals(&self, other: &Hash) -> bool {
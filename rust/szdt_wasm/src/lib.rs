@@ -25,6 +25,7 @@ pub mod ed25519_key_material;
 pub mod hash;
 pub mod memo;
 pub mod mnemonic;
+pub mod url;
 
 // Re-export main types for easy use
 pub use cbor_seq::{CborSeqReader, CborSeqWriter};
@@ -33,6 +34,7 @@ pub use ed25519_key_material::Ed25519KeyMaterial;
 pub use hash::Hash;
 pub use memo::Memo;
 pub use mnemonic::Mnemonic;
+pub use url::SzdtUrl;
 
 // Utility function to initialize the WASM module
 #[wasm_bindgen(start)]
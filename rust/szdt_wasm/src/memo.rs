@@ -1,7 +1,11 @@
 use crate::ed25519_key_material::Ed25519KeyMaterial;
 use crate::hash::Hash;
+use szdt_core::bytes::Bytes;
+use szdt_core::content_encoding::decode_body;
 use szdt_core::error::Error as CoreError;
+use szdt_core::limits::Limits;
 use szdt_core::memo::Memo as CoreMemo;
+use szdt_core::validation::ValidationOptions;
 use wasm_bindgen::prelude::*;
 
 /// WASM wrapper for SZDT memo operations
@@ -52,6 +56,41 @@ impl Memo {
         Ok(())
     }
 
+    /// Co-sign the memo with the given key material.
+    /// Co-signatures are stored in the unprotected headers, alongside the
+    /// issuer signature.
+    #[wasm_bindgen]
+    pub fn cosign(&mut self, key_material: &Ed25519KeyMaterial) -> Result<(), JsError> {
+        self.inner
+            .cosign(key_material.as_core())
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(())
+    }
+
+    /// Witness the memo with the given key material.
+    /// Witness signatures are stored in the unprotected headers, alongside the
+    /// issuer signature.
+    #[wasm_bindgen]
+    pub fn witness(&mut self, key_material: &Ed25519KeyMaterial) -> Result<(), JsError> {
+        self.inner
+            .witness(key_material.as_core())
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(())
+    }
+
+    /// Verify witness signatures, returning `{ signed, invalid }`, where
+    /// `signed` is the DIDs of witnesses that validly signed.
+    #[wasm_bindgen]
+    pub fn verify_witnesses(&self) -> Result<JsValue, JsError> {
+        let report = self
+            .inner
+            .verify_witnesses()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        let js_value =
+            serde_wasm_bindgen::to_value(&report).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(js_value)
+    }
+
     /// Verify the memo signature
     #[wasm_bindgen]
     pub fn verify(&self) -> Result<bool, JsError> {
@@ -66,12 +105,46 @@ impl Memo {
     /// Validate the memo (verify signature and check time bounds)
     #[wasm_bindgen]
     pub fn validate(&self, timestamp: Option<u64>) -> Result<bool, JsError> {
-        match self.inner.validate(timestamp) {
+        let options = ValidationOptions {
+            time: timestamp,
+            ..Default::default()
+        };
+        match self.inner.validate(&options) {
             Ok(()) => Ok(true),
             Err(_) => Ok(false),
         }
     }
 
+    /// Validate the memo with validation options
+    /// (`time`, `clock_skew`, `max_age`, `iat_not_in_future`, `required_headers`,
+    /// `required_cosigners`, `witness_policy: { threshold, signers }`)
+    #[wasm_bindgen]
+    pub fn validate_with_options(&self, options: JsValue) -> Result<bool, JsError> {
+        let options = validation_options_from_js(options)?;
+        match self.inner.validate(&options) {
+            Ok(()) => Ok(true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Validate the memo, returning a report of the result of every check.
+    /// Takes optional validation options (see `validate_with_options`).
+    /// If a body hash is given, it is checked against the memo's body hash.
+    #[wasm_bindgen]
+    pub fn validation_report(
+        &self,
+        options: JsValue,
+        body_hash: Option<Hash>,
+    ) -> Result<JsValue, JsError> {
+        let options = validation_options_from_js(options)?;
+        let report = self
+            .inner
+            .validation_report(&options, body_hash.as_ref().map(|hash| hash.as_core()));
+        let js_value =
+            serde_wasm_bindgen::to_value(&report).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(js_value)
+    }
+
     /// Check if the memo is expired
     #[wasm_bindgen]
     pub fn is_expired(&self, timestamp: Option<u64>) -> bool {
@@ -95,8 +168,7 @@ impl Memo {
     /// Deserialize a memo from CBOR bytes
     #[wasm_bindgen]
     pub fn from_cbor(data: &[u8]) -> Result<Memo, JsError> {
-        let inner: CoreMemo =
-            serde_cbor_core::from_slice(data).map_err(|e| JsError::new(&e.to_string()))?;
+        let inner = CoreMemo::from_slice(data).map_err(|e| JsError::new(&e.to_string()))?;
         Ok(Self { inner })
     }
 
@@ -109,6 +181,17 @@ impl Memo {
         }
     }
 
+    /// Decode a body according to the memo's `content-encoding` header.
+    /// Bodies without a `content-encoding` are returned as-is. Check the body
+    /// against the memo (see `checksum`) before decoding it. Chunked bodies
+    /// are rejected, since they must be reassembled from their chunks (see
+    /// `SzdtUrl.resolve`).
+    #[wasm_bindgen]
+    pub fn decode_body(&self, body: &[u8]) -> Result<Vec<u8>, JsError> {
+        let decoded = decode_body(&self.inner, Bytes(body.to_vec()), &Limits::default())?;
+        Ok(decoded.0)
+    }
+
     /// Get the body hash
     #[wasm_bindgen]
     pub fn body_hash(&self) -> Hash {
@@ -176,6 +259,15 @@ impl Memo {
     }
 }
 
+/// Read validation options from a JavaScript object, using defaults for
+/// `undefined` or `null`.
+pub(crate) fn validation_options_from_js(options: JsValue) -> Result<ValidationOptions, JsError> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(ValidationOptions::default());
+    }
+    serde_wasm_bindgen::from_value(options).map_err(|e| JsError::new(&e.to_string()))
+}
+
 // Internal conversion methods for use within the WASM crate
 impl Memo {
     pub fn from_core(core: CoreMemo) -> Self {
@@ -0,0 +1,128 @@
+use crate::did_key::DidKey;
+use crate::hash::Hash;
+use crate::memo::{Memo, validation_options_from_js};
+use std::io::Cursor;
+use szdt_core::bytes::Bytes;
+use szdt_core::cbor_seq::CborSeqReader;
+use szdt_core::chunked::read_file;
+use szdt_core::dag::{DagEntry, DagIndex};
+use szdt_core::limits::Limits;
+use szdt_core::url::SzdtUrl as CoreSzdtUrl;
+use wasm_bindgen::prelude::*;
+
+/// WASM wrapper for `szdt:` URLs
+#[wasm_bindgen]
+pub struct SzdtUrl {
+    inner: CoreSzdtUrl,
+}
+
+#[wasm_bindgen]
+impl SzdtUrl {
+    /// Parse a `szdt:` URL
+    #[wasm_bindgen]
+    pub fn parse(url: &str) -> Result<SzdtUrl, JsError> {
+        let inner = CoreSzdtUrl::parse(url)?;
+        Ok(Self { inner })
+    }
+
+    /// URL for content with the given hash
+    #[wasm_bindgen]
+    pub fn for_hash(hash: &Hash) -> SzdtUrl {
+        Self {
+            inner: CoreSzdtUrl::for_hash(*hash.as_core()),
+        }
+    }
+
+    /// URL for a file at a path, with the given hash
+    #[wasm_bindgen]
+    pub fn for_hash_path(hash: &Hash, path: &str) -> SzdtUrl {
+        Self {
+            inner: CoreSzdtUrl::for_hash_path(*hash.as_core(), path),
+        }
+    }
+
+    /// URL for a file at a path, signed by the given issuer
+    #[wasm_bindgen]
+    pub fn for_iss_path(iss: &DidKey, path: &str) -> SzdtUrl {
+        Self {
+            inner: CoreSzdtUrl::for_iss_path(iss.as_core().clone(), path),
+        }
+    }
+
+    /// Get the issuer, if any
+    #[wasm_bindgen]
+    pub fn iss(&self) -> Option<DidKey> {
+        self.inner.iss().cloned().map(DidKey::from_core)
+    }
+
+    /// Get the hash, if any
+    #[wasm_bindgen]
+    pub fn hash(&self) -> Option<Hash> {
+        self.inner.hash().copied().map(Hash::from_core)
+    }
+
+    /// Get the path, if any
+    #[wasm_bindgen]
+    pub fn path(&self) -> Option<String> {
+        self.inner.path().map(str::to_string)
+    }
+
+    /// Get the URL as a string
+    // Allow inherent_to_string so we can expose `.toString()` via wasm_bindgen
+    #[allow(clippy::inherent_to_string)]
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string(&self) -> String {
+        self.inner.to_string()
+    }
+
+    /// Resolve the URL against an archive, returning the latest matching
+    /// memo that is valid. Takes optional validation options (see
+    /// `Memo.validate_with_options`).
+    #[wasm_bindgen]
+    pub fn resolve_memo(&self, archive: &[u8], options: JsValue) -> Result<Memo, JsError> {
+        let index = read_index(archive)?;
+        let entry = self.resolve_entry(&index, options)?;
+        Ok(Memo::from_core(entry.memo))
+    }
+
+    /// Resolve the URL against an archive, returning the decoded bytes of the
+    /// latest matching file with a valid memo. Chunked files are reassembled
+    /// from chunks in the archive. Takes optional validation options (see
+    /// `Memo.validate_with_options`).
+    #[wasm_bindgen]
+    pub fn resolve(&self, archive: &[u8], options: JsValue) -> Result<Vec<u8>, JsError> {
+        let index = read_index(archive)?;
+        let entry = self.resolve_entry(&index, options)?;
+        match entry.body {
+            Some(cbor4ii::core::Value::Bytes(bytes)) => {
+                let body = read_file(
+                    &entry.memo,
+                    Bytes(bytes.clone()),
+                    &Limits::default(),
+                    |hash| index.get_as::<Bytes>(hash),
+                )?;
+                Ok(body.0)
+            }
+            Some(_) => Err(JsError::new("Unexpected body. Expected bytes.")),
+            None => Err(JsError::new("File body is absent from archive")),
+        }
+    }
+}
+
+impl SzdtUrl {
+    fn resolve_entry<'a>(
+        &self,
+        index: &'a DagIndex,
+        options: JsValue,
+    ) -> Result<DagEntry<'a>, JsError> {
+        let options = validation_options_from_js(options)?;
+        self.inner
+            .resolve(index, &options)?
+            .ok_or_else(|| JsError::new(&format!("No file found for {}", self.inner)))
+    }
+}
+
+fn read_index(archive: &[u8]) -> Result<DagIndex, JsError> {
+    let mut reader = CborSeqReader::new(Cursor::new(archive));
+    Ok(DagIndex::read(&mut reader)?)
+}